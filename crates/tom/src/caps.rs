@@ -1,20 +1,73 @@
 //! FIXME: write short doc here
 
 use languageserver_types::{
-    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncOptions, TextDocumentSyncKind,
+    InitializeResult, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncOptions,
+    TextDocumentSyncKind, Registration, CodeActionProviderCapability, RenameProviderCapability,
+    RenameOptions,
 };
 
+use crate::{req, semantic_tokens};
+
 pub fn server_capabilities() -> ServerCapabilities {
     ServerCapabilities {
         text_document_sync: Some(TextDocumentSyncCapability::Options(
             TextDocumentSyncOptions {
                 open_close: Some(true),
-                change: Some(TextDocumentSyncKind::Full),
+                change: Some(TextDocumentSyncKind::Incremental),
                 will_save: None,
                 will_save_wait_until: None,
                 save: None,
             },
         )),
+        hover_provider: Some(true),
+        definition_provider: Some(true),
+        references_provider: Some(true),
+        document_symbol_provider: Some(true),
+        workspace_symbol_provider: Some(true),
+        document_formatting_provider: Some(true),
+        document_range_formatting_provider: Some(true),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        rename_provider: Some(RenameProviderCapability::Options(RenameOptions {
+            prepare_provider: Some(true),
+        })),
         ..Default::default()
     }
 }
+
+/// The result of `initialize`. `ServerCapabilities` predates semantic
+/// tokens, so their provider is added to the JSON, unless the client
+/// registers them dynamically after the initialization.
+pub fn initialize_result(can_register_semantic_tokens: bool) -> serde_json::Value {
+    let result = InitializeResult {
+        capabilities: server_capabilities(),
+    };
+    let mut result = serde_json::to_value(result).unwrap();
+    if !can_register_semantic_tokens {
+        result["capabilities"]["semanticTokensProvider"] =
+            serde_json::to_value(semantic_tokens_options()).unwrap();
+    }
+    result
+}
+
+pub fn semantic_tokens_registration() -> Registration {
+    let options = req::SemanticTokensRegistrationOptions {
+        document_selector: None,
+        options: semantic_tokens_options(),
+    };
+    Registration {
+        id: "semantic-tokens".to_string(),
+        method: "textDocument/semanticTokens".to_string(),
+        register_options: Some(serde_json::to_value(options).unwrap()),
+    }
+}
+
+fn semantic_tokens_options() -> req::SemanticTokensOptions {
+    req::SemanticTokensOptions {
+        legend: req::SemanticTokensLegend {
+            token_types: semantic_tokens::TOKEN_TYPES,
+            token_modifiers: semantic_tokens::TOKEN_MODIFIERS,
+        },
+        range: true,
+        full: req::SemanticTokensFullOptions { delta: true },
+    }
+}
@@ -0,0 +1,199 @@
+//! Sorting and reordering as code actions.
+//!
+//! The actions depend on where the cursor is: the values of the array and
+//! the entries of the table around it can be sorted, and the tables of the
+//! document can be reordered if `.tomfmt.toml` gives their order. Only the
+//! actions which would change something are offered, and none for a
+//! document with errors.
+
+use languageserver_types::code_action_kind::{REFACTOR_REWRITE, SOURCE};
+use tom_syntax::{ast, symbol::*, Edit, SyntaxNodeRef, TextEdit, TextRange, TextUnit, TomlDoc};
+
+#[derive(Debug)]
+pub(crate) struct Action {
+    pub(crate) title: &'static str,
+    pub(crate) kind: &'static str,
+    pub(crate) edit: TextEdit,
+}
+
+pub(crate) fn code_actions(doc: &TomlDoc, range: TextRange, table_order: &[String]) -> Vec<Action> {
+    let mut res = Vec::new();
+    if !doc.errors().is_empty() {
+        return res;
+    }
+    let node = doc.cst().covering_node(range);
+    if let Some(array) = node.ancestors().find_map(ast::Array::cast) {
+        res.extend(action(doc, "Sort array values", REFACTOR_REWRITE, |edit| {
+            edit.sort_values(array)
+        }));
+    }
+
+    let owner = node
+        .ancestors()
+        .find(|it| [DICT, TABLE, ARRAY_TABLE].contains(&it.symbol()))
+        .unwrap_or_else(|| doc.cst());
+    let sorted = action(doc, "Sort entries", REFACTOR_REWRITE, |edit| {
+        sort_entries(edit, owner, false)
+    });
+    let by_groups = action(
+        doc,
+        "Sort entries within groups",
+        REFACTOR_REWRITE,
+        |edit| sort_entries(edit, owner, true),
+    );
+    // Sorting within groups is offered only if it differs from sorting.
+    let has_groups = match (&sorted, &by_groups) {
+        (Some(sorted), Some(by_groups)) => sorted.edit != by_groups.edit,
+        _ => sorted.is_some(),
+    };
+    res.extend(sorted);
+    if has_groups {
+        res.extend(by_groups);
+    }
+
+    if !table_order.is_empty() {
+        let order: Vec<&str> = table_order.iter().map(String::as_str).collect();
+        // An invalid order changes nothing.
+        res.extend(action(doc, "Reorder tables", SOURCE, |edit| {
+            let _ = edit.reorder_tables(&order);
+        }));
+    }
+    res
+}
+
+fn sort_entries<'a>(edit: &mut Edit<'a>, owner: SyntaxNodeRef<'a>, by_groups: bool) {
+    if let Some(dict) = ast::Dict::cast(owner) {
+        edit.sort_entries(dict, by_groups);
+    } else if let Some(table) = ast::Table::cast(owner) {
+        edit.sort_entries(table, by_groups);
+    } else if let Some(table) = ast::ArrayTable::cast(owner) {
+        edit.sort_entries(table, by_groups);
+    } else if let Some(doc) = ast::Doc::cast(owner) {
+        edit.sort_entries(doc, by_groups);
+    }
+}
+
+/// Returns the action if it changes the document and the result has no
+/// errors.
+fn action<'a>(
+    doc: &'a TomlDoc,
+    title: &'static str,
+    kind: &'static str,
+    f: impl FnOnce(&mut Edit<'a>),
+) -> Option<Action> {
+    let mut edit = doc.start_edit();
+    f(&mut edit);
+    let new_doc = edit.finish_edit_full_reparse();
+    if !new_doc.errors().is_empty() {
+        return None;
+    }
+    let new_text = new_doc.cst().get_text();
+    let edit = text_edit(&doc.cst().get_text(), &new_text)?;
+    Some(Action { title, kind, edit })
+}
+
+/// Returns the edit which replaces only the changed middle of the text.
+fn text_edit(old: &str, new: &str) -> Option<TextEdit> {
+    if old == new {
+        return None;
+    }
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let mut suffix = old[prefix..]
+        .bytes()
+        .rev()
+        .zip(new[prefix..].bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) {
+        suffix -= 1;
+    }
+    let delete = TextRange::from_to(
+        TextUnit::from_usize(prefix),
+        TextUnit::from_usize(old.len() - suffix),
+    );
+    Some(TextEdit::replace(delete, &new[prefix..new.len() - suffix]))
+}
+
+#[test]
+fn test_code_actions() {
+    fn check(text: &str, table_order: &[&str], expected: &[(&str, &str)]) {
+        let offset = text.find('|').unwrap();
+        let text = text.replacen('|', "", 1);
+        let doc = TomlDoc::new(&text);
+        let range = TextRange::offset_len(TextUnit::from_usize(offset), 0.into());
+        let table_order: Vec<String> = table_order.iter().map(|it| it.to_string()).collect();
+        let actual: Vec<(&str, String)> = code_actions(&doc, range, &table_order)
+            .into_iter()
+            .map(|action| (action.title, action.edit.apply(&text)))
+            .collect();
+        let expected: Vec<(&str, String)> = expected
+            .iter()
+            .map(|&(title, text)| (title, text.to_string()))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    check(
+        "b = 1\n|a = 2\n",
+        &[],
+        &[("Sort entries", "a = 2\nb = 1\n")],
+    );
+    check("a = 1\n|b = 2\n", &[], &[]);
+    check(
+        "[t]\nb = 1\na = 1\n\nd = 1\n|c = 1\n",
+        &[],
+        &[("Sort entries", "[t]\na = 1\nb = 1\n\nc = 1\nd = 1\n")],
+    );
+    check(
+        "[t]\nd = 1\na = 1\n\nc = 1\n|b = 1\n",
+        &[],
+        &[
+            ("Sort entries", "[t]\na = 1\nb = 1\n\nc = 1\nd = 1\n"),
+            (
+                "Sort entries within groups",
+                "[t]\na = 1\nd = 1\n\nb = 1\nc = 1\n",
+            ),
+        ],
+    );
+    check(
+        "a = [3, |2, 1]\nc = { b = 1, a = 2 }\n",
+        &[],
+        &[("Sort array values", "a = [1, 2, 3]\nc = { b = 1, a = 2 }\n")],
+    );
+    check(
+        "a = [3, 2, 1]\nc = { b = 1, |a = 2 }\n",
+        &[],
+        &[("Sort entries", "a = [3, 2, 1]\nc = { a = 2, b = 1 }\n")],
+    );
+    check(
+        "[b]\n|[a]\n",
+        &["a", "b"],
+        &[("Reorder tables", "[a]\n[b]\n")],
+    );
+    // `[a.x.z]` stays after the dotted keys which define `a.x`.
+    check("[a]\nx.y = 1\n|[a.x.z]\n", &["a.x.z"], &[]);
+    check("b = 1\n|a = \n", &[], &[]);
+}
+
+#[test]
+fn test_text_edit() {
+    fn check(old: &str, new: &str) {
+        let edit = text_edit(old, new).unwrap();
+        assert_eq!(edit.apply(old), new);
+    }
+
+    assert_eq!(text_edit("abc", "abc"), None);
+    check("abc", "axc");
+    check("abc", "abcd");
+    check("abc", "");
+    check("aa", "aaa");
+    check("ä", "ö");
+    check("xäy", "xöy");
+}
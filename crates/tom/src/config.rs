@@ -0,0 +1,91 @@
+//! Formatting options and the order of tables from `.tomfmt.toml`.
+//!
+//! The options of a file come from the `.tomfmt.toml` in its directory or
+//! the nearest parent directory, and the missing ones are the defaults.
+
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+use tom_syntax::fmt::FmtOptions;
+
+const CONFIG_FILE: &str = ".tomfmt.toml";
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    max_width: Option<usize>,
+    indent_width: Option<usize>,
+    indent_entries: Option<bool>,
+    blank_lines: Option<usize>,
+    trailing_comma: Option<bool>,
+    align_comments: Option<bool>,
+    /// The dotted keys of the tables, in the order of the "Reorder tables"
+    /// code action.
+    table_order: Option<Vec<String>>,
+}
+
+/// Returns the options of the file, or a message about an invalid config.
+pub(crate) fn fmt_options(path: &Path) -> Result<FmtOptions, String> {
+    let config = load(path)?;
+    let default = FmtOptions::default();
+    Ok(FmtOptions {
+        max_width: config.max_width.unwrap_or(default.max_width),
+        indent_width: config.indent_width.unwrap_or(default.indent_width),
+        indent_entries: config.indent_entries.unwrap_or(default.indent_entries),
+        blank_lines: config.blank_lines.unwrap_or(default.blank_lines),
+        trailing_comma: config.trailing_comma.unwrap_or(default.trailing_comma),
+        align_comments: config.align_comments.unwrap_or(default.align_comments),
+    })
+}
+
+/// Returns the order of tables for the file, empty if there's none.
+pub(crate) fn table_order(path: &Path) -> Result<Vec<String>, String> {
+    Ok(load(path)?.table_order.unwrap_or_default())
+}
+
+fn load(path: &Path) -> Result<Config, String> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let config_path = match path
+        .ancestors()
+        .skip(1)
+        .map(|it| it.join(CONFIG_FILE))
+        .find(|it| it.is_file())
+    {
+        Some(it) => it,
+        None => return Ok(Config::default()),
+    };
+    fs::read_to_string(&config_path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| tom_syntax::de::from_str(&text).map_err(|e| e.to_string()))
+        .map_err(|e| format!("invalid {}: {}", config_path.display(), e))
+}
+
+#[test]
+fn test_fmt_options() {
+    let dir = std::env::temp_dir().join(format!("tom-config-{}", std::process::id()));
+    let nested = dir.join("nested");
+    fs::create_dir_all(&nested).unwrap();
+    let file = nested.join("Cargo.toml");
+
+    fs::write(
+        dir.join(CONFIG_FILE),
+        "max_width = 100\nindent_entries = true\ntable_order = [\"package\"]\n",
+    )
+    .unwrap();
+    let options = fmt_options(&file).unwrap();
+    assert_eq!(
+        options,
+        FmtOptions {
+            max_width: 100,
+            indent_entries: true,
+            ..FmtOptions::default()
+        }
+    );
+    assert_eq!(table_order(&file).unwrap(), vec!["package".to_string()]);
+
+    fs::write(nested.join(CONFIG_FILE), "max_witdh = 100\n").unwrap();
+    let error = fmt_options(&file).unwrap_err();
+    assert!(error.contains("max_witdh"), "{}", error);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
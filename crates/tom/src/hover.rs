@@ -0,0 +1,123 @@
+//! Hover information for keys.
+//!
+//! A key shows the full path of the value it defines or extends, the type
+//! and the decoded value, so `0x10` is shown as `16`, and the comments above
+//! its entry or its `[table]`. Tables and arrays show only their type. There
+//! is no hover in a document with errors.
+
+use tom_syntax::{ast, IntoValue, Item, TextRange, TomlDateTime, TomlDoc};
+
+/// Returns the markdown for the `key`, and the range it is for.
+pub(crate) fn hover(doc: &TomlDoc, key: ast::Key) -> Option<(TextRange, String)> {
+    if !doc.errors().is_empty() {
+        return None;
+    }
+    let path = key.path()?;
+    let item = doc.get(&path);
+    let mut res = format!("```toml\n{}", path);
+    if let Some(item) = &item {
+        if !matches!(item, Item::Map(_) | Item::Array(_)) {
+            if let Ok(text) = item.value_text() {
+                res += &format!(" = {}", text);
+            }
+        }
+    }
+    res += "\n```";
+    if let Some(item) = &item {
+        res += &format!("\n\n{}", type_name(item));
+    }
+    let comments: Vec<&str> = comments(key).iter().map(|it| it.content()).collect();
+    if !comments.is_empty() {
+        res += "\n\n";
+        res += &comments.join("\n");
+    }
+    Some((key.syntax().range(), res))
+}
+
+fn type_name(item: &Item) -> &'static str {
+    match item {
+        Item::Map(_) => "table",
+        Item::Array(_) => "array",
+        Item::Integer(_) => "integer",
+        Item::Float(_) => "float",
+        Item::Bool(_) => "boolean",
+        Item::String(_) => "string",
+        Item::DateTime(TomlDateTime::OffsetDateTime(..)) => "offset date-time",
+        Item::DateTime(TomlDateTime::LocalDateTime(..)) => "local date-time",
+        Item::DateTime(TomlDateTime::LocalDate(_)) => "local date",
+        Item::DateTime(TomlDateTime::LocalTime(_)) => "local time",
+    }
+}
+
+/// The comments above the entry or the table whose last key is `key`.
+fn comments(key: ast::Key) -> Vec<ast::Comment> {
+    let parent = match key.syntax().parent() {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    let is_last = parent
+        .children()
+        .filter_map(ast::Key::cast)
+        .last()
+        .map(|it| it.syntax())
+        == Some(key.syntax());
+    if !is_last {
+        return Vec::new();
+    }
+    if let Some(entry) = ast::Entry::cast(parent) {
+        return entry.leading_comments().collect();
+    }
+    let table = match parent.parent() {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+    if let Some(table) = ast::Table::cast(table) {
+        table.doc_comments().collect()
+    } else if let Some(table) = ast::ArrayTable::cast(table) {
+        table.doc_comments().collect()
+    } else {
+        Vec::new()
+    }
+}
+
+#[test]
+fn test_hover() {
+    fn check(text: &str, expected: &str) {
+        let offset = text.find('|').unwrap();
+        let text = text.replacen('|', "", 1);
+        let doc = TomlDoc::new(&text);
+        let key = crate::key_at(&doc, tom_syntax::TextUnit::from_usize(offset)).unwrap();
+        match hover(&doc, key) {
+            Some((range, actual)) => {
+                assert_eq!(range, key.syntax().range());
+                assert_eq!(actual, expected);
+            }
+            None => assert_eq!("", expected),
+        }
+    }
+
+    check(
+        "[a.b]\n# The answer.\n# Really.\nc.|d = 0x2a\n",
+        "```toml\na.b.c.d = 42\n```\n\ninteger\n\nThe answer.\nReally.",
+    );
+    check(
+        "[a.b]\n# Not about c.\n|c.d = 1\n",
+        "```toml\na.b.c\n```\n\ntable",
+    );
+    check(
+        "[[servers]]\n[[servers]]\n\n# The main one.\n[[ser|vers]]\nhost = 'x'\n",
+        "```toml\nservers[2]\n```\n\ntable\n\nThe main one.",
+    );
+    check(
+        "[[servers]]\n[[servers]]\nhost| = \"x\\u0041\"\n",
+        "```toml\nservers[1].host = \"xA\"\n```\n\nstring",
+    );
+    check(
+        "a = 1979-05-27T07:32:00Z\nb| = 07:32:00\n",
+        "```toml\nb = 07:32:00\n```\n\nlocal time",
+    );
+    check("|a = [1, 2]\n", "```toml\na\n```\n\narray");
+    check("|a = ?\n", "");
+    check("|a = 1\n[b.", "");
+    check("a = 1\n[b.|]\n", "");
+}
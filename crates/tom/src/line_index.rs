@@ -1,11 +1,15 @@
 //! FIXME: write short doc here
 
-use crate::TextUnit;
+use crate::{TextEdit, TextUnit};
 use superslice::Ext;
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct LineIndex {
     newlines: Vec<TextUnit>,
+    /// The characters of each line which take more UTF-8 bytes than UTF-16
+    /// code units, that is all the non-ASCII ones.
+    wide_chars: Vec<Vec<WideChar>>,
+    len: TextUnit,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -14,17 +18,52 @@ pub struct LineCol {
     pub col: TextUnit,
 }
 
+/// A non-ASCII character, the range of its UTF-8 column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct WideChar {
+    start: TextUnit,
+    end: TextUnit,
+}
+
+impl WideChar {
+    fn len_utf16(self) -> u32 {
+        if u32::from(self.end - self.start) == 4 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// The number of UTF-8 bytes above the UTF-16 code units.
+    fn extra_len(self) -> u32 {
+        u32::from(self.end - self.start) - self.len_utf16()
+    }
+}
+
 impl LineIndex {
     pub fn new(text: &str) -> LineIndex {
         let mut newlines = vec![0.into()];
+        let mut wide_chars = vec![Vec::new()];
         let mut curr = 0.into();
         for c in text.chars() {
-            curr += TextUnit::of_char(c);
+            let len = TextUnit::of_char(c);
             if c == '\n' {
-                newlines.push(curr);
+                newlines.push(curr + len);
+                wide_chars.push(Vec::new());
+            } else if !c.is_ascii() {
+                let start = curr - *newlines.last().unwrap();
+                wide_chars.last_mut().unwrap().push(WideChar {
+                    start,
+                    end: start + len,
+                });
             }
+            curr += len;
+        }
+        LineIndex {
+            newlines,
+            wide_chars,
+            len: curr,
         }
-        LineIndex { newlines }
     }
 
     pub fn line_col(&self, offset: TextUnit) -> LineCol {
@@ -37,9 +76,98 @@ impl LineIndex {
         }
     }
 
-    pub fn offset(&self, line_col: LineCol) -> TextUnit {
-        //FIXME: return Result
-        self.newlines[line_col.line as usize] + line_col.col
+    /// Returns the column of `line_col` in UTF-16 code units, the way the
+    /// language server protocol counts them.
+    pub fn utf16_col(&self, line_col: LineCol) -> u32 {
+        let extra: u32 = self.wide_chars[line_col.line as usize]
+            .iter()
+            .take_while(|c| c.end <= line_col.col)
+            .map(|c| c.extra_len())
+            .sum();
+        u32::from(line_col.col) - extra
+    }
+
+    /// Returns the offset of the UTF-16 column `col` of the `line`. Positions
+    /// past the end of the line or of the text are moved back to it, and
+    /// positions inside a character to its start.
+    pub fn offset_utf16(&self, line: u32, col: u32) -> TextUnit {
+        let line = line as usize;
+        if line >= self.newlines.len() {
+            return self.len;
+        }
+        let line_end = match self.newlines.get(line + 1) {
+            Some(&next) => next - TextUnit::of_char('\n'),
+            None => self.len,
+        };
+        let mut col = col;
+        for &c in self.wide_chars[line].iter() {
+            let start = u32::from(c.start);
+            if col <= start {
+                break;
+            }
+            if col < start + c.len_utf16() {
+                col = start;
+                break;
+            }
+            col = col.saturating_add(c.extra_len());
+        }
+        let line_start = self.newlines[line];
+        line_start + TextUnit::from(col.min(u32::from(line_end - line_start)))
+    }
+
+    /// Updates the index for the text with `edit` applied, without scanning
+    /// the whole text.
+    pub fn apply_edit(&mut self, edit: &TextEdit) {
+        let start = edit.delete.start();
+        let end = edit.delete.end();
+        // The lines which start after a deleted newline.
+        let first = self.newlines.upper_bound(&start);
+        let last = self.newlines.upper_bound(&end);
+        let start_col = start - self.newlines[first - 1];
+        let end_col = end - self.newlines[last - 1];
+        let inserted = TextUnit::of_str(&edit.insert);
+        for line_start in self.newlines[last..].iter_mut() {
+            *line_start = start + inserted + (*line_start - end);
+        }
+        let mut new_lines = Vec::new();
+        let mut new_wide_chars = Vec::new();
+        let mut line: Vec<WideChar> = self.wide_chars[first - 1]
+            .iter()
+            .copied()
+            .take_while(|c| c.end <= start_col)
+            .collect();
+        let mut curr = start;
+        let mut col = start_col;
+        for c in edit.insert.chars() {
+            let len = TextUnit::of_char(c);
+            curr += len;
+            if c == '\n' {
+                new_lines.push(curr);
+                new_wide_chars.push(std::mem::take(&mut line));
+                col = 0.into();
+            } else {
+                if !c.is_ascii() {
+                    line.push(WideChar {
+                        start: col,
+                        end: col + len,
+                    });
+                }
+                col += len;
+            }
+        }
+        line.extend(
+            self.wide_chars[last - 1]
+                .iter()
+                .filter(|c| c.start >= end_col)
+                .map(|c| WideChar {
+                    start: c.start - end_col + col,
+                    end: c.end - end_col + col,
+                }),
+        );
+        new_wide_chars.push(line);
+        self.newlines.splice(first..last, new_lines);
+        self.wide_chars.splice(first - 1..last, new_wide_chars);
+        self.len = self.len - (end - start) + inserted;
     }
 }
 
@@ -149,3 +277,52 @@ fn test_line_index() {
         }
     );
 }
+
+#[test]
+fn test_apply_edit() {
+    use crate::TextRange;
+
+    fn check(text: &str, start: u32, end: u32, insert: &str) {
+        let edit = TextEdit::replace(TextRange::from_to(start.into(), end.into()), insert);
+        let mut index = LineIndex::new(text);
+        index.apply_edit(&edit);
+        assert_eq!(
+            index,
+            LineIndex::new(&edit.apply(text)),
+            "{:?} {:?}",
+            text,
+            edit
+        );
+    }
+
+    check("hello\nworld", 0, 0, "");
+    check("hello\nworld", 2, 2, "x\ny\n");
+    check("hello\nworld", 5, 6, "");
+    check("hello\nworld", 3, 8, " ");
+    check("a\nb\nc\nd", 1, 5, "\n\n\n");
+    check("a\nb\nc\nd", 0, 7, "");
+    check("a\nb", 3, 3, "\nc");
+    check("é\nb\n", 0, 2, "ü\n\n");
+    check("aéb\nü𝕏c", 1, 7, "x");
+    check("aéb\nü𝕏c", 3, 3, "\n𝕏\n");
+    check("aéb\nü𝕏c", 0, 11, "é");
+}
+
+#[test]
+fn test_utf16() {
+    let text = "aé𝕏b\nc\n";
+    let index = LineIndex::new(text);
+    let cols = [(0, 0), (1, 1), (3, 2), (7, 4), (8, 5)];
+    for &(offset, col) in cols.iter() {
+        let line_col = index.line_col(offset.into());
+        assert_eq!(index.utf16_col(line_col), col);
+        assert_eq!(index.offset_utf16(0, col), offset.into());
+    }
+    // Inside a surrogate pair.
+    assert_eq!(index.offset_utf16(0, 3), 3.into());
+    // Past the end of a line and of the text.
+    assert_eq!(index.offset_utf16(0, 100), 8.into());
+    assert_eq!(index.offset_utf16(1, 100), 10.into());
+    assert_eq!(index.offset_utf16(2, 100), 11.into());
+    assert_eq!(index.offset_utf16(5, 0), 11.into());
+}
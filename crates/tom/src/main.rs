@@ -3,24 +3,43 @@
 mod line_index;
 mod req;
 mod caps;
+mod symbols;
+mod semantic_tokens;
+mod config;
+mod code_actions;
+mod hover;
+mod navigation;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::Path};
 
 use crossbeam_channel::{Sender, Receiver};
 use languageserver_types::{
-    InitializeParams, Url, Range, Position,
+    InitializeParams, Url, Range, Position, Location,
+    TextDocumentContentChangeEvent, Diagnostic, DiagnosticSeverity, DiagnosticRelatedInformation,
+    NumberOrString, PublishDiagnosticsParams, DocumentSymbol, DocumentSymbolResponse,
+    SymbolInformation, RegistrationParams, ShowMessageParams, MessageType, CodeAction,
+    CodeActionResponse, WorkspaceEdit, Hover, HoverContents, MarkupContent, MarkupKind,
+    PrepareRenameResponse,
     notification::{self},
+    request::{self, GotoDefinitionResponse},
 };
 use gen_lsp_server::{
-    run_server, stdio_transport, handle_shutdown, RawMessage, RawResponse, RawNotification,
+    stdio_transport, handle_shutdown, RawMessage, RawRequest, RawResponse,
+    RawNotification, ErrorCode,
 };
 use flexi_logger::{Logger, Duplicate};
-use tom_syntax::{TomlDoc, TextRange, TextUnit, symbol::*};
-use failure::format_err;
+use tom_syntax::{
+    ast, TomlDoc, TextEdit, TextRange, TextUnit,
+    fmt::{self, FmtOptions},
+};
 
-use line_index::{LineIndex, LineCol};
+use line_index::LineIndex;
 
 fn main() -> Result<(), failure::Error> {
+    let args: Vec<String> = ::std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("fmt") {
+        return format_files(&args[1..]);
+    }
     ::std::env::set_var("RUST_BACKTRACE", "short");
     Logger::with_env_or_str("error")
         .duplicate_to_stderr(Duplicate::All)
@@ -28,17 +47,84 @@ fn main() -> Result<(), failure::Error> {
         .directory("log")
         .start()?;
     let (receiver, sender, io_threads) = stdio_transport();
-    run_server(caps::server_capabilities(), receiver, sender, main_loop)?;
+    run_server(&receiver, &sender)?;
     io_threads.join()?;
     Ok(())
 }
 
+/// Like `gen_lsp_server::run_server`, but reads the client capabilities
+/// which are newer than `InitializeParams` from the raw parameters of the
+/// `initialize` request.
+fn run_server(
+    receiver: &Receiver<RawMessage>,
+    sender: &Sender<RawMessage>,
+) -> Result<(), failure::Error> {
+    let req = match receiver.recv() {
+        Some(RawMessage::Request(req)) => req,
+        msg => failure::bail!("expected initialize request, got {:?}", msg),
+    };
+    let can_register_semantic_tokens = can_register_semantic_tokens(&req.params);
+    let (id, params) = match req.cast::<request::Initialize>() {
+        Ok(it) => it,
+        Err(req) => failure::bail!("expected initialize request, got {:?}", req),
+    };
+    let result = caps::initialize_result(can_register_semantic_tokens);
+    sender.send(RawMessage::Response(RawResponse {
+        id,
+        result: Some(result),
+        error: None,
+    }));
+    match receiver.recv() {
+        Some(RawMessage::Notification(not)) if not.method == "initialized" => (),
+        _ => failure::bail!("expected initialized notification"),
+    }
+
+    main_loop(params, can_register_semantic_tokens, receiver, sender)?;
+
+    match receiver.recv() {
+        Some(RawMessage::Notification(not)) if not.method == "exit" => Ok(()),
+        msg => failure::bail!("unexpected message during shutdown: {:?}", msg),
+    }
+}
+
+/// Whether the client supports the dynamic registration of semantic tokens.
+fn can_register_semantic_tokens(params: &serde_json::Value) -> bool {
+    let pointer = "/capabilities/textDocument/semanticTokens/dynamicRegistration";
+    params.pointer(pointer) == Some(&serde_json::Value::Bool(true))
+}
+
+/// Formats the files in place, like `tom fmt Cargo.toml`.
+fn format_files(paths: &[String]) -> Result<(), failure::Error> {
+    for path in paths {
+        let path = Path::new(path);
+        let text = fs::read_to_string(path)?;
+        let options = config::fmt_options(path).map_err(failure::err_msg)?;
+        let formatted = match fmt::format(&TomlDoc::new(&text), &options) {
+            Some(it) => it,
+            None => failure::bail!("{}: can't format a file with syntax errors", path.display()),
+        };
+        if formatted != text {
+            fs::write(path, formatted)?;
+        }
+    }
+    Ok(())
+}
+
 fn main_loop(
     _params: InitializeParams,
+    can_register_semantic_tokens: bool,
     receiver: &Receiver<RawMessage>,
     sender: &Sender<RawMessage>,
 ) -> Result<(), failure::Error> {
     let mut state = State::default();
+    if can_register_semantic_tokens {
+        let registration = RegistrationParams {
+            registrations: vec![caps::semantic_tokens_registration()],
+        };
+        sender.send(RawMessage::Request(RawRequest::new::<
+            request::RegisterCapability,
+        >(0, &registration)));
+    }
 
     for msg in receiver {
         match msg {
@@ -47,10 +133,32 @@ fn main_loop(
                     None => return Ok(()),
                     Some(req) => req,
                 };
-                let req = match req.cast::<req::DecorationsRequest>() {
+                let req = match req.cast::<req::SemanticTokensFull>() {
                     Ok((id, params)) => {
-                        let decorations = state.decorations(&params.uri);
-                        let resp = RawResponse::ok::<req::DecorationsRequest>(id, &decorations);
+                        let tokens = state.semantic_tokens(&params.text_document.uri);
+                        let resp = RawResponse::ok::<req::SemanticTokensFull>(id, &tokens);
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<req::SemanticTokensFullDelta>() {
+                    Ok((id, params)) => {
+                        let tokens = state.semantic_tokens_delta(
+                            &params.text_document.uri,
+                            &params.previous_result_id,
+                        );
+                        let resp = RawResponse::ok::<req::SemanticTokensFullDelta>(id, &tokens);
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<req::SemanticTokensRange>() {
+                    Ok((id, params)) => {
+                        let tokens =
+                            state.semantic_tokens_range(&params.text_document.uri, params.range);
+                        let resp = RawResponse::ok::<req::SemanticTokensRange>(id, &tokens);
                         sender.send(RawMessage::Response(resp));
                         continue;
                     }
@@ -65,7 +173,7 @@ fn main_loop(
                     }
                     Err(req) => req,
                 };
-                let _req = match req.cast::<req::ExtendSelection>() {
+                let req = match req.cast::<req::ExtendSelection>() {
                     Ok((id, params)) => {
                         let selections =
                             state.extend_selections(&params.text_document.uri, &params.selections);
@@ -78,6 +186,119 @@ fn main_loop(
                     }
                     Err(req) => req,
                 };
+                let req = match req.cast::<request::Formatting>() {
+                    Ok((id, params)) => {
+                        let edits = state.format(&params.text_document.uri, None, sender);
+                        let resp = RawResponse::ok::<request::Formatting>(id, &edits);
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::RangeFormatting>() {
+                    Ok((id, params)) => {
+                        let edits =
+                            state.format(&params.text_document.uri, Some(params.range), sender);
+                        let resp = RawResponse::ok::<request::RangeFormatting>(id, &edits);
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::CodeActionRequest>() {
+                    Ok((id, params)) => {
+                        let actions = state.code_actions(&params.text_document.uri, params.range);
+                        let resp = RawResponse::ok::<request::CodeActionRequest>(
+                            id,
+                            &actions.map(CodeActionResponse::Actions),
+                        );
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::HoverRequest>() {
+                    Ok((id, params)) => {
+                        let hover = state.hover(&params.text_document.uri, params.position);
+                        let resp = RawResponse::ok::<request::HoverRequest>(id, &hover);
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::GotoDefinition>() {
+                    Ok((id, params)) => {
+                        let location = state.definition(&params.text_document.uri, params.position);
+                        let resp = RawResponse::ok::<request::GotoDefinition>(
+                            id,
+                            &location.map(GotoDefinitionResponse::Scalar),
+                        );
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::References>() {
+                    Ok((id, params)) => {
+                        let locations = state.references(
+                            &params.text_document.uri,
+                            params.position,
+                            params.context.include_declaration,
+                        );
+                        let resp = RawResponse::ok::<request::References>(id, &locations);
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::PrepareRenameRequest>() {
+                    Ok((id, params)) => {
+                        let range =
+                            state.prepare_rename(&params.text_document.uri, params.position);
+                        let resp = RawResponse::ok::<request::PrepareRenameRequest>(id, &range);
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::Rename>() {
+                    Ok((id, params)) => {
+                        let resp = match state.rename(
+                            &params.text_document.uri,
+                            params.position,
+                            &params.new_name,
+                        ) {
+                            Ok(edit) => RawResponse::ok::<request::Rename>(id, &edit),
+                            Err(message) => {
+                                RawResponse::err(id, ErrorCode::InvalidParams as i32, message)
+                            }
+                        };
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let req = match req.cast::<request::DocumentSymbolRequest>() {
+                    Ok((id, params)) => {
+                        let symbols = state.document_symbols(&params.text_document.uri);
+                        let resp = RawResponse::ok::<request::DocumentSymbolRequest>(
+                            id,
+                            &symbols.map(DocumentSymbolResponse::Nested),
+                        );
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
+                let _req = match req.cast::<request::WorkspaceSymbol>() {
+                    Ok((id, params)) => {
+                        let symbols = state.workspace_symbols(&params.query);
+                        let resp = RawResponse::ok::<request::WorkspaceSymbol>(id, &Some(symbols));
+                        sender.send(RawMessage::Response(resp));
+                        continue;
+                    }
+                    Err(req) => req,
+                };
             }
             RawMessage::Response(_resp) => (),
             RawMessage::Notification(not) => {
@@ -85,21 +306,16 @@ fn main_loop(
                     Ok(params) => {
                         let uri = params.text_document.uri;
                         state.add_file(uri.clone(), &params.text_document.text);
-                        state.publish_decorations(uri, sender);
+                        state.publish_diagnostics(uri, sender);
                         continue;
                     }
                     Err(not) => not,
                 };
                 let not = match not.cast::<notification::DidChangeTextDocument>() {
-                    Ok(mut params) => {
+                    Ok(params) => {
                         let uri = params.text_document.uri;
-                        let text = params
-                            .content_changes
-                            .pop()
-                            .ok_or_else(|| format_err!("empty changes"))?
-                            .text;
-                        state.add_file(uri.clone(), &text);
-                        state.publish_decorations(uri, sender);
+                        state.change_file(uri.clone(), params.content_changes);
+                        state.publish_diagnostics(uri, sender);
                         continue;
                     }
                     Err(not) => not,
@@ -108,6 +324,7 @@ fn main_loop(
                     Ok(params) => {
                         let uri = params.text_document.uri;
                         state.remove_file(&uri);
+                        state.publish_diagnostics(uri, sender);
                         continue;
                     }
                     Err(not) => not,
@@ -121,6 +338,10 @@ fn main_loop(
 #[derive(Default)]
 struct State {
     files: HashMap<Url, (TomlDoc, LineIndex)>,
+    /// The last full tokens of each file with their result id, the base for
+    /// the next delta request.
+    semantic_tokens: HashMap<Url, (String, Vec<u32>)>,
+    next_result_id: u64,
 }
 
 impl State {
@@ -130,45 +351,279 @@ impl State {
         self.files.insert(url, (doc.clone(), line_index));
         doc
     }
+    /// Applies the changes one by one, each reparses only the part of the
+    /// document it touches.
+    fn change_file(&mut self, url: Url, changes: Vec<TextDocumentContentChangeEvent>) {
+        for change in changes {
+            let range = match change.range {
+                Some(range) => range,
+                None => {
+                    self.add_file(url.clone(), &change.text);
+                    continue;
+                }
+            };
+            let (doc, line_index) = match self.files.get_mut(&url) {
+                Some(it) => it,
+                None => return,
+            };
+            let edit = TextEdit::replace(from_vs_range(range, line_index), change.text);
+            line_index.apply_edit(&edit);
+            *doc = doc.reparse(edit);
+        }
+    }
     fn remove_file(&mut self, url: &Url) {
         self.files.remove(url);
+        self.semantic_tokens.remove(url);
     }
 
-    fn decorations(&self, url: &Url) -> Vec<req::Decoration> {
+    fn semantic_tokens(&mut self, url: &Url) -> Option<req::SemanticTokens> {
+        let (doc, line_index) = self.files.get(url)?;
+        let data = semantic_tokens::semantic_tokens(doc, line_index, None);
+        let result_id = self.save_semantic_tokens(url, data.clone());
+        Some(req::SemanticTokens {
+            result_id: Some(result_id),
+            data,
+        })
+    }
+
+    /// Returns the edits to the previous tokens, or all the tokens if the
+    /// previous ones are outdated.
+    fn semantic_tokens_delta(
+        &mut self,
+        url: &Url,
+        previous_result_id: &str,
+    ) -> Option<req::SemanticTokensFullDeltaResult> {
+        let (doc, line_index) = self.files.get(url)?;
+        let data = semantic_tokens::semantic_tokens(doc, line_index, None);
+        let edits = match self.semantic_tokens.get(url) {
+            Some((result_id, old)) if result_id == previous_result_id => {
+                Some(semantic_tokens::diff(old, &data))
+            }
+            _ => None,
+        };
+        let result_id = Some(self.save_semantic_tokens(url, data.clone()));
+        let res = match edits {
+            Some(edits) => req::SemanticTokensFullDeltaResult::Delta(req::SemanticTokensDelta {
+                result_id,
+                edits,
+            }),
+            None => {
+                req::SemanticTokensFullDeltaResult::Tokens(req::SemanticTokens { result_id, data })
+            }
+        };
+        Some(res)
+    }
+
+    fn semantic_tokens_range(&self, url: &Url, range: Range) -> Option<req::SemanticTokens> {
+        let (doc, line_index) = self.files.get(url)?;
+        let range = from_vs_range(range, line_index);
+        Some(req::SemanticTokens {
+            result_id: None,
+            data: semantic_tokens::semantic_tokens(doc, line_index, Some(range)),
+        })
+    }
+
+    fn save_semantic_tokens(&mut self, url: &Url, data: Vec<u32>) -> String {
+        self.next_result_id += 1;
+        let result_id = self.next_result_id.to_string();
+        self.semantic_tokens
+            .insert(url.clone(), (result_id.clone(), data));
+        result_id
+    }
+
+    fn diagnostics(&self, url: &Url) -> Vec<Diagnostic> {
         let (doc, line_index) = match self.files.get(url) {
             Some((doc, line_index)) => (doc, line_index),
             None => return Vec::new(),
         };
-        let mut decorations = Vec::new();
-        for node in doc.cst().descendants() {
-            let tag = match node.symbol() {
-                TABLE_HEADER => "keyword",
-
-                BASIC_STRING
-                | MULTILINE_BASIC_STRING
-                | LITERAL_STRING
-                | MULTILINE_LITERAL_STRING => "string",
-                COMMENT => "comment",
-                _ => continue,
-            };
+        doc.errors()
+            .into_iter()
+            .map(|error| {
+                let related_information = error.secondary_range().map(|range| {
+                    vec![DiagnosticRelatedInformation {
+                        location: Location::new(url.clone(), to_vs_range(range, line_index)),
+                        message: "first defined here".to_string(),
+                    }]
+                });
+                Diagnostic {
+                    range: to_vs_range(error.range(), line_index),
+                    // TOML has no warnings, every error makes the document
+                    // invalid.
+                    severity: Some(DiagnosticSeverity::Error),
+                    code: Some(NumberOrString::String(error.code().as_str().to_string())),
+                    source: Some("tom".to_string()),
+                    message: error.message().to_string(),
+                    related_information,
+                }
+            })
+            .collect()
+    }
+
+    /// Publishes the errors of the file, or clears them if the file is
+    /// closed.
+    fn publish_diagnostics(&self, url: Url, sender: &Sender<RawMessage>) {
+        let diagnostics = self.diagnostics(&url);
+        let params = PublishDiagnosticsParams::new(url, diagnostics);
+        let msg = RawMessage::Notification(
+            RawNotification::new::<notification::PublishDiagnostics>(&params),
+        );
+        sender.send(msg);
+    }
 
-            decorations.push(req::Decoration {
-                range: to_vs_range(node.range(), line_index),
-                tag,
-            });
+    /// Formats the whole file or the lines in the `range`. An invalid
+    /// `.tomfmt.toml` is reported to the user instead.
+    fn format(
+        &self,
+        url: &Url,
+        range: Option<Range>,
+        sender: &Sender<RawMessage>,
+    ) -> Option<Vec<languageserver_types::TextEdit>> {
+        let (doc, line_index) = self.files.get(url)?;
+        let options = match url.to_file_path() {
+            Ok(path) => config::fmt_options(&path),
+            Err(()) => Ok(FmtOptions::default()),
+        };
+        let options = match options {
+            Ok(it) => it,
+            Err(message) => {
+                let params = ShowMessageParams {
+                    typ: MessageType::Error,
+                    message,
+                };
+                let msg = RawMessage::Notification(
+                    RawNotification::new::<notification::ShowMessage>(&params),
+                );
+                sender.send(msg);
+                return None;
+            }
+        };
+        let edit = match range {
+            Some(range) => fmt::format_range(doc, from_vs_range(range, line_index), &options)?,
+            None => TextEdit::replace(doc.cst().range(), fmt::format(doc, &options)?),
+        };
+        let text = doc.cst().get_text();
+        if text[edit.delete] == edit.insert {
+            return Some(Vec::new());
         }
-        decorations
+        Some(vec![languageserver_types::TextEdit::new(
+            to_vs_range(edit.delete, line_index),
+            edit.insert,
+        )])
+    }
+
+    /// The sorting actions at the `range`. An invalid `.tomfmt.toml` only
+    /// means that the tables can't be reordered, it is reported on
+    /// formatting.
+    fn code_actions(&self, url: &Url, range: Range) -> Option<Vec<CodeAction>> {
+        let (doc, line_index) = self.files.get(url)?;
+        let table_order = url
+            .to_file_path()
+            .ok()
+            .and_then(|path| config::table_order(&path).ok())
+            .unwrap_or_default();
+        let range = from_vs_range(range, line_index);
+        let actions = code_actions::code_actions(doc, range, &table_order)
+            .into_iter()
+            .map(|action| {
+                let edit = languageserver_types::TextEdit::new(
+                    to_vs_range(action.edit.delete, line_index),
+                    action.edit.insert,
+                );
+                let mut changes = HashMap::new();
+                changes.insert(url.clone(), vec![edit]);
+                CodeAction {
+                    title: action.title.to_string(),
+                    kind: Some(action.kind.to_string()),
+                    diagnostics: None,
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(changes),
+                        document_changes: None,
+                    }),
+                    command: None,
+                }
+            })
+            .collect();
+        Some(actions)
+    }
+
+    fn hover(&self, url: &Url, position: Position) -> Option<Hover> {
+        let (doc, line_index) = self.files.get(url)?;
+        let key = key_at(doc, from_vs_position(position, line_index))?;
+        let (range, value) = hover::hover(doc, key)?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(to_vs_range(range, line_index)),
+        })
+    }
+
+    fn definition(&self, url: &Url, position: Position) -> Option<Location> {
+        let (doc, line_index) = self.files.get(url)?;
+        let key = key_at(doc, from_vs_position(position, line_index))?;
+        let definition = navigation::definition(doc, key)?;
+        let range = to_vs_range(definition.syntax().range(), line_index);
+        Some(Location::new(url.clone(), range))
+    }
+
+    fn references(
+        &self,
+        url: &Url,
+        position: Position,
+        include_declaration: bool,
+    ) -> Option<Vec<Location>> {
+        let (doc, line_index) = self.files.get(url)?;
+        let key = key_at(doc, from_vs_position(position, line_index))?;
+        let locations = navigation::references(doc, key, include_declaration)
+            .into_iter()
+            .map(|it| Location::new(url.clone(), to_vs_range(it.syntax().range(), line_index)))
+            .collect();
+        Some(locations)
+    }
+
+    fn prepare_rename(&self, url: &Url, position: Position) -> Option<PrepareRenameResponse> {
+        let (doc, line_index) = self.files.get(url)?;
+        let key = key_at(doc, from_vs_position(position, line_index))?;
+        Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range: to_vs_range(key.syntax().range(), line_index),
+            placeholder: key.try_name()?.into_owned(),
+        })
     }
 
-    fn publish_decorations(&self, url: Url, sender: &Sender<RawMessage>) {
-        let decorations = self.decorations(&url);
-        let params = req::PublishDecorationsParams {
-            uri: url,
-            decorations,
+    /// Renames the key and all the keys of the same value, or returns why
+    /// it can't be done.
+    fn rename(
+        &self,
+        url: &Url,
+        position: Position,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>, String> {
+        let (doc, line_index) = match self.files.get(url) {
+            Some(it) => it,
+            None => return Ok(None),
         };
-        let msg =
-            RawMessage::Notification(RawNotification::new::<req::PublishDecorations>(&params));
-        sender.send(msg);
+        let key = match key_at(doc, from_vs_position(position, line_index)) {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        let edits = doc
+            .rename_key(key, new_name)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|edit| {
+                languageserver_types::TextEdit::new(
+                    to_vs_range(edit.delete, line_index),
+                    edit.insert,
+                )
+            })
+            .collect();
+        let mut changes = HashMap::new();
+        changes.insert(url.clone(), edits);
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            document_changes: None,
+        }))
     }
 
     fn extend_selections(&self, uri: &Url, selections: &[Range]) -> Vec<Range> {
@@ -186,6 +641,24 @@ impl State {
         res
     }
 
+    fn document_symbols(&self, uri: &Url) -> Option<Vec<DocumentSymbol>> {
+        let (doc, line_index) = self.files.get(uri)?;
+        Some(symbols::to_document_symbols(
+            symbols::symbols(doc),
+            line_index,
+        ))
+    }
+
+    fn workspace_symbols(&self, query: &str) -> Vec<SymbolInformation> {
+        let query = query.to_lowercase();
+        let mut res = Vec::new();
+        for (uri, (doc, line_index)) in self.files.iter() {
+            let symbols = symbols::symbols(doc);
+            symbols::to_symbol_information(&symbols, &query, None, uri, line_index, &mut res);
+        }
+        res
+    }
+
     fn syntax_tree(&self, uri: &Url) -> String {
         let doc = match self.files.get(uri) {
             Some((doc, _line_index)) => doc,
@@ -204,6 +677,14 @@ pub(crate) fn extend(doc: &TomlDoc, range: TextRange) -> TextRange {
     }
 }
 
+/// Returns the key under the cursor, or the one right before it.
+pub(crate) fn key_at(doc: &TomlDoc, offset: TextUnit) -> Option<ast::Key<'_>> {
+    doc.cst()
+        .descendants()
+        .filter(|it| it.is_leaf() && it.range().start() <= offset && offset <= it.range().end())
+        .find_map(|it| it.ancestors().find_map(ast::Key::cast))
+}
+
 fn to_vs_range(range: TextRange, line_index: &LineIndex) -> Range {
     Range::new(
         to_vs_position(range.start(), line_index),
@@ -213,7 +694,10 @@ fn to_vs_range(range: TextRange, line_index: &LineIndex) -> Range {
 
 fn to_vs_position(offset: TextUnit, line_index: &LineIndex) -> Position {
     let line_col = line_index.line_col(offset);
-    Position::new(u64::from(line_col.line), u64::from(u32::from(line_col.col)))
+    Position::new(
+        u64::from(line_col.line),
+        u64::from(line_index.utf16_col(line_col)),
+    )
 }
 
 fn from_vs_range(range: Range, line_index: &LineIndex) -> TextRange {
@@ -224,9 +708,117 @@ fn from_vs_range(range: Range, line_index: &LineIndex) -> TextRange {
 }
 
 fn from_vs_position(position: Position, line_index: &LineIndex) -> TextUnit {
-    let line_col = LineCol {
-        line: position.line as u32,
-        col: (position.character as u32).into(),
+    let clamp = |it: u64| it.min(u64::from(u32::MAX)) as u32;
+    line_index.offset_utf16(clamp(position.line), clamp(position.character))
+}
+
+#[test]
+fn test_change_file() {
+    let mut state = State::default();
+    let url = Url::parse("file:///a.toml").unwrap();
+    state.add_file(url.clone(), "\"é\" = 1\n# café\n");
+    let change = |(line, start): (u64, u64), end: u64, text: &str| TextDocumentContentChangeEvent {
+        range: Some(Range::new(
+            Position::new(line, start),
+            Position::new(line, end),
+        )),
+        range_length: None,
+        text: text.to_string(),
     };
-    line_index.offset(line_col)
+    state.change_file(
+        url.clone(),
+        vec![
+            // After the `é`, one UTF-16 code unit but two bytes.
+            change((0, 2), 2, "x"),
+            change((1, 6), 6, "s"),
+            // Past the end of the line and of the file.
+            change((0, 100), 100, " # 𝕏"),
+            change((9, 0), 5, "b = 2\n"),
+        ],
+    );
+    let (doc, line_index) = &state.files[&url];
+    let text = "\"éx\" = 1 # 𝕏\n# cafés\nb = 2\n";
+    assert_eq!(doc.cst().get_text(), text);
+    assert_eq!(line_index, &LineIndex::new(text));
+    let position = to_vs_position(TextUnit::of_str("\"éx\" = 1 # 𝕏"), line_index);
+    assert_eq!(position, Position::new(0, 13));
+}
+
+#[test]
+fn test_diagnostics() {
+    let mut state = State::default();
+    let url = Url::parse("file:///a.toml").unwrap();
+    state.add_file(url.clone(), "a = 1\na = 2\n");
+    let diagnostics = state.diagnostics(&url);
+    assert_eq!(diagnostics.len(), 1);
+    let diagnostic = &diagnostics[0];
+    assert_eq!(
+        diagnostic.code,
+        Some(NumberOrString::String("redefinition".to_string()))
+    );
+    assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::Error));
+    assert_eq!(diagnostic.range.start, Position::new(1, 0));
+    let related = diagnostic.related_information.as_ref().unwrap();
+    assert_eq!(related.len(), 1);
+    assert_eq!(related[0].message, "first defined here");
+    assert_eq!(related[0].location.uri, url);
+    assert_eq!(
+        related[0].location.range,
+        Range::new(Position::new(0, 0), Position::new(0, 1))
+    );
+
+    state.remove_file(&url);
+    assert!(state.diagnostics(&url).is_empty());
+}
+
+#[test]
+fn test_prepare_rename() {
+    let mut state = State::default();
+    let url = Url::parse("file:///a.toml").unwrap();
+    state.add_file(url.clone(), "a = 1\n[b.]\n");
+    match state.prepare_rename(&url, Position::new(0, 0)) {
+        Some(PrepareRenameResponse::RangeWithPlaceholder { placeholder, .. }) => {
+            assert_eq!(placeholder, "a")
+        }
+        res => panic!("{:?}", res),
+    }
+    assert!(state.prepare_rename(&url, Position::new(1, 3)).is_none());
+    let error = state.rename(&url, Position::new(0, 0), "c").unwrap_err();
+    assert_eq!(error, "the document has errors");
+}
+
+#[test]
+fn test_can_register_semantic_tokens() {
+    let params = |dynamic_registration: bool| {
+        serde_json::json!({
+            "capabilities": {
+                "textDocument": {
+                    "semanticTokens": { "dynamicRegistration": dynamic_registration }
+                }
+            }
+        })
+    };
+    assert!(can_register_semantic_tokens(&params(true)));
+    assert!(!can_register_semantic_tokens(&params(false)));
+    assert!(!can_register_semantic_tokens(&serde_json::json!({ "capabilities": {} })));
+
+    let legend = serde_json::json!({
+        "tokenTypes": semantic_tokens::TOKEN_TYPES,
+        "tokenModifiers": semantic_tokens::TOKEN_MODIFIERS,
+    });
+    let provider = &caps::initialize_result(false)["capabilities"]["semanticTokensProvider"];
+    assert_eq!(provider["legend"], legend);
+    assert_eq!(provider["range"], true);
+    assert_eq!(provider["full"]["delta"], true);
+    let result = caps::initialize_result(true);
+    assert!(result["capabilities"].get("semanticTokensProvider").is_none());
+    assert!(result["capabilities"]["hoverProvider"].as_bool().unwrap());
+
+    let registration = caps::semantic_tokens_registration();
+    assert_eq!(registration.method, "textDocument/semanticTokens");
+    let options = registration.register_options.unwrap();
+    assert_eq!(options["documentSelector"], serde_json::Value::Null);
+    assert_eq!(options["legend"], legend);
+    assert_eq!(options["range"], true);
+    assert_eq!(options["full"]["delta"], true);
 }
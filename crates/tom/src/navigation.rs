@@ -0,0 +1,95 @@
+//! Go to definition and find references for keys.
+//!
+//! A key refers to the value at its path, so `a` in `a.b = 1` and in
+//! `[a.c]` refer to the same table. The references of a value are all the
+//! keys which define or extend it, and its definition is the first of them.
+//! A key in a malformed path, like in `[a.]`, refers to nothing.
+
+use tom_syntax::{ast, TomlDoc};
+
+/// Returns the key which first introduced the value of the `key`.
+pub(crate) fn definition<'a>(doc: &'a TomlDoc, key: ast::Key) -> Option<ast::Key<'a>> {
+    doc.get_keys(&key.path()?).into_iter().next()
+}
+
+/// Returns the keys which refer to the same value as the `key`, in the
+/// document order, without the definition unless `include_declaration`.
+pub(crate) fn references<'a>(
+    doc: &'a TomlDoc,
+    key: ast::Key,
+    include_declaration: bool,
+) -> Vec<ast::Key<'a>> {
+    let mut res = match key.path() {
+        Some(path) => doc.get_keys(&path),
+        None => return Vec::new(),
+    };
+    if !include_declaration && !res.is_empty() {
+        res.remove(0);
+    }
+    res
+}
+
+/// Marks the keys which `f` returns for the key at `|`.
+#[cfg(test)]
+fn mark(
+    text: &str,
+    f: impl for<'a> FnOnce(&'a TomlDoc, ast::Key<'a>) -> Vec<ast::Key<'a>>,
+) -> String {
+    let offset = text.find('|').unwrap();
+    let text = text.replacen('|', "", 1);
+    let doc = TomlDoc::new(&text);
+    let key = crate::key_at(&doc, tom_syntax::TextUnit::from_usize(offset)).unwrap();
+    let mut res = text.clone();
+    for key in f(&doc, key).into_iter().rev() {
+        let range = key.syntax().range();
+        res.insert(range.end().to_usize(), '>');
+        res.insert(range.start().to_usize(), '<');
+    }
+    res
+}
+
+#[test]
+fn test_definition() {
+    let check = |text| mark(text, |doc, key| definition(doc, key).into_iter().collect());
+    assert_eq!(
+        check("x.a.b = 1\n[x]\na.c = 1\n[|x.a.d]\n"),
+        "<x>.a.b = 1\n[x]\na.c = 1\n[x.a.d]\n",
+    );
+    assert_eq!(
+        check("[a]\nx = 1\n[a.b]\ny = 1\n[a.|b.e]\n"),
+        "[a]\nx = 1\n[a.<b>]\ny = 1\n[a.b.e]\n",
+    );
+    assert_eq!(
+        check("[[s]]\n[[s]]\nx = 1\n[|s.t]\n"),
+        "[[s]]\n[[<s>]]\nx = 1\n[s.t]\n",
+    );
+    assert_eq!(check("|a = ?\n"), "a = ?\n");
+    assert_eq!(check("x = 1\n[a.|]\n"), "x = 1\n[a.]\n");
+    assert_eq!(
+        check("a.b = 1\n[|a.c]\nx = 1\n[d."),
+        "<a>.b = 1\n[a.c]\nx = 1\n[d.",
+    );
+}
+
+#[test]
+fn test_references() {
+    let text = "a.b = 1\n[a.c]\nd = { e = 1 }\n[[a.f]]\n[[a.f]]\n[a.|f.g]\n";
+    assert_eq!(
+        mark(text, |doc, key| references(doc, key, true)),
+        "a.b = 1\n[a.c]\nd = { e = 1 }\n[[a.f]]\n[[a.<f>]]\n[a.<f>.g]\n",
+    );
+    assert_eq!(
+        mark(text, |doc, key| references(doc, key, false)),
+        "a.b = 1\n[a.c]\nd = { e = 1 }\n[[a.f]]\n[[a.f]]\n[a.<f>.g]\n",
+    );
+    assert_eq!(
+        mark("|a.b = 1\n[a.c]\nd = { e = 1 }\n", |doc, key| references(
+            doc, key, true
+        )),
+        "<a>.b = 1\n[<a>.c]\nd = { e = 1 }\n",
+    );
+    assert_eq!(
+        mark("[a.|]\n[a.b]\n", |doc, key| references(doc, key, true)),
+        "[a.]\n[a.b]\n",
+    );
+}
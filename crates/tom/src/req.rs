@@ -1,11 +1,7 @@
 //! FIXME: write short doc here
 
 use serde::{Serialize, Deserialize};
-use languageserver_types::{
-    Url, Range, TextDocumentIdentifier,
-    request::Request,
-    notification::{Notification},
-};
+use languageserver_types::{Range, TextDocumentIdentifier, DocumentSelector, request::Request};
 
 pub enum SyntaxTree {}
 
@@ -42,32 +38,111 @@ pub struct ExtendSelectionResult {
     pub selections: Vec<Range>,
 }
 
-pub enum DecorationsRequest {}
+pub enum SemanticTokensFull {}
 
-impl Request for DecorationsRequest {
-    type Params = TextDocumentIdentifier;
-    type Result = Vec<Decoration>;
-    const METHOD: &'static str = "m/decorationsRequest";
+impl Request for SemanticTokensFull {
+    type Params = SemanticTokensParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens/full";
 }
 
-pub enum PublishDecorations {}
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+pub enum SemanticTokensFullDelta {}
 
-impl Notification for PublishDecorations {
-    type Params = PublishDecorationsParams;
-    const METHOD: &'static str = "m/publishDecorations";
+impl Request for SemanticTokensFullDelta {
+    type Params = SemanticTokensDeltaParams;
+    type Result = Option<SemanticTokensFullDeltaResult>;
+    const METHOD: &'static str = "textDocument/semanticTokens/full/delta";
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct PublishDecorationsParams {
-    #[serde(with = "url_serde")]
-    pub uri: Url,
-    pub decorations: Vec<Decoration>,
+pub struct SemanticTokensDeltaParams {
+    pub text_document: TextDocumentIdentifier,
+    pub previous_result_id: String,
 }
 
 #[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum SemanticTokensFullDeltaResult {
+    Tokens(SemanticTokens),
+    Delta(SemanticTokensDelta),
+}
+
+pub enum SemanticTokensRange {}
+
+impl Request for SemanticTokensRange {
+    type Params = SemanticTokensRangeParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens/range";
+}
+
+#[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct Decoration {
+pub struct SemanticTokensRangeParams {
+    pub text_document: TextDocumentIdentifier,
     pub range: Range,
-    pub tag: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    pub data: Vec<u32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDelta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Vec<u32>>,
+}
+
+/// The `semanticTokensProvider` of the server capabilities.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensOptions {
+    pub legend: SemanticTokensLegend,
+    pub range: bool,
+    pub full: SemanticTokensFullOptions,
+}
+
+/// The options of the dynamic registration of
+/// `textDocument/semanticTokens`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensRegistrationOptions {
+    /// `None` stands for the document selector of the client.
+    pub document_selector: Option<DocumentSelector>,
+    #[serde(flatten)]
+    pub options: SemanticTokensOptions,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensLegend {
+    pub token_types: &'static [&'static str],
+    pub token_modifiers: &'static [&'static str],
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensFullOptions {
+    pub delta: bool,
 }
@@ -0,0 +1,268 @@
+//! Semantic highlighting.
+//!
+//! Every leaf of the syntax tree which means something gets a token, encoded
+//! relative to the previous one as the protocol wants. Clients are not
+//! required to support tokens spanning several lines, so multiline strings
+//! get a token per line.
+
+use tom_syntax::{symbol::*, SyntaxNodeRef, TextRange, TextUnit, TomlDoc};
+
+use crate::{line_index::LineIndex, req::SemanticTokensEdit};
+
+/// The legend of the token types, in the order of `TokenType`.
+pub(crate) const TOKEN_TYPES: &[&str] = &[
+    "namespace",
+    "property",
+    "string",
+    "number",
+    "boolean",
+    "dateTime",
+    "comment",
+    "invalid",
+];
+
+/// The legend of the token modifiers, the bit `1 << i` stands for the `i`th.
+pub(crate) const TOKEN_MODIFIERS: &[&str] = &["quoted"];
+
+const QUOTED: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenType {
+    TableKey,
+    Key,
+    String,
+    Number,
+    Boolean,
+    DateTime,
+    Comment,
+    Invalid,
+}
+
+/// Returns the encoded tokens of the whole document, or of the leaves which
+/// intersect the `range`.
+pub(crate) fn semantic_tokens(
+    doc: &TomlDoc,
+    line_index: &LineIndex,
+    range: Option<TextRange>,
+) -> Vec<u32> {
+    let mut builder = Builder::default();
+    for leaf in doc.cst().descendants().filter(|it| it.is_leaf()) {
+        if range.is_some_and(|range| leaf.range().intersection(&range).is_none()) {
+            continue;
+        }
+        let (ty, modifiers) = match classify(leaf) {
+            Some(it) => it,
+            None => continue,
+        };
+        let mut offset = leaf.range().start();
+        for line in leaf.leaf_text().unwrap_or("").split('\n') {
+            let len = TextUnit::of_str(line.trim_end_matches('\r'));
+            if len > 0.into() {
+                builder.push(offset, len, ty, modifiers, line_index);
+            }
+            offset += TextUnit::of_str(line) + TextUnit::of_char('\n');
+        }
+    }
+    builder.data
+}
+
+/// Returns the edit which turns the `old` tokens into the `new` ones, or
+/// nothing if they are the same.
+pub(crate) fn diff(old: &[u32], new: &[u32]) -> Vec<SemanticTokensEdit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix == old.len() && prefix == new.len() {
+        return Vec::new();
+    }
+    vec![SemanticTokensEdit {
+        start: prefix as u32,
+        delete_count: (old.len() - prefix - suffix) as u32,
+        data: Some(new[prefix..new.len() - suffix].to_vec()),
+    }]
+}
+
+fn classify(leaf: SyntaxNodeRef) -> Option<(TokenType, u32)> {
+    if leaf.symbol() == WHITESPACE {
+        return None;
+    }
+    if leaf.ancestors().any(|it| it.symbol() == ERROR) {
+        return Some((TokenType::Invalid, 0));
+    }
+    let ty = match leaf.symbol() {
+        BARE_KEY
+        | BASIC_STRING
+        | LITERAL_STRING
+        | MULTILINE_BASIC_STRING
+        | MULTILINE_LITERAL_STRING
+            if leaf.parent().map(|it| it.symbol()) == Some(KEY) =>
+        {
+            let in_header = leaf.ancestors().any(|it| it.symbol() == TABLE_HEADER);
+            let ty = if in_header {
+                TokenType::TableKey
+            } else {
+                TokenType::Key
+            };
+            let modifiers = if leaf.symbol() == BARE_KEY { 0 } else { QUOTED };
+            return Some((ty, modifiers));
+        }
+        BASIC_STRING | LITERAL_STRING | MULTILINE_BASIC_STRING | MULTILINE_LITERAL_STRING => {
+            TokenType::String
+        }
+        NUMBER => TokenType::Number,
+        BOOL => TokenType::Boolean,
+        DATE_TIME => TokenType::DateTime,
+        COMMENT => TokenType::Comment,
+        _ => return None,
+    };
+    Some((ty, 0))
+}
+
+#[derive(Default)]
+struct Builder {
+    data: Vec<u32>,
+    prev_line: u32,
+    prev_col: u32,
+}
+
+impl Builder {
+    fn push(
+        &mut self,
+        offset: TextUnit,
+        len: TextUnit,
+        ty: TokenType,
+        modifiers: u32,
+        line_index: &LineIndex,
+    ) {
+        let line_col = line_index.line_col(offset);
+        let col = line_index.utf16_col(line_col);
+        let end_col = line_index.utf16_col(line_index.line_col(offset + len));
+        let delta_line = line_col.line - self.prev_line;
+        let delta_col = if delta_line == 0 {
+            col - self.prev_col
+        } else {
+            col
+        };
+        self.data
+            .extend_from_slice(&[delta_line, delta_col, end_col - col, ty as u32, modifiers]);
+        self.prev_line = line_col.line;
+        self.prev_col = col;
+    }
+}
+
+#[test]
+fn test_semantic_tokens() {
+    fn render(text: &str, range: Option<TextRange>) -> String {
+        let doc = TomlDoc::new(text);
+        let line_index = LineIndex::new(text);
+        let data = semantic_tokens(&doc, &line_index, range);
+        let lines: Vec<&str> = text.split('\n').collect();
+        let (mut line, mut col) = (0, 0);
+        let mut buff = String::new();
+        for token in data.chunks(5) {
+            if token[0] != 0 {
+                col = 0;
+            }
+            line += token[0] as usize;
+            col += token[1] as usize;
+            let text = &lines[line][col..col + token[2] as usize];
+            let modifiers = if token[4] & QUOTED != 0 {
+                " quoted"
+            } else {
+                ""
+            };
+            buff += &format!(
+                "{}:{} {}{} {}\n",
+                line, col, TOKEN_TYPES[token[3] as usize], modifiers, text
+            );
+        }
+        buff
+    }
+
+    let text = r#"# comment
+[table."quoted".key]
+a = "x"
+'b' = 1
+c.d = true
+e = 1979-05-27
+f = { g = 1.5 }
+h = """
+multi
+line"""
+[[array]]
+i = ?
+"#;
+    let expected = r#"0:0 comment # comment
+1:1 namespace table
+1:7 namespace quoted "quoted"
+1:16 namespace key
+2:0 property a
+2:4 string "x"
+3:0 property quoted 'b'
+3:6 number 1
+4:0 property c
+4:2 property d
+4:6 boolean true
+5:0 property e
+5:4 dateTime 1979-05-27
+6:0 property f
+6:6 property g
+6:10 number 1.5
+7:0 property h
+7:4 string """
+8:0 string multi
+9:0 string line"""
+10:2 namespace array
+11:0 property i
+11:4 invalid ?
+"#;
+    assert_eq!(render(text, None), expected);
+
+    let start = text.find("\"x\"").unwrap() as u32;
+    let end = text.find("'b'").unwrap() as u32 + 3;
+    let range = TextRange::from_to(start.into(), end.into());
+    let expected = r#"2:4 string "x"
+3:0 property quoted 'b'
+"#;
+    assert_eq!(render(text, Some(range)), expected);
+
+    // Columns and lengths are in UTF-16 code units.
+    let text = "\"é\" = \"𝕏\"\n# ü\n";
+    let data = semantic_tokens(&TomlDoc::new(text), &LineIndex::new(text), None);
+    let (key, string, comment) = (
+        TokenType::Key as u32,
+        TokenType::String as u32,
+        TokenType::Comment as u32,
+    );
+    let expected = [
+        [0, 0, 3, key, QUOTED],
+        [0, 6, 4, string, 0],
+        [1, 0, 3, comment, 0],
+    ];
+    assert_eq!(data, expected.concat());
+}
+
+#[test]
+fn test_diff() {
+    fn check(old: &[u32], new: &[u32]) {
+        let mut actual = old.to_vec();
+        for edit in diff(old, new) {
+            let start = edit.start as usize;
+            let end = start + edit.delete_count as usize;
+            actual.splice(start..end, edit.data.unwrap_or_default());
+        }
+        assert_eq!(actual, new);
+    }
+
+    assert!(diff(&[1, 2, 3], &[1, 2, 3]).is_empty());
+    check(&[1, 2, 3], &[1, 4, 3]);
+    check(&[1, 2, 3], &[1, 2, 3, 4]);
+    check(&[1, 2, 3], &[0, 1, 2, 3]);
+    check(&[1, 2, 3], &[]);
+    check(&[], &[1]);
+    check(&[1, 1, 1], &[1, 1]);
+}
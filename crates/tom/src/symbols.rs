@@ -0,0 +1,260 @@
+//! The outline of a document.
+//!
+//! Tables and array tables are top-level symbols named by their full paths,
+//! like `servers[1]` for the second `[[servers]]`. Entries are nested in
+//! their table, dotted keys are nested in the symbols for their prefixes,
+//! and inline tables in their entry.
+
+use languageserver_types::{DocumentSymbol, Location, SymbolInformation, SymbolKind, Url};
+use tom_syntax::{ast, TextRange, TomlDoc};
+
+use crate::{line_index::LineIndex, to_vs_range};
+
+#[derive(Debug)]
+pub(crate) struct Symbol {
+    name: String,
+    kind: SymbolKind,
+    detail: Option<String>,
+    range: TextRange,
+    selection_range: TextRange,
+    children: Vec<Symbol>,
+    /// Whether the symbol is for a prefix of dotted keys, which merges with
+    /// the same prefix of the next entry.
+    is_prefix: bool,
+}
+
+pub(crate) fn symbols(doc: &TomlDoc) -> Vec<Symbol> {
+    let mut res = Vec::new();
+    for entry in doc.ast().entries() {
+        push_entry(&mut res, entry);
+    }
+    for (header, path) in doc.table_paths() {
+        res.push(table_symbol(path, header));
+    }
+    res
+}
+
+pub(crate) fn to_document_symbols(
+    symbols: Vec<Symbol>,
+    line_index: &LineIndex,
+) -> Vec<DocumentSymbol> {
+    symbols
+        .into_iter()
+        .map(|symbol| DocumentSymbol {
+            name: symbol.name,
+            detail: symbol.detail,
+            kind: symbol.kind,
+            deprecated: None,
+            range: to_vs_range(symbol.range, line_index),
+            selection_range: to_vs_range(symbol.selection_range, line_index),
+            children: if symbol.children.is_empty() {
+                None
+            } else {
+                Some(to_document_symbols(symbol.children, line_index))
+            },
+        })
+        .collect()
+}
+
+/// Flattens the symbols whose names contain the lowercase `query`, ignoring
+/// the case.
+pub(crate) fn to_symbol_information(
+    symbols: &[Symbol],
+    query: &str,
+    container: Option<&str>,
+    url: &Url,
+    line_index: &LineIndex,
+    acc: &mut Vec<SymbolInformation>,
+) {
+    for symbol in symbols {
+        if symbol.name.to_lowercase().contains(query) {
+            acc.push(SymbolInformation {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                deprecated: None,
+                location: Location::new(url.clone(), to_vs_range(symbol.range, line_index)),
+                container_name: container.map(|it| it.to_string()),
+            });
+        }
+        let name = match container {
+            Some(container) => format!("{}.{}", container, symbol.name),
+            None => symbol.name.clone(),
+        };
+        to_symbol_information(&symbol.children, query, Some(&name), url, line_index, acc);
+    }
+}
+
+fn table_symbol(name: String, header: ast::TableHeader) -> Symbol {
+    let table = header.syntax().parent().unwrap();
+    let mut children = Vec::new();
+    for entry in table.children().filter_map(ast::Entry::cast) {
+        push_entry(&mut children, entry);
+    }
+    Symbol {
+        name,
+        kind: SymbolKind::Object,
+        detail: None,
+        range: table.range(),
+        selection_range: header.syntax().range(),
+        children,
+        is_prefix: false,
+    }
+}
+
+/// Adds the symbol of the entry, unless a key or the value is malformed.
+fn push_entry(symbols: &mut Vec<Symbol>, entry: ast::Entry) {
+    let keys = match entry
+        .keys()
+        .map(|key| Some((key, key.try_name()?.into_owned())))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(it) => it,
+        None => return,
+    };
+    let ((last, last_name), prefix) = match keys.split_last() {
+        Some(it) => it,
+        None => return,
+    };
+    let mut symbol = match value_symbol(last_name.clone(), entry.value()) {
+        Some(it) => it,
+        None => return,
+    };
+    symbol.range = entry.syntax().range();
+    symbol.selection_range = last.syntax().range();
+    for (key, name) in prefix.iter().rev() {
+        symbol = Symbol {
+            name: name.clone(),
+            kind: SymbolKind::Object,
+            detail: None,
+            range: entry.syntax().range(),
+            selection_range: key.syntax().range(),
+            children: vec![symbol],
+            is_prefix: true,
+        };
+    }
+    merge(symbols, symbol);
+}
+
+/// Adds the symbol, merging `a.b = 1` and `a.c = 2` into a single `a`.
+fn merge(symbols: &mut Vec<Symbol>, symbol: Symbol) {
+    match symbols.last_mut() {
+        Some(prev) if prev.is_prefix && symbol.is_prefix && prev.name == symbol.name => {
+            prev.range = prev.range.extend_to(&symbol.range);
+            for child in symbol.children {
+                merge(&mut prev.children, child);
+            }
+        }
+        _ => symbols.push(symbol),
+    }
+}
+
+fn value_symbol(name: String, value: ast::Value) -> Option<Symbol> {
+    let mut children = Vec::new();
+    let kind = match value.try_kind()? {
+        ast::ValueKind::Dict(dict) => {
+            for entry in dict.entries() {
+                push_entry(&mut children, entry);
+            }
+            SymbolKind::Object
+        }
+        ast::ValueKind::Array(array) => {
+            for (idx, value) in array.values().enumerate() {
+                if let Some(ast::ValueKind::Dict(_)) = value.try_kind() {
+                    children.extend(value_symbol(format!("[{}]", idx), value));
+                }
+            }
+            SymbolKind::Array
+        }
+        ast::ValueKind::StringLit(_) => SymbolKind::String,
+        ast::ValueKind::Number(_) => SymbolKind::Number,
+        ast::ValueKind::Bool(_) => SymbolKind::Boolean,
+        ast::ValueKind::DateTime(_) => SymbolKind::Constant,
+    };
+    let text = value.syntax().get_text();
+    let detail = match kind {
+        SymbolKind::Object | SymbolKind::Array => None,
+        _ if text.contains('\n') => None,
+        _ => Some(text),
+    };
+    Some(Symbol {
+        name,
+        kind,
+        detail,
+        range: value.syntax().range(),
+        selection_range: value.syntax().range(),
+        children,
+        is_prefix: false,
+    })
+}
+
+#[test]
+fn test_symbols() {
+    fn render(symbols: &[Symbol], level: usize, buff: &mut String) {
+        for symbol in symbols {
+            *buff += &format!(
+                "{}{} {:?} {:?}\n",
+                "  ".repeat(level),
+                symbol.name,
+                symbol.kind,
+                symbol.detail
+            );
+            render(&symbol.children, level + 1, buff);
+        }
+    }
+
+    let doc = TomlDoc::new(
+        r#"title = "x"
+a.b = 1
+a.c.d = true
+e = { f = 1979-05-27, g = [{ h = 1 }, 2] }
+
+[servers]
+alpha.ip = "10.0.0.1"
+
+[[servers.disks]]
+size = 1
+
+[[servers.disks]]
+
+[[fruit]]
+[[fruit.variety]]
+[[fruit]]
+[[fruit.variety]]
+"#,
+    );
+    let mut actual = String::new();
+    render(&symbols(&doc), 0, &mut actual);
+    let expected = r#"title String Some("\"x\"")
+a Object None
+  b Number Some("1")
+  c Object None
+    d Boolean Some("true")
+e Object None
+  f Constant Some("1979-05-27")
+  g Array None
+    [0] Object None
+      h Number Some("1")
+servers Object None
+  alpha Object None
+    ip String Some("\"10.0.0.1\"")
+servers.disks[0] Object None
+  size Number Some("1")
+servers.disks[1] Object None
+fruit[0] Object None
+fruit[0].variety[0] Object None
+fruit[1] Object None
+fruit[1].variety[0] Object None
+"#;
+    assert_eq!(actual, expected);
+
+    let doc = TomlDoc::new("a. = 1\nb = ?\nc = [{ d = 1 }, ?]\n[g]\nh = 1\n[e.");
+    let mut actual = String::new();
+    render(&symbols(&doc), 0, &mut actual);
+    let expected = r#"c Array None
+  [0] Object None
+    d Number Some("1")
+g Object None
+  h Number Some("1")
+"#;
+    assert_eq!(actual, expected);
+}
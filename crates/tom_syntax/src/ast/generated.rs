@@ -75,6 +75,10 @@ pub type Bool<'a> = BoolNode<RefRoot<'a>>;
 pub struct DateTimeNode<R: TreeRoot<TomTypes> = OwnedRoot>(SyntaxNode<R>);
 pub type DateTime<'a> = DateTimeNode<RefRoot<'a>>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentNode<R: TreeRoot<TomTypes> = OwnedRoot>(SyntaxNode<R>);
+pub type Comment<'a> = CommentNode<RefRoot<'a>>;
+
 impl<'a> AstNode<'a> for Doc<'a> {
     fn cast(node: SyntaxNodeRef<'a>) -> Option<Self>
     where
@@ -94,7 +98,7 @@ impl<'a> From<Doc<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Doc<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Doc> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Doc<'a>> {
         match node.symbol() {
             DOC => Some(DocNode(node)),
             _ => None,
@@ -135,7 +139,7 @@ impl<'a> From<Table<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Table<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Table> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Table<'a>> {
         match node.symbol() {
             TABLE => Some(TableNode(node)),
             _ => None,
@@ -173,7 +177,7 @@ impl<'a> From<ArrayTable<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> ArrayTable<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<ArrayTable> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<ArrayTable<'a>> {
         match node.symbol() {
             ARRAY_TABLE => Some(ArrayTableNode(node)),
             _ => None,
@@ -211,7 +215,7 @@ impl<'a> From<TableHeader<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> TableHeader<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<TableHeader> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<TableHeader<'a>> {
         match node.symbol() {
             TABLE_HEADER => Some(TableHeaderNode(node)),
             _ => None,
@@ -246,7 +250,7 @@ impl<'a> From<Entry<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Entry<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Entry> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Entry<'a>> {
         match node.symbol() {
             ENTRY => Some(EntryNode(node)),
             _ => None,
@@ -284,7 +288,7 @@ impl<'a> From<Key<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Key<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Key> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Key<'a>> {
         match node.symbol() {
             KEY => Some(KeyNode(node)),
             _ => None,
@@ -326,7 +330,7 @@ impl<'a> From<Value<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Value<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Value> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Value<'a>> {
         match node.symbol() {
             VALUE => Some(ValueNode(node)),
             _ => None,
@@ -380,7 +384,7 @@ impl<'a> From<StringLit<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> StringLit<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<StringLit> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<StringLit<'a>> {
         match node.symbol() {
             BASIC_STRING => Some(StringLitNode(node)),
             MULTILINE_BASIC_STRING => Some(StringLitNode(node)),
@@ -418,7 +422,7 @@ impl<'a> From<BareKey<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> BareKey<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<BareKey> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<BareKey<'a>> {
         match node.symbol() {
             BARE_KEY => Some(BareKeyNode(node)),
             _ => None,
@@ -453,7 +457,7 @@ impl<'a> From<Array<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Array<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Array> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Array<'a>> {
         match node.symbol() {
             ARRAY => Some(ArrayNode(node)),
             _ => None,
@@ -488,7 +492,7 @@ impl<'a> From<Dict<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Dict<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Dict> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Dict<'a>> {
         match node.symbol() {
             DICT => Some(DictNode(node)),
             _ => None,
@@ -523,7 +527,7 @@ impl<'a> From<Number<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Number<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Number> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Number<'a>> {
         match node.symbol() {
             NUMBER => Some(NumberNode(node)),
             _ => None,
@@ -558,7 +562,7 @@ impl<'a> From<Bool<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> Bool<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Bool> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Bool<'a>> {
         match node.symbol() {
             BOOL => Some(BoolNode(node)),
             _ => None,
@@ -593,7 +597,7 @@ impl<'a> From<DateTime<'a>> for SyntaxNodeRef<'a> {
 }
 
 impl<'a> DateTime<'a> {
-    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<DateTime> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<DateTime<'a>> {
         match node.symbol() {
             DATE_TIME => Some(DateTimeNode(node)),
             _ => None,
@@ -608,3 +612,38 @@ impl<'a> DateTime<'a> {
         self.syntax().leaf_text().unwrap()
     }
 }
+
+impl<'a> AstNode<'a> for Comment<'a> {
+    fn cast(node: SyntaxNodeRef<'a>) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Self::cast(node)
+    }
+    fn syntax(self) -> SyntaxNodeRef<'a> {
+        self.0
+    }
+}
+
+impl<'a> From<Comment<'a>> for SyntaxNodeRef<'a> {
+    fn from(ast: Comment<'a>) -> SyntaxNodeRef<'a> {
+        ast.syntax()
+    }
+}
+
+impl<'a> Comment<'a> {
+    pub fn cast(node: SyntaxNodeRef<'a>) -> Option<Comment<'a>> {
+        match node.symbol() {
+            COMMENT => Some(CommentNode(node)),
+            _ => None,
+        }
+    }
+
+    pub fn syntax(self) -> SyntaxNodeRef<'a> {
+        self.0
+    }
+
+    pub fn text(self) -> &'a str {
+        self.syntax().leaf_text().unwrap()
+    }
+}
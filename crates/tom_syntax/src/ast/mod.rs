@@ -1,11 +1,19 @@
 //! FIXME: write short doc here
 
 mod generated;
+mod number;
+mod string_lit;
 
 use std::borrow::Cow;
 
-use crate::{ast, AstNode, AstChildren};
-pub use self::generated::*;
+use crate::{
+    ast,
+    datetime::{DateTimeError, TomlDateTime},
+    symbol::*,
+    AstNode, AstChildren, ErrorCode, Symbol, SyntaxError, SyntaxNodeRef,
+};
+pub use self::{generated::*, number::NumberValue};
+pub(crate) use self::string_lit::unescape_basic;
 
 pub trait EntryOwner<'a>: AstNode<'a> {
     fn entries(self) -> AstChildren<'a, ast::Entry<'a>>;
@@ -21,20 +29,59 @@ pub trait KeyOwner<'a>: AstNode<'a> {
 }
 
 impl<'a> ast::Key<'a> {
+    /// Returns the decoded name of the key.
+    ///
+    /// Panics if the key has a syntax error, like the missing key in `[a.]`,
+    /// use `try_name` for keys of a document with errors.
     pub fn name(self) -> Cow<'a, str> {
-        match self.kind() {
-            ast::KeyKind::StringLit(lit) => lit.value(),
-            ast::KeyKind::BareKey(key) => Cow::from(key.text()),
+        self.try_name().expect("malformed key")
+    }
+
+    /// Returns the decoded name of the key, or `None` if the key has a syntax
+    /// error.
+    pub fn try_name(self) -> Option<Cow<'a, str>> {
+        let child = self.syntax().first_child()?;
+        if let Some(lit) = ast::StringLit::cast(child) {
+            Some(lit.value())
+        } else {
+            ast::BareKey::cast(child).map(|key| Cow::from(key.text()))
+        }
+    }
+}
+
+impl<'a> ast::Value<'a> {
+    /// Returns the kind of the value, or `None` if the value is missing or
+    /// has a syntax error, like in `a = ?`.
+    pub fn try_kind(self) -> Option<ast::ValueKind<'a>> {
+        match self.syntax().first_child()?.symbol() {
+            ERROR => None,
+            _ => Some(self.kind()),
         }
     }
 }
 
 impl<'a> ast::StringLit<'a> {
+    /// Returns the decoded contents of the string.
+    ///
+    /// Invalid escape sequences are replaced with `U+FFFD`, use `try_value`
+    /// to detect them.
     pub fn value(self) -> Cow<'a, str> {
-        //FIXME: broken completely
-        let text = self.text();
-        let len = text.len();
-        Cow::from(&text[1..len - 1])
+        string_lit::decode(self, &mut Vec::new())
+    }
+
+    /// Returns the decoded contents of the string, or the error for the first
+    /// invalid escape sequence.
+    pub fn try_value(self) -> Result<Cow<'a, str>, SyntaxError> {
+        let mut errors = Vec::new();
+        let value = string_lit::decode(self, &mut errors);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(value),
+        }
+    }
+
+    pub(crate) fn check(self, errors: &mut Vec<SyntaxError>) {
+        string_lit::decode(self, errors);
     }
 }
 
@@ -45,18 +92,69 @@ impl<'a> ast::Bool<'a> {
 }
 
 impl<'a> ast::Number<'a> {
-    pub fn value(self) -> i64 {
-        self.text().parse().unwrap()
+    /// Decodes the number, returning an error if it is malformed or if an
+    /// integer doesn't fit into `i64`.
+    pub fn value(self) -> Result<NumberValue, SyntaxError> {
+        number::decode(self)
     }
 }
 
 impl<'a> ast::DateTime<'a> {
-    // chrono?
-    pub fn value(self) -> ::std::time::SystemTime {
-        unimplemented!()
+    /// Decodes the date-time, returning an error if it is malformed or if a
+    /// component is out of range, like in `1979-02-30`.
+    pub fn value(self) -> Result<TomlDateTime, SyntaxError> {
+        self.text().parse().map_err(|err: DateTimeError| SyntaxError {
+            range: self.syntax().range(),
+            message: err.message().into(),
+            secondary_range: None,
+            code: ErrorCode::InvalidDateTime,
+        })
+    }
+}
+
+impl<'a> ast::Comment<'a> {
+    /// The text of the comment without the `#` and the surrounding
+    /// whitespace.
+    pub fn content(self) -> &'a str {
+        self.text()[1..].trim()
+    }
+}
+
+impl<'a> ast::Entry<'a> {
+    /// Comments on the lines right above the entry.
+    pub fn leading_comments(self) -> impl Iterator<Item = ast::Comment<'a>> {
+        comments_before(self.syntax(), KEY)
+    }
+
+    /// The comment at the end of the entry's line.
+    pub fn trailing_comment(self) -> Option<ast::Comment<'a>> {
+        self.syntax()
+            .children()
+            .skip_while(|it| it.symbol() != VALUE)
+            .find_map(ast::Comment::cast)
     }
 }
 
+impl<'a> ast::Table<'a> {
+    /// Comments on the lines right above the header.
+    pub fn doc_comments(self) -> impl Iterator<Item = ast::Comment<'a>> {
+        comments_before(self.syntax(), TABLE_HEADER)
+    }
+}
+
+impl<'a> ast::ArrayTable<'a> {
+    /// Comments on the lines right above the header.
+    pub fn doc_comments(self) -> impl Iterator<Item = ast::Comment<'a>> {
+        comments_before(self.syntax(), TABLE_HEADER)
+    }
+}
+
+fn comments_before(node: SyntaxNodeRef, symbol: Symbol) -> impl Iterator<Item = ast::Comment> {
+    node.children()
+        .take_while(move |it| it.symbol() != symbol)
+        .filter_map(ast::Comment::cast)
+}
+
 impl<'a> ast::Value<'a> {
     pub fn as_string(self) -> Option<Cow<'a, str>> {
         match self.kind() {
@@ -74,7 +172,14 @@ impl<'a> ast::Value<'a> {
 
     pub fn as_i64(self) -> Option<i64> {
         match self.kind() {
-            ast::ValueKind::Number(l) => Some(l.value()),
+            ast::ValueKind::Number(l) => l.value().ok()?.as_i64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(self) -> Option<f64> {
+        match self.kind() {
+            ast::ValueKind::Number(l) => Some(l.value().ok()?.as_f64()),
             _ => None,
         }
     }
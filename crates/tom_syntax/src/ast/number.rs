@@ -0,0 +1,150 @@
+//! Decoding of TOML integers and floats.
+
+use crate::{ast, ErrorCode, SyntaxError};
+
+/// The value of a TOML number: TOML integers are 64-bit signed, and floats
+/// are IEEE 754 binary64.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberValue {
+    Integer(i64),
+    Float(f64),
+}
+
+impl NumberValue {
+    pub fn as_i64(self) -> Option<i64> {
+        match self {
+            NumberValue::Integer(v) => Some(v),
+            NumberValue::Float(_) => None,
+        }
+    }
+
+    /// Returns the value as a float; integers are converted, possibly with a
+    /// loss of precision.
+    pub fn as_f64(self) -> f64 {
+        match self {
+            NumberValue::Integer(v) => v as f64,
+            NumberValue::Float(v) => v,
+        }
+    }
+}
+
+pub(crate) fn decode(number: ast::Number) -> Result<NumberValue, SyntaxError> {
+    decode_text(number.text()).map_err(|message| SyntaxError {
+        range: number.syntax().range(),
+        message: message.into(),
+        secondary_range: None,
+        code: ErrorCode::InvalidNumber,
+    })
+}
+
+fn decode_text(text: &str) -> Result<NumberValue, &'static str> {
+    let (negative, unsigned) = match text.as_bytes().first() {
+        Some(b'+') => (false, &text[1..]),
+        Some(b'-') => (true, &text[1..]),
+        _ => (false, text),
+    };
+    let sign = if negative { -1.0 } else { 1.0 };
+
+    match unsigned {
+        "inf" => return Ok(NumberValue::Float(sign * f64::INFINITY)),
+        "nan" => return Ok(NumberValue::Float(sign * f64::NAN)),
+        _ => (),
+    }
+
+    let radix = match unsigned.get(..2) {
+        Some("0x") => Some(16),
+        Some("0o") => Some(8),
+        Some("0b") => Some(2),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        if unsigned.len() != text.len() {
+            return Err("a sign is not allowed in hexadecimal, octal or binary integers");
+        }
+        let digits = strip_underscores(&unsigned[2..], |b| (b as char).is_digit(radix))?;
+        return i64::from_str_radix(&digits, radix)
+            .map(NumberValue::Integer)
+            .map_err(|_| "integer is out of range of a 64-bit signed integer");
+    }
+
+    let (int_part, rest) = split_at_byte(unsigned, |b| b == b'.' || b == b'e' || b == b'E');
+    let int_digits = strip_underscores(int_part, |b| b.is_ascii_digit())?;
+    if int_digits.len() > 1 && int_digits.starts_with('0') {
+        return Err("leading zeros are not allowed");
+    }
+
+    if rest.is_empty() {
+        let mut buff = String::with_capacity(int_digits.len() + 1);
+        if negative {
+            buff.push('-');
+        }
+        buff.push_str(&int_digits);
+        return buff
+            .parse()
+            .map(NumberValue::Integer)
+            .map_err(|_| "integer is out of range of a 64-bit signed integer");
+    }
+
+    let mut buff = String::with_capacity(text.len());
+    if negative {
+        buff.push('-');
+    }
+    buff.push_str(&int_digits);
+
+    let rest = match rest.strip_prefix('.') {
+        Some(rest) => {
+            let (frac_part, rest) = split_at_byte(rest, |b| b == b'e' || b == b'E');
+            let frac_digits = strip_underscores(frac_part, |b| b.is_ascii_digit())?;
+            buff.push('.');
+            buff.push_str(&frac_digits);
+            rest
+        }
+        None => rest,
+    };
+    if !rest.is_empty() {
+        let exp = &rest[1..];
+        let (exp_sign, exp) = match exp.as_bytes().first() {
+            Some(b'+') | Some(b'-') => (&exp[..1], &exp[1..]),
+            _ => ("", exp),
+        };
+        let exp_digits = strip_underscores(exp, |b| b.is_ascii_digit())?;
+        buff.push('e');
+        buff.push_str(exp_sign);
+        buff.push_str(&exp_digits);
+    }
+    buff.parse()
+        .map(NumberValue::Float)
+        .map_err(|_| "invalid float")
+}
+
+fn split_at_byte(text: &str, p: impl Fn(u8) -> bool) -> (&str, &str) {
+    let idx = text.bytes().position(p).unwrap_or(text.len());
+    text.split_at(idx)
+}
+
+/// Checks that `digits` is a non-empty sequence of digits, where each
+/// underscore is surrounded by digits, and removes the underscores.
+fn strip_underscores(digits: &str, is_digit: impl Fn(u8) -> bool) -> Result<String, &'static str> {
+    if digits.is_empty() {
+        return Err("expected a digit");
+    }
+    let mut buff = String::with_capacity(digits.len());
+    let mut prev_is_digit = false;
+    for b in digits.bytes() {
+        if b == b'_' {
+            if !prev_is_digit {
+                return Err("an underscore must be surrounded by digits");
+            }
+            prev_is_digit = false;
+        } else if is_digit(b) {
+            buff.push(b as char);
+            prev_is_digit = true;
+        } else {
+            return Err("invalid digit");
+        }
+    }
+    if !prev_is_digit {
+        return Err("an underscore must be surrounded by digits");
+    }
+    Ok(buff)
+}
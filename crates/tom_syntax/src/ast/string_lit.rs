@@ -0,0 +1,155 @@
+//! Decoding of the four kinds of TOML string literals.
+
+use std::borrow::Cow;
+
+use crate::{ast, symbol::*, ErrorCode, SyntaxError, TextRange, TextUnit};
+
+/// Decodes the literal, reporting each invalid escape sequence to `errors`.
+///
+/// Invalid escapes are replaced with `U+FFFD`, so that the rest of the string
+/// is still usable.
+pub(crate) fn decode<'a>(lit: ast::StringLit<'a>, errors: &mut Vec<SyntaxError>) -> Cow<'a, str> {
+    let text = lit.text();
+    let (delim, multiline, escapes) = match lit.syntax().symbol() {
+        BASIC_STRING => ("\"", false, true),
+        MULTILINE_BASIC_STRING => ("\"\"\"", true, true),
+        LITERAL_STRING => ("'", false, false),
+        MULTILINE_LITERAL_STRING => ("'''", true, false),
+        s => unreachable!("not a string literal: {:?}", s),
+    };
+    let mut start = delim.len().min(text.len());
+    // Unterminated strings lack the closing delimiter.
+    let end = match text[start..].strip_suffix(delim) {
+        Some(body) => start + body.len(),
+        None => text.len(),
+    };
+    if multiline {
+        // A newline immediately following the opening delimiter is trimmed.
+        let body = &text[start..end];
+        if body.starts_with('\n') {
+            start += 1;
+        } else if body.starts_with("\r\n") {
+            start += 2;
+        }
+    }
+    let body = &text[start..end];
+    if !escapes || !body.contains('\\') {
+        return Cow::Borrowed(body);
+    }
+    let offset = lit.syntax().range().start() + TextUnit::from(start as u32);
+    Cow::Owned(unescape(body, offset, multiline, errors))
+}
+
+/// Decodes the escapes in the body of a single line basic string, or returns
+/// `None` if some of them are invalid.
+pub(crate) fn unescape_basic(body: &str) -> Option<String> {
+    let mut errors = Vec::new();
+    let res = unescape(body, 0.into(), false, &mut errors);
+    if errors.is_empty() {
+        Some(res)
+    } else {
+        None
+    }
+}
+
+fn unescape(
+    body: &str,
+    offset: TextUnit,
+    multiline: bool,
+    errors: &mut Vec<SyntaxError>,
+) -> String {
+    let mut buff = String::with_capacity(body.len());
+    let mut chars = body.char_indices().peekable();
+    while let Some((escape_start, c)) = chars.next() {
+        if c != '\\' {
+            buff.push(c);
+            continue;
+        }
+        let mut error = |len: usize, message: &str| {
+            let start = offset + TextUnit::from(escape_start as u32);
+            errors.push(SyntaxError {
+                range: TextRange::offset_len(start, TextUnit::from(len as u32)),
+                message: message.into(),
+                secondary_range: None,
+                code: ErrorCode::InvalidString,
+            })
+        };
+        let (_, c) = match chars.next() {
+            Some(it) => it,
+            None => {
+                error(1, "invalid escape sequence");
+                buff.push('\u{FFFD}');
+                break;
+            }
+        };
+        match c {
+            'b' => buff.push('\u{8}'),
+            't' => buff.push('\t'),
+            'n' => buff.push('\n'),
+            'f' => buff.push('\u{C}'),
+            'r' => buff.push('\r'),
+            '"' => buff.push('"'),
+            '\\' => buff.push('\\'),
+            'u' | 'U' => {
+                let n_digits = if c == 'u' { 4 } else { 8 };
+                let digits_start = escape_start + 2;
+                let digits = body
+                    .get(digits_start..digits_start + n_digits)
+                    .filter(|it| it.bytes().all(|b| b.is_ascii_hexdigit()));
+                let decoded = digits
+                    .and_then(|it| u32::from_str_radix(it, 16).ok())
+                    .and_then(std::char::from_u32);
+                match (digits, decoded) {
+                    (Some(_), Some(decoded)) => {
+                        buff.push(decoded);
+                        for _ in 0..n_digits {
+                            chars.next();
+                        }
+                    }
+                    (Some(_), None) => {
+                        error(2 + n_digits, "invalid unicode scalar value");
+                        buff.push('\u{FFFD}');
+                        for _ in 0..n_digits {
+                            chars.next();
+                        }
+                    }
+                    (None, _) => {
+                        let len = body[digits_start..]
+                            .bytes()
+                            .take(n_digits)
+                            .take_while(u8::is_ascii_hexdigit)
+                            .count();
+                        error(2 + len, "invalid unicode escape");
+                        buff.push('\u{FFFD}');
+                        for _ in 0..len {
+                            chars.next();
+                        }
+                    }
+                }
+            }
+            ' ' | '\t' | '\n' | '\r'
+                if multiline && is_line_ending_backslash(body, escape_start) =>
+            {
+                // Line ending backslash: trim all whitespace, including
+                // newlines, up to the next non-whitespace character. TOML
+                // whitespace is only spaces and tabs, `\u{A0}` is content.
+                while chars
+                    .peek()
+                    .is_some_and(|&(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r'))
+                {
+                    chars.next();
+                }
+            }
+            c => {
+                error(1 + c.len_utf8(), "invalid escape sequence");
+                buff.push('\u{FFFD}');
+            }
+        }
+    }
+    buff
+}
+
+fn is_line_ending_backslash(body: &str, backslash: usize) -> bool {
+    let rest = body[backslash + 1..].trim_start_matches([' ', '\t']);
+    rest.starts_with('\n') || rest.starts_with("\r\n")
+}
@@ -0,0 +1,669 @@
+//! TOML date-time values.
+//!
+//! TOML has four kinds of date-times: offset date-time, local date-time,
+//! local date and local time, all of them are represented by `TomlDateTime`.
+//! With the `chrono` and `time` cargo features enabled, `TomlDateTime` can be
+//! converted to and from the types of the respective crates. The conversions
+//! which involve a date are fallible: TOML years are in `0..=9999`.
+
+use std::{error::Error, fmt, str::FromStr};
+
+/// A valid calendar date, created with `Date::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+/// Time of day, with nanosecond precision, created with `Time::new`.
+/// `second` can be `60` to represent a leap second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Time {
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanosecond: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Offset {
+    /// `Z`, that is UTC.
+    Z,
+    /// Offset from UTC in minutes.
+    Minutes(i16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TomlDateTime {
+    /// `1979-05-27T07:32:00Z`
+    OffsetDateTime(Date, Time, Offset),
+    /// `1979-05-27T07:32:00`
+    LocalDateTime(Date, Time),
+    /// `1979-05-27`
+    LocalDate(Date),
+    /// `07:32:00`
+    LocalTime(Time),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateTimeError {
+    message: &'static str,
+}
+
+impl DateTimeError {
+    fn new(message: &'static str) -> DateTimeError {
+        DateTimeError { message }
+    }
+
+    pub fn message(&self) -> &str {
+        self.message
+    }
+}
+
+impl fmt::Display for DateTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl Error for DateTimeError {}
+
+impl Date {
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Date, DateTimeError> {
+        if year > 9999 {
+            return Err(DateTimeError::new("year must be in 0..=9999"));
+        }
+        if !(1..=12).contains(&month) {
+            return Err(DateTimeError::new("month must be in 1..=12"));
+        }
+        if day < 1 || day > days_in_month(year, month) {
+            return Err(DateTimeError::new("day is out of range for month"));
+        }
+        Ok(Date { year, month, day })
+    }
+
+    pub fn year(self) -> u16 {
+        self.year
+    }
+
+    pub fn month(self) -> u8 {
+        self.month
+    }
+
+    pub fn day(self) -> u8 {
+        self.day
+    }
+}
+
+impl Time {
+    pub fn new(hour: u8, minute: u8, second: u8, nanosecond: u32) -> Result<Time, DateTimeError> {
+        if hour > 23 {
+            return Err(DateTimeError::new("hour must be in 0..=23"));
+        }
+        if minute > 59 {
+            return Err(DateTimeError::new("minute must be in 0..=59"));
+        }
+        if second > 60 {
+            return Err(DateTimeError::new("second must be in 0..=60"));
+        }
+        if nanosecond > 999_999_999 {
+            return Err(DateTimeError::new("nanosecond must be in 0..=999_999_999"));
+        }
+        Ok(Time {
+            hour,
+            minute,
+            second,
+            nanosecond,
+        })
+    }
+
+    pub fn hour(self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(self) -> u8 {
+        self.second
+    }
+
+    pub fn nanosecond(self) -> u32 {
+        self.nanosecond
+    }
+}
+
+impl Offset {
+    pub fn minutes(self) -> i16 {
+        match self {
+            Offset::Z => 0,
+            Offset::Minutes(minutes) => minutes,
+        }
+    }
+}
+
+impl TomlDateTime {
+    pub fn date(&self) -> Option<Date> {
+        match *self {
+            TomlDateTime::OffsetDateTime(date, _, _)
+            | TomlDateTime::LocalDateTime(date, _)
+            | TomlDateTime::LocalDate(date) => Some(date),
+            TomlDateTime::LocalTime(_) => None,
+        }
+    }
+
+    pub fn time(&self) -> Option<Time> {
+        match *self {
+            TomlDateTime::OffsetDateTime(_, time, _)
+            | TomlDateTime::LocalDateTime(_, time)
+            | TomlDateTime::LocalTime(time) => Some(time),
+            TomlDateTime::LocalDate(_) => None,
+        }
+    }
+
+    pub fn offset(&self) -> Option<Offset> {
+        match *self {
+            TomlDateTime::OffsetDateTime(_, _, offset) => Some(offset),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)?;
+        if self.nanosecond != 0 {
+            let fraction = format!("{:09}", self.nanosecond);
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Offset::Z => f.write_str("Z"),
+            Offset::Minutes(minutes) => {
+                let sign = if minutes < 0 { '-' } else { '+' };
+                let minutes = minutes.abs();
+                write!(f, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+            }
+        }
+    }
+}
+
+impl fmt::Display for TomlDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TomlDateTime::OffsetDateTime(date, time, offset) => {
+                write!(f, "{}T{}{}", date, time, offset)
+            }
+            TomlDateTime::LocalDateTime(date, time) => write!(f, "{}T{}", date, time),
+            TomlDateTime::LocalDate(date) => date.fmt(f),
+            TomlDateTime::LocalTime(time) => time.fmt(f),
+        }
+    }
+}
+
+impl FromStr for TomlDateTime {
+    type Err = DateTimeError;
+
+    fn from_str(text: &str) -> Result<TomlDateTime, DateTimeError> {
+        let mut p = Parser { text, pos: 0 };
+        let res = p.date_time()?;
+        if p.pos != text.len() {
+            return Err(DateTimeError::new("unexpected trailing characters"));
+        }
+        Ok(res)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn date_time(&mut self) -> Result<TomlDateTime, DateTimeError> {
+        if self.text.as_bytes().get(2) == Some(&b':') {
+            return Ok(TomlDateTime::LocalTime(self.time()?));
+        }
+        let date = self.date()?;
+        match self.peek() {
+            Some(b'T') | Some(b't') | Some(b' ') => self.pos += 1,
+            _ => return Ok(TomlDateTime::LocalDate(date)),
+        }
+        let time = self.time()?;
+        let offset = match self.peek() {
+            Some(b'Z') | Some(b'z') => {
+                self.pos += 1;
+                Offset::Z
+            }
+            Some(sign @ b'+') | Some(sign @ b'-') => {
+                self.pos += 1;
+                let hours = self.digits(2)?;
+                self.expect(b':')?;
+                let minutes = self.digits(2)?;
+                if hours > 23 || minutes > 59 {
+                    return Err(DateTimeError::new("offset is out of range"));
+                }
+                let minutes = (hours * 60 + minutes) as i16;
+                Offset::Minutes(if sign == b'-' { -minutes } else { minutes })
+            }
+            _ => return Ok(TomlDateTime::LocalDateTime(date, time)),
+        };
+        Ok(TomlDateTime::OffsetDateTime(date, time, offset))
+    }
+
+    fn date(&mut self) -> Result<Date, DateTimeError> {
+        let year = self.digits(4)?;
+        self.expect(b'-')?;
+        let month = self.digits(2)?;
+        self.expect(b'-')?;
+        let day = self.digits(2)?;
+        Date::new(year as u16, month as u8, day as u8)
+    }
+
+    fn time(&mut self) -> Result<Time, DateTimeError> {
+        let hour = self.digits(2)?;
+        self.expect(b':')?;
+        let minute = self.digits(2)?;
+        self.expect(b':')?;
+        let second = self.digits(2)?;
+        let mut nanosecond = 0;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            let start = self.pos;
+            while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                self.pos += 1;
+            }
+            let fraction = &self.text[start..self.pos];
+            if fraction.is_empty() {
+                return Err(DateTimeError::new("expected fractional seconds"));
+            }
+            // Digits beyond nanosecond precision are truncated.
+            for (i, b) in fraction.bytes().take(9).enumerate() {
+                nanosecond += u32::from(b - b'0') * 10u32.pow(8 - i as u32);
+            }
+        }
+        Time::new(hour as u8, minute as u8, second as u8, nanosecond)
+    }
+
+    fn digits(&mut self, n: usize) -> Result<u32, DateTimeError> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + n)
+            .filter(|it| it.bytes().all(|b| b.is_ascii_digit()))
+            .ok_or_else(|| DateTimeError::new("expected a digit"))?;
+        self.pos += n;
+        Ok(digits.parse().unwrap())
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), DateTimeError> {
+        if self.peek() != Some(b) {
+            return Err(DateTimeError::new(match b {
+                b'-' => "expected `-`",
+                b':' => "expected `:`",
+                _ => unreachable!(),
+            }));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+/// The year of a date of `chrono` or `time`, which may be negative or have
+/// more than four digits.
+#[cfg(any(feature = "chrono", feature = "time"))]
+fn year(year: i32) -> Result<u16, DateTimeError> {
+    use std::convert::TryFrom;
+
+    u16::try_from(year).map_err(|_| DateTimeError::new("year must be in 0..=9999"))
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use std::convert::{TryFrom, TryInto};
+
+    use chrono::{Datelike, Timelike};
+
+    use super::{year, Date, DateTimeError, Offset, Time, TomlDateTime};
+
+    impl TryFrom<chrono::NaiveDate> for Date {
+        type Error = DateTimeError;
+
+        fn try_from(date: chrono::NaiveDate) -> Result<Date, DateTimeError> {
+            Date::new(year(date.year())?, date.month() as u8, date.day() as u8)
+        }
+    }
+
+    impl From<chrono::NaiveTime> for Time {
+        fn from(time: chrono::NaiveTime) -> Time {
+            // chrono represents leap seconds as a nanosecond overflow.
+            let (second, nanosecond) = if time.nanosecond() >= 1_000_000_000 {
+                (time.second() + 1, time.nanosecond() - 1_000_000_000)
+            } else {
+                (time.second(), time.nanosecond())
+            };
+            Time {
+                hour: time.hour() as u8,
+                minute: time.minute() as u8,
+                second: second as u8,
+                nanosecond,
+            }
+        }
+    }
+
+    impl TryFrom<chrono::NaiveDate> for TomlDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date: chrono::NaiveDate) -> Result<TomlDateTime, DateTimeError> {
+            Ok(TomlDateTime::LocalDate(date.try_into()?))
+        }
+    }
+
+    impl From<chrono::NaiveTime> for TomlDateTime {
+        fn from(time: chrono::NaiveTime) -> TomlDateTime {
+            TomlDateTime::LocalTime(time.into())
+        }
+    }
+
+    impl TryFrom<chrono::NaiveDateTime> for TomlDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: chrono::NaiveDateTime) -> Result<TomlDateTime, DateTimeError> {
+            Ok(TomlDateTime::LocalDateTime(
+                date_time.date().try_into()?,
+                date_time.time().into(),
+            ))
+        }
+    }
+
+    impl TryFrom<chrono::DateTime<chrono::FixedOffset>> for TomlDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(
+            date_time: chrono::DateTime<chrono::FixedOffset>,
+        ) -> Result<TomlDateTime, DateTimeError> {
+            let local = date_time.naive_local();
+            let seconds = date_time.offset().local_minus_utc();
+            if seconds % 60 != 0 {
+                return Err(DateTimeError::new(
+                    "offset must be a whole number of minutes",
+                ));
+            }
+            Ok(TomlDateTime::OffsetDateTime(
+                local.date().try_into()?,
+                local.time().into(),
+                Offset::Minutes((seconds / 60) as i16),
+            ))
+        }
+    }
+
+    impl TryFrom<Date> for chrono::NaiveDate {
+        type Error = DateTimeError;
+
+        fn try_from(date: Date) -> Result<chrono::NaiveDate, DateTimeError> {
+            chrono::NaiveDate::from_ymd_opt(
+                i32::from(date.year),
+                u32::from(date.month),
+                u32::from(date.day),
+            )
+            .ok_or_else(|| DateTimeError::new("invalid date"))
+        }
+    }
+
+    impl TryFrom<Time> for chrono::NaiveTime {
+        type Error = DateTimeError;
+
+        fn try_from(time: Time) -> Result<chrono::NaiveTime, DateTimeError> {
+            let (second, nanosecond) = if time.second == 60 {
+                (59, time.nanosecond + 1_000_000_000)
+            } else {
+                (time.second, time.nanosecond)
+            };
+            chrono::NaiveTime::from_hms_nano_opt(
+                u32::from(time.hour),
+                u32::from(time.minute),
+                u32::from(second),
+                nanosecond,
+            )
+            .ok_or_else(|| DateTimeError::new("invalid time"))
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for chrono::NaiveDate {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: TomlDateTime) -> Result<chrono::NaiveDate, DateTimeError> {
+            match date_time {
+                TomlDateTime::LocalDate(date) => chrono::NaiveDate::try_from(date),
+                _ => Err(DateTimeError::new("expected a local date")),
+            }
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for chrono::NaiveTime {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: TomlDateTime) -> Result<chrono::NaiveTime, DateTimeError> {
+            match date_time {
+                TomlDateTime::LocalTime(time) => chrono::NaiveTime::try_from(time),
+                _ => Err(DateTimeError::new("expected a local time")),
+            }
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for chrono::NaiveDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: TomlDateTime) -> Result<chrono::NaiveDateTime, DateTimeError> {
+            match date_time {
+                TomlDateTime::LocalDateTime(date, time) => Ok(chrono::NaiveDateTime::new(
+                    chrono::NaiveDate::try_from(date)?,
+                    chrono::NaiveTime::try_from(time)?,
+                )),
+                _ => Err(DateTimeError::new("expected a local date-time")),
+            }
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for chrono::DateTime<chrono::FixedOffset> {
+        type Error = DateTimeError;
+
+        fn try_from(
+            date_time: TomlDateTime,
+        ) -> Result<chrono::DateTime<chrono::FixedOffset>, DateTimeError> {
+            match date_time {
+                TomlDateTime::OffsetDateTime(date, time, offset) => {
+                    let local = chrono::NaiveDateTime::new(
+                        chrono::NaiveDate::try_from(date)?,
+                        chrono::NaiveTime::try_from(time)?,
+                    );
+                    let offset = chrono::FixedOffset::east_opt(i32::from(offset.minutes()) * 60)
+                        .ok_or_else(|| DateTimeError::new("invalid offset"))?;
+                    local
+                        .and_local_timezone(offset)
+                        .single()
+                        .ok_or_else(|| DateTimeError::new("invalid date-time"))
+                }
+                _ => Err(DateTimeError::new("expected an offset date-time")),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impls {
+    use std::convert::{TryFrom, TryInto};
+
+    use super::{year, Date, DateTimeError, Offset, Time, TomlDateTime};
+
+    impl TryFrom<time::Date> for Date {
+        type Error = DateTimeError;
+
+        fn try_from(date: time::Date) -> Result<Date, DateTimeError> {
+            Date::new(year(date.year())?, date.month() as u8, date.day())
+        }
+    }
+
+    impl From<time::Time> for Time {
+        fn from(time: time::Time) -> Time {
+            Time {
+                hour: time.hour(),
+                minute: time.minute(),
+                second: time.second(),
+                nanosecond: time.nanosecond(),
+            }
+        }
+    }
+
+    impl TryFrom<time::Date> for TomlDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date: time::Date) -> Result<TomlDateTime, DateTimeError> {
+            Ok(TomlDateTime::LocalDate(date.try_into()?))
+        }
+    }
+
+    impl From<time::Time> for TomlDateTime {
+        fn from(time: time::Time) -> TomlDateTime {
+            TomlDateTime::LocalTime(time.into())
+        }
+    }
+
+    impl TryFrom<time::PrimitiveDateTime> for TomlDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: time::PrimitiveDateTime) -> Result<TomlDateTime, DateTimeError> {
+            Ok(TomlDateTime::LocalDateTime(
+                date_time.date().try_into()?,
+                date_time.time().into(),
+            ))
+        }
+    }
+
+    impl TryFrom<time::OffsetDateTime> for TomlDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: time::OffsetDateTime) -> Result<TomlDateTime, DateTimeError> {
+            let offset = date_time.offset();
+            if offset.seconds_past_minute() != 0 {
+                return Err(DateTimeError::new(
+                    "offset must be a whole number of minutes",
+                ));
+            }
+            Ok(TomlDateTime::OffsetDateTime(
+                date_time.date().try_into()?,
+                date_time.time().into(),
+                Offset::Minutes(offset.whole_minutes()),
+            ))
+        }
+    }
+
+    impl TryFrom<Date> for time::Date {
+        type Error = DateTimeError;
+
+        fn try_from(date: Date) -> Result<time::Date, DateTimeError> {
+            let month = time::Month::try_from(date.month)
+                .map_err(|_| DateTimeError::new("invalid date"))?;
+            time::Date::from_calendar_date(i32::from(date.year), month, date.day)
+                .map_err(|_| DateTimeError::new("invalid date"))
+        }
+    }
+
+    impl TryFrom<Time> for time::Time {
+        type Error = DateTimeError;
+
+        fn try_from(time: Time) -> Result<time::Time, DateTimeError> {
+            time::Time::from_hms_nano(time.hour, time.minute, time.second, time.nanosecond)
+                .map_err(|_| DateTimeError::new("invalid time"))
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for time::Date {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: TomlDateTime) -> Result<time::Date, DateTimeError> {
+            match date_time {
+                TomlDateTime::LocalDate(date) => time::Date::try_from(date),
+                _ => Err(DateTimeError::new("expected a local date")),
+            }
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for time::Time {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: TomlDateTime) -> Result<time::Time, DateTimeError> {
+            match date_time {
+                TomlDateTime::LocalTime(time) => time::Time::try_from(time),
+                _ => Err(DateTimeError::new("expected a local time")),
+            }
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for time::PrimitiveDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: TomlDateTime) -> Result<time::PrimitiveDateTime, DateTimeError> {
+            match date_time {
+                TomlDateTime::LocalDateTime(date, time) => Ok(time::PrimitiveDateTime::new(
+                    time::Date::try_from(date)?,
+                    time::Time::try_from(time)?,
+                )),
+                _ => Err(DateTimeError::new("expected a local date-time")),
+            }
+        }
+    }
+
+    impl TryFrom<TomlDateTime> for time::OffsetDateTime {
+        type Error = DateTimeError;
+
+        fn try_from(date_time: TomlDateTime) -> Result<time::OffsetDateTime, DateTimeError> {
+            match date_time {
+                TomlDateTime::OffsetDateTime(date, time, offset) => {
+                    let minutes = offset.minutes();
+                    let offset = time::UtcOffset::from_hms(
+                        (minutes / 60) as i8,
+                        (minutes % 60) as i8,
+                        0,
+                    )
+                    .map_err(|_| DateTimeError::new("invalid offset"))?;
+                    Ok(time::PrimitiveDateTime::new(
+                        time::Date::try_from(date)?,
+                        time::Time::try_from(time)?,
+                    )
+                    .assume_offset(offset))
+                }
+                _ => Err(DateTimeError::new("expected an offset date-time")),
+            }
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! Deserializes a `TomlDoc` into Rust types with serde.
+//!
+//! The structure of the data comes from the semantic model (`Map` and
+//! `Item`), while the text ranges come from the syntax tree, so that an error
+//! points to the value which couldn't be deserialized.
+
+use std::{fmt, slice, vec};
+
+use serde::de::{self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+
+use crate::{
+    ast,
+    model::{entry_value, is_array_table_key},
+    spanned::de as spanned,
+    Item, Map, TextRange, TomlDoc,
+};
+
+/// Parses `text` and deserializes it into a `T`.
+pub fn from_str<T: DeserializeOwned>(text: &str) -> Result<T, Error> {
+    from_doc(&TomlDoc::new(text))
+}
+
+/// Deserializes the document into a `T`. Fails with the first syntax error,
+/// if the document has any.
+pub fn from_doc<T: DeserializeOwned>(doc: &TomlDoc) -> Result<T, Error> {
+    if let Some(error) = doc.errors().into_iter().next() {
+        return Err(Error {
+            message: error.message().to_string(),
+            range: Some(error.range()),
+        });
+    }
+    let root = Item::Map(doc.model());
+    T::deserialize(ValueDeserializer::element(
+        &root,
+        doc.cst().range(),
+        None,
+        None,
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+    range: Option<TextRange>,
+}
+
+impl Error {
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+
+    /// The range of the value which failed to deserialize. `None` only if
+    /// the error is not related to any particular value.
+    pub fn range(&self) -> Option<TextRange> {
+        self.range
+    }
+
+    fn with_range(mut self, range: TextRange) -> Error {
+        if self.range.is_none() {
+            self.range = Some(range);
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error {
+            message: msg.to_string(),
+            range: None,
+        }
+    }
+}
+
+struct ValueDeserializer<'m, 'a> {
+    item: &'m Item<'a>,
+    range: TextRange,
+    key_range: Option<TextRange>,
+    /// The syntax of the value, if it is written inline (`key = value`).
+    value: Option<ast::Value<'a>>,
+    /// The keys which define the value, in the document order.
+    keys: Vec<ast::Key<'a>>,
+}
+
+impl<'m, 'a> ValueDeserializer<'m, 'a> {
+    fn entry(map: &'m Map<'a>, name: &str, item: &'m Item<'a>) -> ValueDeserializer<'m, 'a> {
+        let keys = map.get_keys(name);
+        let value = entry_value(&keys);
+        let range = match (value, keys.first()) {
+            (Some(value), _) => value.syntax().range(),
+            (None, Some(key)) => key.syntax().range(),
+            (None, None) => TextRange::offset_len(0.into(), 0.into()),
+        };
+        ValueDeserializer {
+            item,
+            range,
+            key_range: keys.first().map(|key| key.syntax().range()),
+            value,
+            keys,
+        }
+    }
+
+    fn element(
+        item: &'m Item<'a>,
+        range: TextRange,
+        key_range: Option<TextRange>,
+        value: Option<ast::Value<'a>>,
+    ) -> Self {
+        ValueDeserializer {
+            item,
+            range,
+            key_range,
+            value,
+            keys: Vec::new(),
+        }
+    }
+
+    fn seq(&self, items: &'m [Item<'a>]) -> SeqAccess<'m, 'a> {
+        let elements: Vec<Element<'a>> = match self.value {
+            Some(value) => match value.kind() {
+                ast::ValueKind::Array(array) => array
+                    .values()
+                    .map(|it| (it.syntax().range(), None, Some(it)))
+                    .collect(),
+                _ => Vec::new(),
+            },
+            // An array of tables: each `[[header]]` starts an element.
+            None => self
+                .keys
+                .iter()
+                .filter(|&&key| is_array_table_key(key))
+                .map(|key| (key.syntax().range(), Some(key.syntax().range()), None))
+                .collect(),
+        };
+        SeqAccess {
+            items: items.iter(),
+            elements: elements.into_iter(),
+            range: self.range,
+        }
+    }
+}
+
+/// The value range, the key range and the syntax of an array element.
+type Element<'a> = (TextRange, Option<TextRange>, Option<ast::Value<'a>>);
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let range = self.range;
+        let res = match self.item {
+            Item::Map(map) => visitor.visit_map(MapAccess::new(map)),
+            Item::Array(items) => visitor.visit_seq(self.seq(items)),
+            Item::Integer(value) => visitor.visit_i64(*value),
+            Item::Float(value) => visitor.visit_f64(*value),
+            Item::Bool(value) => visitor.visit_bool(*value),
+            Item::DateTime(value) => visitor.visit_string(value.to_string()),
+            Item::String(value) => visitor.visit_str(value),
+        };
+        res.map_err(|e| e.with_range(range))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if name == spanned::NAME {
+            return visitor.visit_map(SpannedAccess::new(self));
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let range = self.range;
+        let res = match self.item {
+            Item::String(value) => visitor.visit_enum(value.as_str().into_deserializer()),
+            Item::Map(map) if map.len() == 1 => visitor.visit_enum(EnumAccess { map }),
+            _ => Err(de::Error::invalid_type(unexpected(self.item), &"an enum")),
+        };
+        res.map_err(|e| e.with_range(range))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        identifier
+    }
+}
+
+fn unexpected<'m>(item: &'m Item) -> de::Unexpected<'m> {
+    match item {
+        Item::Map(_) => de::Unexpected::Map,
+        Item::Array(_) => de::Unexpected::Seq,
+        Item::Integer(value) => de::Unexpected::Signed(*value),
+        Item::Float(value) => de::Unexpected::Float(*value),
+        Item::Bool(value) => de::Unexpected::Bool(*value),
+        Item::DateTime(_) => de::Unexpected::Other("date-time"),
+        Item::String(value) => de::Unexpected::Str(value),
+    }
+}
+
+struct SeqAccess<'m, 'a> {
+    items: slice::Iter<'m, Item<'a>>,
+    elements: vec::IntoIter<Element<'a>>,
+    /// The range of the whole array, used if an element has no syntax.
+    range: TextRange,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let item = match self.items.next() {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        let (range, key_range, value) = self.elements.next().unwrap_or((self.range, None, None));
+        seed.deserialize(ValueDeserializer::element(item, range, key_range, value))
+            .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// Feeds the ranges and the value to `Spanned`'s visitor.
+struct SpannedAccess<'m, 'a> {
+    ranges: vec::IntoIter<(&'static str, u32)>,
+    range: Option<u32>,
+    value: Option<ValueDeserializer<'m, 'a>>,
+}
+
+impl<'m, 'a> SpannedAccess<'m, 'a> {
+    fn new(value: ValueDeserializer<'m, 'a>) -> SpannedAccess<'m, 'a> {
+        let mut ranges = Vec::new();
+        if let Some(range) = value.key_range {
+            ranges.push((spanned::KEY_START, range.start().to_usize() as u32));
+            ranges.push((spanned::KEY_END, range.end().to_usize() as u32));
+        }
+        ranges.push((spanned::VALUE_START, value.range.start().to_usize() as u32));
+        ranges.push((spanned::VALUE_END, value.range.end().to_usize() as u32));
+        SpannedAccess {
+            ranges: ranges.into_iter(),
+            range: None,
+            value: Some(value),
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for SpannedAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let field = match self.ranges.next() {
+            Some((field, offset)) => {
+                self.range = Some(offset);
+                field
+            }
+            None if self.value.is_some() => spanned::VALUE,
+            None => return Ok(None),
+        };
+        seed.deserialize(field.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        match self.range.take() {
+            Some(offset) => seed.deserialize(offset.into_deserializer()),
+            None => seed.deserialize(
+                self.value
+                    .take()
+                    .expect("next_value_seed called before next_key_seed"),
+            ),
+        }
+    }
+}
+
+struct MapAccess<'m, 'a> {
+    map: &'m Map<'a>,
+    entries: Box<dyn Iterator<Item = (&'m str, &'m Item<'a>)> + 'm>,
+    next: Option<(&'m str, &'m Item<'a>)>,
+}
+
+impl<'m, 'a> MapAccess<'m, 'a> {
+    fn new(map: &'m Map<'a>) -> MapAccess<'m, 'a> {
+        MapAccess {
+            map,
+            entries: Box::new(map.iter()),
+            next: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let (name, item) = match self.entries.next() {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        self.next = Some((name, item));
+        let res = seed.deserialize(name.to_string().into_deserializer());
+        match self.map.get_keys(name).first() {
+            Some(key) => res
+                .map(Some)
+                .map_err(|e: Error| e.with_range(key.syntax().range())),
+            None => res.map(Some),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (name, item) = self
+            .next
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(ValueDeserializer::entry(self.map, name, item))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.map.len())
+    }
+}
+
+struct EnumAccess<'m, 'a> {
+    map: &'m Map<'a>,
+}
+
+impl<'de, 'm, 'a> de::EnumAccess<'de> for EnumAccess<'m, 'a> {
+    type Error = Error;
+    type Variant = ValueDeserializer<'m, 'a>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let (name, item) = self.map.iter().next().unwrap();
+        let value = ValueDeserializer::entry(self.map, name, item);
+        let variant = seed
+            .deserialize(name.to_string().into_deserializer())
+            .map_err(|e: Error| e.with_range(value.range))?;
+        Ok((variant, value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer<'_, '_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::IgnoredAny::deserialize(self).map(|_| ())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -1,340 +1,841 @@
-//! FIXME: write short doc here
+//! Editing of documents.
+//!
+//! Syntax trees are immutable, so editing is a session: `TomlDoc::start_edit`
+//! returns an `Edit`, which records operations against the nodes of the
+//! original document, and `Edit::finish_edit_*` builds a new document. Only
+//! the nodes on the paths from the root to the edited nodes are rebuilt, the
+//! rest of the green tree is shared with the original document.
+//!
+//! Nodes are always inserted, replaced and swapped as they are in the
+//! original document, without the other edits to their descendants. New
+//! nodes are created by the `Edit::new_*` functions, each returns a detached
+//! node, which is the root of its own tree.
+//!
+//! By default, the whitespace around the edited nodes is fixed up to follow
+//! the `Style` of the original document: inserted nodes are respaced and
+//! separated from their neighbours, and detached nodes take their
+//! whitespace and commas with them.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fmt,
+    hash::BuildHasher,
+};
 
-use std::{mem};
-use {
-    CstNode, TomlDoc, ChunkedText,
+use crate::{
     ast,
-    parser,
-    tree::InsertPos,
+    rtree::GreenNode,
+    style::{Parent, Style},
     symbol::*,
+    GreenBuilder, Item, SmolStr, Symbol, SyntaxNode, SyntaxNodeRef, TomlDateTime, TomlDoc,
+    WalkEvent,
 };
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-pub enum Position {
-    After(CstNode),
-    Before(CstNode),
-    AppendTo(CstNode),
-    PrependTo(CstNode),
+pub enum Position<'a> {
+    After(SyntaxNodeRef<'a>),
+    Before(SyntaxNodeRef<'a>),
+    AppendTo(SyntaxNodeRef<'a>),
+    PrependTo(SyntaxNodeRef<'a>),
 }
 
 impl TomlDoc {
-    pub fn start_edit(&mut self) {
-        self.edit_in_progress = true;
+    pub fn start_edit(&self) -> Edit<'_> {
+        Edit {
+            doc: self,
+            changes: HashMap::new(),
+            smart_ws: true,
+        }
     }
+}
+
+pub struct Edit<'a> {
+    pub(crate) doc: &'a TomlDoc,
+    changes: HashMap<SyntaxNodeRef<'a>, Changes>,
+    smart_ws: bool,
+}
+
+#[derive(Default)]
+struct Changes {
+    /// `Some(None)` if the node is detached.
+    replacement: Option<Option<GreenNode>>,
+    before: Vec<GreenNode>,
+    after: Vec<GreenNode>,
+    /// For the children of the node.
+    first: Vec<GreenNode>,
+    last: Vec<GreenNode>,
+}
+
+impl<'a> Edit<'a> {
+    /// Whether to fix up the whitespace around the edited nodes, which is
+    /// the default.
     pub fn set_smart_ws(&mut self, smart: bool) {
         self.smart_ws = smart;
     }
-    pub fn finish_edit_no_reparse(&mut self) {
-        let mut data = mem::replace(&mut self.data, Vec::new());
-        self.recalculate_ranges(&mut data);
-        self.data = data;
-        self.edit_in_progress = false;
+
+    /// Builds the new document, without reparsing it. The new document has
+    /// only the validation errors, unless the original document has syntax
+    /// errors: those can't be carried over the edited nodes, so the new
+    /// document is reparsed instead.
+    pub fn finish_edit_no_reparse(self) -> TomlDoc {
+        // The deepest nodes whose children change, they and their
+        // ancestors are rebuilt.
+        let dirty: Vec<SyntaxNodeRef> = self
+            .changes
+            .iter()
+            .filter_map(|(&node, changes)| {
+                let is_parent_dirty = changes.replacement.is_some()
+                    || !changes.before.is_empty()
+                    || !changes.after.is_empty();
+                if is_parent_dirty {
+                    node.parent()
+                } else {
+                    Some(node)
+                }
+            })
+            .collect();
+        let style = if self.smart_ws {
+            Some(Style::infer(self.doc.cst()))
+        } else {
+            None
+        };
+        let green = self.rebuild(self.doc.cst(), &self.doc.green, &dirty, style.as_ref());
+        if !self.doc.root.root_data().is_empty() {
+            let doc = TomlDoc::from_green(green, Vec::new());
+            return TomlDoc::new(&doc.cst().get_text());
+        }
+        TomlDoc::from_green(green, Vec::new())
     }
-    pub fn finish_edit_full_reparse(&mut self) {
-        let text = self.cst().get_text(self);
-        *self = TomlDoc::new(&text);
+
+    /// Builds the new document and reparses its text from scratch.
+    pub fn finish_edit_full_reparse(self) -> TomlDoc {
+        let doc = self.finish_edit_no_reparse();
+        TomlDoc::new(&doc.cst().get_text())
     }
 
-    pub fn insert(&mut self, what: impl Into<CstNode>, where_: Position) {
-        self.assert_edit();
-        let new_node = what.into();
+    pub fn insert<'b>(&mut self, what: impl Into<SyntaxNodeRef<'b>>, where_: Position<'a>) {
+        let green = green(what.into());
         match where_ {
             Position::After(sibling) => {
-                let parent = sibling.parent(self).unwrap();
-                self.tree
-                    .insert_child(parent.0, new_node.0, InsertPos::After(sibling.0));
+                assert_has_parent(sibling);
+                self.changes(sibling).after.insert(0, green);
             }
             Position::Before(sibling) => {
-                let parent = sibling.parent(self).unwrap();
-                self.tree
-                    .insert_child(parent.0, new_node.0, InsertPos::Before(sibling.0));
-            }
-            Position::AppendTo(parent) => {
-                self.tree
-                    .insert_child(parent.0, new_node.0, InsertPos::Last);
+                assert_has_parent(sibling);
+                self.changes(sibling).before.push(green);
             }
-            Position::PrependTo(parent) => {
-                self.tree
-                    .insert_child(parent.0, new_node.0, InsertPos::First);
-            }
-        };
-        if self.smart_ws {
-            self.fix_ws(new_node);
-        } else {
-            covered_by!("basic_insertion_no_ws");
+            Position::AppendTo(parent) => self.changes(parent).last.push(green),
+            Position::PrependTo(parent) => self.changes(parent).first.insert(0, green),
         }
     }
 
-    pub fn replace(&mut self, what: impl Into<CstNode>, replacement: impl Into<CstNode>) {
+    pub fn replace<'b>(
+        &mut self,
+        what: impl Into<SyntaxNodeRef<'a>>,
+        replacement: impl Into<SyntaxNodeRef<'b>>,
+    ) {
         let what = what.into();
-        let replacement = replacement.into();
-        self.tree.replace(what.0, replacement.0);
+        assert_has_parent(what);
+        let green = green(replacement.into());
+        self.changes(what).replacement = Some(Some(green));
     }
 
-    pub fn detach(&mut self, what: impl Into<CstNode>) {
+    pub fn detach(&mut self, what: impl Into<SyntaxNodeRef<'a>>) {
         let what = what.into();
-        self.assert_edit();
-        let siblings = (what.prev_sibling(self), what.next_sibling(&self));
-        self.tree.detach(what.0);
-        match siblings {
-            (Some(left), Some(right)) => {
-                if self.smart_ws
-                    && left.symbol(self) == WHITESPACE
-                    && right.symbol(self) == WHITESPACE
-                {
-                    self.tree.detach(right.0);
-                }
-            }
-            _ => (),
-        }
+        assert_has_parent(what);
+        self.changes(what).replacement = Some(None);
     }
 
-    pub fn swap(&mut self, node1: impl Into<CstNode>, node2: impl Into<CstNode>) {
+    pub fn swap(
+        &mut self,
+        node1: impl Into<SyntaxNodeRef<'a>>,
+        node2: impl Into<SyntaxNodeRef<'a>>,
+    ) {
         let node1 = node1.into();
         let node2 = node2.into();
-        let tmp = self.new_whitespace("");
-        self.replace(node1, tmp);
+        self.replace(node1, node2);
         self.replace(node2, node1);
-        self.replace(tmp, node2);
     }
 
-    pub fn new_key(&mut self, name: &str) -> ast::Key {
-        let res = self
-            .new_entry_from_text(&format!("{} = 92", escaped_key(name)))
-            .keys(self)
-            .next()
-            .unwrap();
-        self.detach(res);
-        res
+    /// Replaces the comments right above an entry or a `[table]` header,
+    /// one line for each string. An empty slice removes them.
+    pub fn set_leading_comments(&mut self, node: impl Into<SyntaxNodeRef<'a>>, comments: &[&str]) {
+        let node = node.into();
+        let anchor = match node.symbol() {
+            ENTRY => KEY,
+            TABLE | ARRAY_TABLE => TABLE_HEADER,
+            _ => panic!("only entries and tables have comments: {:?}", node),
+        };
+        assert_not_inline(node);
+        let anchor = node.children().find(|it| it.symbol() == anchor).unwrap();
+        for child in node.children().take_while(|&it| it != anchor) {
+            self.detach(child);
+        }
+        let indent = node
+            .prev_sibling()
+            .and_then(|it| it.leaf_text())
+            .and_then(|ws| ws.rfind('\n').map(|idx| ws[idx + 1..].to_string()))
+            .unwrap_or_default();
+        for &line in comments {
+            self.insert(self.new_comment(line).borrowed(), Position::Before(anchor));
+            let ws = self.new_whitespace(&format!("\n{}", indent));
+            self.insert(ws.borrowed(), Position::Before(anchor));
+        }
     }
 
-    pub fn new_value_from_text(&mut self, text: &str) -> ast::Value {
-        let res = self
-            .new_entry_from_text(&format!("foo = {}", text))
-            .value(self);
-        self.detach(res);
-        res
+    /// Replaces the comment at the end of the entry's line, `None` removes
+    /// it.
+    pub fn set_trailing_comment(&mut self, entry: ast::Entry<'a>, comment: Option<&str>) {
+        let node = entry.syntax();
+        assert_not_inline(node);
+        let trailing = node
+            .children()
+            .skip_while(|it| it.symbol() != VALUE)
+            .skip(1);
+        for child in trailing {
+            self.detach(child);
+        }
+        if let Some(comment) = comment {
+            self.insert(
+                self.new_whitespace(" ").borrowed(),
+                Position::AppendTo(node),
+            );
+            self.insert(
+                self.new_comment(comment).borrowed(),
+                Position::AppendTo(node),
+            );
+        }
     }
 
-    pub fn new_value(&mut self, val: impl IntoValue) -> ast::Value {
-        self.new_value_from_text(&val.value_text())
+    pub fn new_key(&self, name: &str) -> SyntaxNode {
+        let doc = TomlDoc::new(&format!("{} = 92", escaped_key(name)));
+        let key = doc.ast().entries().next().unwrap().keys().next().unwrap();
+        detached(key.syntax())
     }
 
-    pub fn new_value_dict(&mut self, entries: impl Iterator<Item = ast::Entry>) -> ast::Value {
-        let buff = join(self, entries, '{', '}');
-        let res = self
-            .new_entry_from_text(&format!("foo = {}", buff))
-            .value(self);
-        self.detach(res);
-        res
+    pub fn new_value_from_text(&self, text: &str) -> SyntaxNode {
+        let doc = TomlDoc::new(&format!("foo = {}", text));
+        let value = doc.ast().entries().next().unwrap().value();
+        detached(value.syntax())
     }
 
-    pub fn new_value_array(&mut self, entries: impl Iterator<Item = ast::Value>) -> ast::Value {
-        let buff = join(self, entries, '[', ']');
-        let res = self
-            .new_entry_from_text(&format!("foo = {}", buff))
-            .value(self);
-        self.detach(res);
-        res
+    /// Panics if the value can't be written in TOML, like a `u64` which is
+    /// out of range of `i64`.
+    pub fn new_value(&self, val: impl IntoValue) -> SyntaxNode {
+        match val.value_text() {
+            Ok(text) => self.new_value_from_text(&text),
+            Err(err) => panic!("{}", err),
+        }
     }
 
-    pub fn new_dict_from_text(&mut self, text: &str) -> ast::Dict {
-        match self.new_value_from_text(text).kind(self) {
-            ast::ValueKind::Dict(d) => d,
+    pub fn new_value_dict<'b>(
+        &self,
+        entries: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+    ) -> SyntaxNode {
+        self.new_value_from_text(&join(entries, '{', '}'))
+    }
+
+    pub fn new_value_array<'b>(
+        &self,
+        values: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+    ) -> SyntaxNode {
+        self.new_value_from_text(&join(values, '[', ']'))
+    }
+
+    pub fn new_dict_from_text(&self, text: &str) -> SyntaxNode {
+        let value = self.new_value_from_text(text);
+        match ast::Value::cast(value.borrowed()).map(ast::Value::kind) {
+            Some(ast::ValueKind::Dict(d)) => detached(d.syntax()),
             _ => panic!("not a valid dict: {:?}", text),
         }
     }
 
-    pub fn new_entry_from_text(&mut self, text: &str) -> ast::Entry {
-        let entry = self.new_doc_from_text(text).entries(self).next().unwrap();
-        self.detach(entry);
-        entry
+    pub fn new_entry_from_text(&self, text: &str) -> SyntaxNode {
+        let doc = TomlDoc::new(text);
+        let entry = doc.ast().entries().next().unwrap();
+        detached(entry.syntax())
     }
 
-    pub fn new_entry(
-        &mut self,
-        keys: impl Iterator<Item = ast::Key>,
-        value: ast::Value,
-    ) -> ast::Entry {
+    pub fn new_entry<'b>(
+        &self,
+        keys: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+        value: SyntaxNodeRef<'b>,
+    ) -> SyntaxNode {
         let mut buff = String::new();
-        join_to(self, &mut buff, keys, ".", "", "");
+        join_to(&mut buff, keys, ".", "", "");
         buff.push_str(" = ");
-        value.cst().chunked_text(self).write_to(&mut buff);
+        buff.push_str(&value.get_text());
         self.new_entry_from_text(&buff)
     }
 
-    pub fn new_doc_from_text(&mut self, text: &str) -> ast::Doc {
-        self.assert_edit();
-        let new_root = self.tree.new_internal(DOC);
-        parser::parse(text, self, new_root);
-        ast::Doc::cast(CstNode(new_root), self).unwrap()
-    }
-
-    pub fn new_table_from_text(&mut self, text: &str) -> ast::Table {
-        let doc = self.new_doc_from_text(text);
-        let res = doc.tables(self).next().unwrap();
-        self.detach(res);
-        res
+    pub fn new_table_from_text(&self, text: &str) -> SyntaxNode {
+        let doc = TomlDoc::new(text);
+        let table = doc.ast().tables().next().unwrap();
+        detached(table.syntax())
     }
 
-    pub fn new_table(
-        &mut self,
-        keys: impl Iterator<Item = ast::Key>,
-        entries: impl Iterator<Item = ast::Entry>,
-    ) -> ast::Table {
-        let text = self.table_text(keys, entries, "[", "]");
+    pub fn new_table<'b>(
+        &self,
+        keys: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+        entries: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+    ) -> SyntaxNode {
+        let text = table_text(keys, entries, "[", "]");
         self.new_table_from_text(&text)
     }
 
-    pub fn new_array_table_from_text(&mut self, text: &str) -> ast::ArrayTable {
-        let doc = self.new_doc_from_text(text);
-        let res = doc.array_tables(self).next().unwrap();
-        self.detach(res);
-        res
+    pub fn new_array_table_from_text(&self, text: &str) -> SyntaxNode {
+        let doc = TomlDoc::new(text);
+        let table = doc.ast().array_tables().next().unwrap();
+        detached(table.syntax())
     }
 
-    pub fn new_array_table(
-        &mut self,
-        keys: impl Iterator<Item = ast::Key>,
-        entries: impl Iterator<Item = ast::Entry>,
-    ) -> ast::ArrayTable {
-        let text = self.table_text(keys, entries, "[[", "]]");
+    pub fn new_array_table<'b>(
+        &self,
+        keys: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+        entries: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+    ) -> SyntaxNode {
+        let text = table_text(keys, entries, "[[", "]]");
         self.new_array_table_from_text(&text)
     }
 
-    pub fn new_whitespace(&mut self, ws: &str) -> CstNode {
-        let idx = self.intern.intern(ws);
-        CstNode(self.tree.new_leaf((WHITESPACE, idx)))
+    pub fn new_whitespace(&self, ws: &str) -> SyntaxNode {
+        SyntaxNode::new(
+            GreenNode::new_leaf(WHITESPACE, SmolStr::new(ws)),
+            Vec::new(),
+        )
     }
 
-    pub fn new_comma(&mut self) -> CstNode {
-        let idx = self.intern.intern(",");
-        CstNode(self.tree.new_leaf((COMMA, idx)))
+    /// A `# comment`, the text must fit on one line.
+    pub fn new_comment(&self, text: &str) -> SyntaxNode {
+        assert!(
+            !text.contains('\n'),
+            "a comment can't span lines: {:?}",
+            text
+        );
+        let text = if text.is_empty() {
+            "#".to_string()
+        } else {
+            format!("# {}", text)
+        };
+        SyntaxNode::new(GreenNode::new_leaf(COMMENT, SmolStr::new(text)), Vec::new())
     }
 
-    fn assert_edit(&self) {
-        assert!(
-            self.edit_in_progress,
-            "call .start_edit to enable editing operations"
-        )
+    pub fn new_comma(&self) -> SyntaxNode {
+        SyntaxNode::new(GreenNode::new_leaf(COMMA, SmolStr::new(",")), Vec::new())
     }
 
-    fn fix_ws(&mut self, new_node: CstNode) {
-        let parent = new_node.parent(self).unwrap();
+    fn changes(&mut self, node: SyntaxNodeRef<'a>) -> &mut Changes {
+        self.changes.entry(node).or_default()
+    }
 
-        if let Some(prev) = new_node.prev_sibling(self) {
-            fix_ws_between(self, parent, prev, new_node);
-        } else {
-            fix_ws_before(self, parent, new_node);
+    fn rebuild(
+        &self,
+        node: SyntaxNodeRef<'a>,
+        green: &GreenNode,
+        dirty: &[SyntaxNodeRef<'a>],
+        style: Option<&Style>,
+    ) -> GreenNode {
+        if !dirty.iter().any(|it| it.ancestors().any(|it| it == node)) {
+            return green.clone();
+        }
+        let dict_style;
+        let style = match style {
+            Some(style) if node.symbol() == DICT => {
+                dict_style = style.in_dict(node);
+                Some(&dict_style)
+            }
+            _ => style,
+        };
+        let new = |green: &GreenNode| match style {
+            Some(style) => Slot::New(style.restyle(green)),
+            None => Slot::New(green.clone()),
+        };
+        let mut slots = Vec::new();
+        let changes = self.changes.get(&node);
+        slots.extend(changes.iter().flat_map(|it| it.first.iter().map(new)));
+        for (child, child_green) in node.children().zip(green.children()) {
+            let changes = match self.changes.get(&child) {
+                Some(it) => it,
+                None => {
+                    slots.push(Slot::Old(self.rebuild(child, child_green, dirty, style)));
+                    continue;
+                }
+            };
+            slots.extend(changes.before.iter().map(new));
+            match &changes.replacement {
+                Some(Some(replacement)) => slots.push(Slot::Old(replacement.clone())),
+                Some(None) => slots.push(Slot::Removed(child.symbol())),
+                None => slots.push(Slot::Old(self.rebuild(child, child_green, dirty, style))),
+            }
+            slots.extend(changes.after.iter().map(new));
+        }
+        slots.extend(changes.iter().flat_map(|it| it.last.iter().map(new)));
+        let children = match style {
+            Some(style) => fix_ws(style, node.symbol(), slots),
+            None => slots.into_iter().filter_map(Slot::into_green).collect(),
+        };
+        GreenNode::new_branch(node.symbol(), children.into_boxed_slice())
+    }
+}
+
+/// A child of a rebuilt node.
+enum Slot {
+    Old(GreenNode),
+    New(GreenNode),
+    Removed(Symbol),
+}
+
+impl Slot {
+    fn symbol(&self) -> Symbol {
+        match self {
+            Slot::Old(green) | Slot::New(green) => green.kind(),
+            Slot::Removed(symbol) => *symbol,
+        }
+    }
+
+    fn is_new(&self) -> bool {
+        matches!(self, Slot::New(_))
+    }
+
+    fn is_ws_or_removed(&self) -> bool {
+        matches!(self, Slot::Removed(_)) || self.symbol() == WHITESPACE
+    }
+
+    fn green(&self) -> Option<&GreenNode> {
+        match self {
+            Slot::Old(green) | Slot::New(green) => Some(green),
+            Slot::Removed(_) => None,
         }
+    }
 
-        if let Some(next) = new_node.next_sibling(self) {
-            fix_ws_between(self, parent, new_node, next);
+    fn into_green(self) -> Option<GreenNode> {
+        match self {
+            Slot::Old(green) | Slot::New(green) => Some(green),
+            Slot::Removed(_) => None,
+        }
+    }
+}
+
+fn fix_ws(style: &Style, symbol: Symbol, mut slots: Vec<Slot>) -> Vec<GreenNode> {
+    let is_list = symbol == ARRAY || symbol == DICT;
+    if is_list {
+        remove_separators(&mut slots);
+    } else {
+        collapse_ws(symbol, &mut slots);
+    }
+    slots.retain(|it| !matches!(it, Slot::Removed(_)));
+    if is_list {
+        add_separators(style, symbol, &mut slots);
+    }
+    space(style, symbol, slots)
+}
+
+/// Removes the comma and the whitespace which separated the detached
+/// elements of an inline array or table.
+fn remove_separators(slots: &mut [Slot]) {
+    for idx in 0..slots.len() {
+        match slots[idx] {
+            Slot::Removed(VALUE) | Slot::Removed(ENTRY) => (),
+            _ => continue,
+        }
+        let prev = (0..idx).rev().find(|&it| !slots[it].is_ws_or_removed());
+        let next = next_non_ws(slots, idx + 1);
+        let ws = if let Some(next) = next.filter(|&it| slots[it].symbol() == COMMA) {
+            slots[next] = Slot::Removed(COMMA);
+            // Keep the whitespace after the opening bracket, or before the
+            // element which follows.
+            let is_first = prev.is_none_or(|it| slots[it].symbol() != COMMA);
+            if is_first {
+                next + 1..next_non_ws(slots, next + 1).unwrap_or(slots.len())
+            } else {
+                prev.unwrap() + 1..idx
+            }
+        } else if let Some(prev) = prev.filter(|&it| slots[it].symbol() == COMMA) {
+            slots[prev] = Slot::Removed(COMMA);
+            prev + 1..idx
         } else {
-            fix_ws_after(self, parent, new_node);
+            continue;
+        };
+        for idx in ws {
+            if slots[idx].symbol() == WHITESPACE {
+                slots[idx] = Slot::Removed(WHITESPACE);
+            }
         }
+    }
+    // An emptied list has no whitespace inside the brackets.
+    let is_empty = slots
+        .iter()
+        .all(|it| it.is_ws_or_removed() || OPENING_OR_CLOSING.contains(&it.symbol()));
+    if is_empty {
+        for slot in slots.iter_mut() {
+            if slot.symbol() == WHITESPACE {
+                *slot = Slot::Removed(WHITESPACE);
+            }
+        }
+    }
+}
 
-        fn fix_ws_between(doc: &mut TomlDoc, parent: CstNode, left: CstNode, right: CstNode) {
-            let ws = match (left.symbol(doc), right.symbol(doc)) {
-                (ENTRY, R_CURLY) | (L_CURLY, ENTRY) | (COMMA, ENTRY) => " ",
-                (ENTRY, ENTRY) | (TABLE_HEADER, ENTRY) => "\n",
-                (TABLE, TABLE) | (ENTRY, TABLE) => "\n\n",
-                _ => "",
-            };
-            if !ws.is_empty() {
-                let ws = doc.new_whitespace(ws);
-                doc.tree
-                    .insert_child(parent.0, ws.0, InsertPos::After(left.0));
+fn next_non_ws(slots: &[Slot], start: usize) -> Option<usize> {
+    (start..slots.len()).find(|&it| !slots[it].is_ws_or_removed())
+}
+
+const OPENING_OR_CLOSING: [Symbol; 4] = [L_BRACK, R_BRACK, L_CURLY, R_CURLY];
+
+/// Leaves at most one whitespace in place of the detached nodes.
+fn collapse_ws(symbol: Symbol, slots: &mut [Slot]) {
+    let mut idx = 0;
+    while idx < slots.len() {
+        if !slots[idx].is_ws_or_removed() {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        while idx < slots.len() && slots[idx].is_ws_or_removed() {
+            idx += 1;
+        }
+        let has_removed = slots[start..idx]
+            .iter()
+            .any(|it| matches!(it, Slot::Removed(symbol) if *symbol != WHITESPACE));
+        if !has_removed {
+            continue;
+        }
+        let ws: Vec<usize> = (start..idx)
+            .filter(|&it| !matches!(slots[it], Slot::Removed(_)))
+            .collect();
+        // The trailing newline of the document stays.
+        let keep = if start == 0 {
+            None
+        } else if idx == slots.len() {
+            ws.last().filter(|_| symbol == DOC)
+        } else {
+            ws.first()
+        };
+        for &it in ws.iter() {
+            if Some(&it) != keep {
+                slots[it] = Slot::Removed(WHITESPACE);
             }
         }
+    }
+}
 
-        fn fix_ws_before(_doc: &mut TomlDoc, _parent: CstNode, _last_child: CstNode) {}
+/// Adds the commas between new elements of an inline array or table and
+/// their neighbours.
+fn add_separators(style: &Style, symbol: Symbol, slots: &mut Vec<Slot>) {
+    let element = if symbol == ARRAY { VALUE } else { ENTRY };
+    let greens: Vec<GreenNode> = slots.iter().filter_map(|it| it.green().cloned()).collect();
+    let trailing_comma = match style.parent(symbol, &greens) {
+        Parent::Array(layout) => style.trailing_comma(&layout),
+        _ => false,
+    };
+    let comma = || Slot::New(GreenNode::new_leaf(COMMA, SmolStr::new(",")));
+    let mut idx = 0;
+    while idx < slots.len() {
+        if !slots[idx].is_new() || slots[idx].symbol() != element {
+            idx += 1;
+            continue;
+        }
+        let prev = (0..idx).rev().find(|&it| slots[it].symbol() != WHITESPACE);
+        let next = (idx + 1..slots.len()).find(|&it| slots[it].symbol() != WHITESPACE);
+        let next = next.map(|it| slots[it].symbol());
+        if next == Some(element) || (next == Some(R_BRACK) && trailing_comma) {
+            slots.insert(idx + 1, comma());
+        }
+        if let Some(prev) = prev.filter(|&it| slots[it].symbol() == element) {
+            slots.insert(prev + 1, comma());
+            idx += 1;
+        }
+        idx += 1;
+    }
+}
 
-        fn fix_ws_after(doc: &mut TomlDoc, parent: CstNode, last_child: CstNode) {
-            let ws = if parent.symbol(doc) == DOC && last_child.symbol(doc) != WHITESPACE {
-                "\n"
-            } else {
-                ""
-            };
-            if !ws.is_empty() {
-                let ws = doc.new_whitespace(ws);
-                doc.tree
-                    .insert_child(parent.0, ws.0, InsertPos::After(last_child.0));
+/// Adds the whitespace between the new nodes and their neighbours.
+fn space(style: &Style, symbol: Symbol, slots: Vec<Slot>) -> Vec<GreenNode> {
+    let greens: Vec<GreenNode> = slots.iter().filter_map(|it| it.green().cloned()).collect();
+    let parent = style.parent(symbol, &greens);
+    let mut res: Vec<(GreenNode, bool)> = Vec::with_capacity(slots.len());
+    for slot in slots {
+        let is_new = slot.is_new();
+        let green = slot.into_green().unwrap();
+        let kind = green.kind();
+        if kind != WHITESPACE {
+            let len = res.len();
+            match res.last() {
+                Some((prev, prev_is_new))
+                    if prev.kind() != WHITESPACE && (is_new || *prev_is_new) =>
+                {
+                    if let Some(ws) = style.ws_between(&parent, prev.kind(), Some(kind)) {
+                        if !ws.is_empty() {
+                            res.push((style.whitespace(&ws), true));
+                        }
+                    }
+                }
+                // Existing whitespace next to a new node may be too short,
+                // like a single newline before a new `[table]`.
+                Some((ws, false)) if len >= 2 && (is_new || res[len - 2].1) => {
+                    let prev = res[len - 2].0.kind();
+                    let expected = style.ws_between(&parent, prev, Some(kind));
+                    let newlines = |text: &str| text.matches('\n').count();
+                    if let Some(expected) = expected {
+                        if newlines(&expected) > newlines(ws.leaf_text().unwrap()) {
+                            res[len - 1] = (style.whitespace(&expected), true);
+                        }
+                    }
+                }
+                _ => (),
             }
         }
+        res.push((green, is_new));
     }
+    if let Some((last, true)) = res.last() {
+        if last.kind() != WHITESPACE {
+            if let Some(ws) = style.ws_between(&parent, last.kind(), None) {
+                res.push((style.whitespace(&ws), true));
+            }
+        }
+    }
+    res.into_iter().map(|(green, _)| green).collect()
+}
 
-    fn table_text(
-        &mut self,
-        keys: impl Iterator<Item = ast::Key>,
-        entries: impl Iterator<Item = ast::Entry>,
-        left: &str,
-        right: &str,
-    ) -> String {
-        let mut buff = String::new();
-        buff.push_str(left);
-        join_to(self, &mut buff, keys, ".", "", "");
-        buff.push_str(right);
-        for entry in entries {
-            buff.push('\n');
-            entry.cst().chunked_text(self).write_to(&mut buff);
+fn assert_has_parent(node: SyntaxNodeRef) {
+    assert!(
+        node.parent().is_some(),
+        "can't insert next to, replace or detach the root node"
+    );
+}
+
+fn assert_not_inline(node: SyntaxNodeRef) {
+    assert!(
+        node.ancestors().all(|it| it.symbol() != DICT),
+        "inline tables can't have comments"
+    );
+}
+
+/// Copies the node into a new tree.
+fn detached(node: SyntaxNodeRef) -> SyntaxNode {
+    SyntaxNode::new(green(node), Vec::new())
+}
+
+/// Builds the green tree of the node, from the node's leaves.
+fn green(node: SyntaxNodeRef) -> GreenNode {
+    let mut builder = GreenBuilder::new();
+    for event in node.preorder() {
+        match event {
+            WalkEvent::Enter(node) => match node.leaf_text() {
+                Some(text) => builder.leaf(node.symbol(), SmolStr::new(text)),
+                None => builder.start_internal(node.symbol()),
+            },
+            WalkEvent::Leave(node) => {
+                if !node.is_leaf() {
+                    builder.finish_internal();
+                }
+            }
         }
-        buff
     }
+    builder.finish()
 }
 
 mod private {
     pub trait Sealed {}
+}
 
-    impl Sealed for bool {}
+/// A Rust value which can be written as a TOML value.
+///
+/// Strings are basic strings by default, literal strings when that avoids
+/// escapes, and their multiline forms if they have newlines. Maps become inline tables.
+///
+/// TOML integers are 64-bit signed, so a `u64`, `usize`, `i128` or `u128`
+/// which doesn't fit into `i64` is an error.
+pub trait IntoValue: private::Sealed {
+    #[doc(hidden)]
+    fn value_text(self) -> Result<String, ValueError>;
+}
 
-    impl Sealed for i64 {}
+/// A Rust value which can't be written as a TOML value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueError {
+    message: String,
+}
 
-    impl<'a> Sealed for &'a str {}
+impl ValueError {
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
 }
 
-pub trait IntoValue: private::Sealed {
-    #[doc(hidden)]
-    fn value_text(self) -> String;
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
+impl std::error::Error for ValueError {}
+
+impl private::Sealed for bool {}
+
 impl IntoValue for bool {
-    fn value_text(self) -> String {
-        if self { "true" } else { "false" }.to_owned()
+    fn value_text(self) -> Result<String, ValueError> {
+        Ok(if self { "true" } else { "false" }.to_owned())
+    }
+}
+
+macro_rules! int_into_value {
+    ($($int:ty),*) => {$(
+        impl private::Sealed for $int {}
+
+        impl IntoValue for $int {
+            fn value_text(self) -> Result<String, ValueError> {
+                let message = "integer is out of range of a 64-bit signed integer";
+                match i64::try_from(self) {
+                    Ok(value) => Ok(value.to_string()),
+                    Err(_) => Err(ValueError {
+                        message: message.to_string(),
+                    }),
+                }
+            }
+        }
+    )*};
+}
+
+int_into_value!(i8, i16, i32, i64, isize, i128, u8, u16, u32, u64, usize, u128);
+
+impl private::Sealed for f64 {}
+
+impl IntoValue for f64 {
+    fn value_text(self) -> Result<String, ValueError> {
+        Ok(float(self))
+    }
+}
+
+impl private::Sealed for &str {}
+
+impl IntoValue for &str {
+    fn value_text(self) -> Result<String, ValueError> {
+        Ok(string_lit(self))
+    }
+}
+
+impl private::Sealed for String {}
+
+impl IntoValue for String {
+    fn value_text(self) -> Result<String, ValueError> {
+        Ok(string_lit(&self))
+    }
+}
+
+impl private::Sealed for TomlDateTime {}
+
+impl IntoValue for TomlDateTime {
+    fn value_text(self) -> Result<String, ValueError> {
+        Ok(self.to_string())
+    }
+}
+
+impl<T: IntoValue> private::Sealed for Vec<T> {}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn value_text(self) -> Result<String, ValueError> {
+        let values = self
+            .into_iter()
+            .map(IntoValue::value_text)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("[{}]", values.join(", ")))
+    }
+}
+
+impl<K: AsRef<str>, V: IntoValue> private::Sealed for BTreeMap<K, V> {}
+
+impl<K: AsRef<str>, V: IntoValue> IntoValue for BTreeMap<K, V> {
+    fn value_text(self) -> Result<String, ValueError> {
+        inline_table(self.into_iter())
+    }
+}
+
+impl<K: AsRef<str>, V: IntoValue, S: BuildHasher> private::Sealed for HashMap<K, V, S> {}
+
+/// Entries are sorted by key, so that the text doesn't depend on the hasher.
+impl<K: AsRef<str>, V: IntoValue, S: BuildHasher> IntoValue for HashMap<K, V, S> {
+    fn value_text(self) -> Result<String, ValueError> {
+        let mut entries: Vec<(K, V)> = self.into_iter().collect();
+        entries.sort_by(|(k1, _), (k2, _)| k1.as_ref().cmp(k2.as_ref()));
+        inline_table(entries.into_iter())
     }
 }
 
-impl IntoValue for i64 {
-    fn value_text(self) -> String {
-        self.to_string()
+impl private::Sealed for &Item<'_> {}
+
+impl IntoValue for &Item<'_> {
+    fn value_text(self) -> Result<String, ValueError> {
+        match self {
+            Item::Map(map) => inline_table(map.iter()),
+            Item::Array(items) => {
+                let values = items
+                    .iter()
+                    .map(IntoValue::value_text)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("[{}]", values.join(", ")))
+            }
+            Item::Integer(value) => value.value_text(),
+            Item::Float(value) => value.value_text(),
+            Item::Bool(value) => value.value_text(),
+            Item::DateTime(value) => value.value_text(),
+            Item::String(value) => value.as_str().value_text(),
+        }
     }
 }
 
-impl<'a> IntoValue for &'a str {
-    fn value_text(self) -> String {
-        //FIXME: escaping
-        format!("{:?}", self)
+impl private::Sealed for Item<'_> {}
+
+impl IntoValue for Item<'_> {
+    fn value_text(self) -> Result<String, ValueError> {
+        (&self).value_text()
     }
 }
 
-fn join<A: Into<CstNode>>(
-    doc: &TomlDoc,
-    items: impl Iterator<Item = A>,
-    left: char,
-    right: char,
+fn inline_table<K: AsRef<str>, V: IntoValue>(
+    entries: impl Iterator<Item = (K, V)>,
+) -> Result<String, ValueError> {
+    let entries = entries
+        .map(|(key, value)| {
+            let text = value.value_text()?;
+            Ok(format!("{} = {}", escaped_key(key.as_ref()), text))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if entries.is_empty() {
+        Ok("{}".to_string())
+    } else {
+        Ok(format!("{{ {} }}", entries.join(", ")))
+    }
+}
+
+fn table_text<'b>(
+    keys: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+    entries: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
+    left: &str,
+    right: &str,
 ) -> String {
+    let mut buff = String::new();
+    buff.push_str(left);
+    join_to(&mut buff, keys, ".", "", "");
+    buff.push_str(right);
+    for entry in entries {
+        buff.push('\n');
+        buff.push_str(&entry.get_text());
+    }
+    buff
+}
+
+fn join<'b>(items: impl IntoIterator<Item = SyntaxNodeRef<'b>>, left: char, right: char) -> String {
     let mut buff = String::new();
     buff.push(left);
-    join_to(doc, &mut buff, items, ", ", " ", " ");
+    join_to(&mut buff, items, ", ", " ", " ");
     buff.push(right);
-
     buff
 }
 
-fn join_to<A: Into<CstNode>>(
-    doc: &TomlDoc,
+fn join_to<'b>(
     buff: &mut String,
-    items: impl Iterator<Item = A>,
+    items: impl IntoIterator<Item = SyntaxNodeRef<'b>>,
     sep: &str,
     before_first: &str,
     after_last: &str,
@@ -349,20 +850,108 @@ fn join_to<A: Into<CstNode>>(
             buff.push_str(sep);
         }
         first = false;
-        item.into().chunked_text(doc).write_to(buff);
+        buff.push_str(&item.get_text());
     }
     if !first {
         buff.push_str(after_last);
     }
 }
 
-fn escaped_key(key: &str) -> String {
-    if key
-        .chars()
-        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
-    {
+/// Returns the key as is if it is a valid bare key, and quoted otherwise.
+pub(crate) fn escaped_key(key: &str) -> String {
+    let is_bare = !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+    if is_bare {
         key.to_string()
     } else {
-        format!("{:?}", key)
+        basic_string(key)
+    }
+}
+
+/// Writes the string in the TOML form which reads the best: a `'literal
+/// string'` if the string has quotes or backslashes which a basic string
+/// would escape, and a `"basic string"` otherwise. Strings with newlines use
+/// the multiline forms.
+pub(crate) fn string_lit(value: &str) -> String {
+    let has_escapes = value.contains(['"', '\\']);
+    if !value.contains('\n') {
+        if has_escapes && is_literal(value) {
+            return format!("'{}'", value);
+        }
+        return basic_string(value);
+    }
+    let is_multiline_literal = !value.contains("'''")
+        && !value.ends_with('\'')
+        && value
+            .chars()
+            .all(|c| c == '\n' || c == '\t' || !c.is_control());
+    if has_escapes && is_multiline_literal {
+        return format!("'''\n{}'''", value);
+    }
+    multiline_basic_string(value)
+}
+
+/// Whether the string can be written as a literal `'string'`.
+pub(crate) fn is_literal(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c != '\'' && (c == '\t' || !c.is_control()))
+}
+
+/// Writes the string as a `"basic string"`, escaping it as necessary.
+pub(crate) fn basic_string(value: &str) -> String {
+    let mut buff = String::with_capacity(value.len() + 2);
+    buff.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => buff += "\\\"",
+            '\\' => buff += "\\\\",
+            '\n' => buff += "\\n",
+            '\t' => buff += "\\t",
+            '\r' => buff += "\\r",
+            '\u{8}' => buff += "\\b",
+            '\u{c}' => buff += "\\f",
+            c if c.is_control() => buff += &format!("\\u{:04X}", c as u32),
+            c => buff.push(c),
+        }
+    }
+    buff.push('"');
+    buff
+}
+
+/// Writes the string as a `"""multiline basic string"""`, which starts on a
+/// new line.
+fn multiline_basic_string(value: &str) -> String {
+    let mut buff = String::with_capacity(value.len() + 7);
+    buff.push_str("\"\"\"\n");
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // Only the quotes which could close the string are escaped.
+            '"' if chars.peek().is_none_or(|&it| it == '"') => buff += "\\\"",
+            '\\' => buff += "\\\\",
+            '\n' | '\t' => buff.push(c),
+            '\r' => buff += "\\r",
+            '\u{8}' => buff += "\\b",
+            '\u{c}' => buff += "\\f",
+            c if c.is_control() => buff += &format!("\\u{:04X}", c as u32),
+            c => buff.push(c),
+        }
+    }
+    buff.push_str("\"\"\"");
+    buff
+}
+
+/// Writes the float so that it reads back as a float.
+pub(crate) fn float(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        // `Debug` always has a fractional part or an exponent.
+        format!("{:?}", value)
     }
 }
@@ -0,0 +1,455 @@
+//! Formatting of documents.
+//!
+//! The formatter prints a document line by line: every entry, table header
+//! and comment on its own line starts a new one, with a fixed number of
+//! blank lines before tables and at most one blank line elsewhere. Arrays
+//! are printed on a single line if they fit into `max_width`, and with an
+//! element per line otherwise. Only the whitespace and the commas of arrays
+//! change, so the formatted document has the same keys, values and comments
+//! as the original one. Documents with syntax errors are left alone.
+
+use crate::{style::Style, symbol::*, SyntaxNodeRef, TextEdit, TextRange, TextUnit, TomlDoc};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FmtOptions {
+    /// The width up to which arrays are printed on a single line.
+    pub max_width: usize,
+    /// The number of spaces per level of indentation.
+    pub indent_width: usize,
+    /// Whether the entries of tables are indented.
+    pub indent_entries: bool,
+    /// The number of blank lines before a table.
+    pub blank_lines: usize,
+    /// Whether the last element of a multi-line array has a comma.
+    pub trailing_comma: bool,
+    /// Whether the trailing comments of adjacent lines start in the same
+    /// column.
+    pub align_comments: bool,
+}
+
+impl Default for FmtOptions {
+    fn default() -> FmtOptions {
+        FmtOptions {
+            max_width: 80,
+            indent_width: 4,
+            indent_entries: false,
+            blank_lines: 1,
+            trailing_comma: true,
+            align_comments: true,
+        }
+    }
+}
+
+/// Returns the formatted text of the document, or `None` if it has syntax
+/// errors.
+pub fn format(doc: &TomlDoc, options: &FmtOptions) -> Option<String> {
+    Some(print(doc, options)?.text)
+}
+
+/// Returns the edit which formats the lines of the document intersecting
+/// the `range`, or `None` if there are no such lines or the document has
+/// syntax errors.
+pub fn format_range(doc: &TomlDoc, range: TextRange, options: &FmtOptions) -> Option<TextEdit> {
+    let output = print(doc, options)?;
+    let mut selected = output
+        .items
+        .iter()
+        .filter(|(source, _)| source.intersection(&range).is_some());
+    let first = selected.next()?;
+    let last = selected.next_back().unwrap_or(first);
+    let output_range = TextRange::from_to(first.1.start(), last.1.end());
+    Some(TextEdit::replace(
+        TextRange::from_to(first.0.start(), last.0.end()),
+        &output.text[output_range],
+    ))
+}
+
+struct Output {
+    text: String,
+    /// The source and the formatted ranges of each line item.
+    items: Vec<(TextRange, TextRange)>,
+}
+
+fn print(doc: &TomlDoc, options: &FmtOptions) -> Option<Output> {
+    if !doc.root.root_data().is_empty() {
+        return None;
+    }
+    let mut collector = Collector::default();
+    collector.node(doc.cst());
+    let source = doc.cst().get_text();
+
+    let mut printer = Printer::new(options, false);
+    let mut spans = Vec::with_capacity(collector.items.len());
+    for (idx, item) in collector.items.iter().enumerate() {
+        let node = item.node;
+        if item.trailing {
+            let line = printer.lines.len() - 1;
+            printer.lines[line].comment = Some(comment_text(node).to_string());
+            spans.push((node.range(), Pos::Comment(line), Pos::LineEnd(line)));
+            continue;
+        }
+        if idx > 0 {
+            let newlines = if item.starts_table {
+                options.blank_lines + 1
+            } else if item.blank_before {
+                2
+            } else {
+                1
+            };
+            for _ in 0..newlines {
+                printer.newline();
+            }
+        }
+        let start = printer.pos();
+        if item.indented && options.indent_entries {
+            printer.write(&" ".repeat(options.indent_width));
+        }
+        let range = match node.symbol() {
+            ENTRY => {
+                printer.entry(node);
+                entry_range(node)
+            }
+            COMMENT => {
+                printer.write(comment_text(node));
+                node.range()
+            }
+            _ => {
+                let leaves = node.descendants().filter(|it| it.symbol() != WHITESPACE);
+                for leaf in leaves.filter_map(|it| it.leaf_text()) {
+                    printer.write(leaf);
+                }
+                node.range()
+            }
+        };
+        let line_start = source[..range.start().to_usize()]
+            .rfind('\n')
+            .map_or(0, |it| it + 1);
+        let range = TextRange::from_to(TextUnit::from_usize(line_start), range.end());
+        spans.push((range, start, printer.pos()));
+    }
+
+    let newline = Style::infer(doc.cst()).newline();
+    let columns = comment_columns(&printer.lines, options.align_comments);
+    let mut text = String::new();
+    // The offsets of the start, the comment and the end of each line.
+    let mut offsets = Vec::with_capacity(printer.lines.len());
+    for (line, column) in printer.lines.iter().zip(columns) {
+        let start = text.len();
+        text.push_str(&line.text);
+        let mut comment = text.len();
+        if let Some(it) = &line.comment {
+            let width = line.text.chars().count();
+            text.extend((width..column).map(|_| ' '));
+            comment = text.len();
+            text.push_str(it);
+        }
+        offsets.push((start, comment, text.len()));
+        text.push_str(newline);
+    }
+    if collector.items.is_empty() {
+        text.clear();
+    }
+    let offset = |pos| {
+        let offset = match pos {
+            Pos::Text(line, col) => offsets[line].0 + col,
+            Pos::Comment(line) => offsets[line].1,
+            Pos::LineEnd(line) => offsets[line].2,
+        };
+        TextUnit::from_usize(offset)
+    };
+    let items = spans
+        .into_iter()
+        .map(|(source, start, end)| (source, TextRange::from_to(offset(start), offset(end))))
+        .collect();
+    Some(Output { text, items })
+}
+
+/// An entry, a table header or a comment.
+struct LineItem<'a> {
+    node: SyntaxNodeRef<'a>,
+    /// Whether the item is a comment on the line of the previous item.
+    trailing: bool,
+    blank_before: bool,
+    starts_table: bool,
+    indented: bool,
+}
+
+#[derive(Default)]
+struct Collector<'a> {
+    items: Vec<LineItem<'a>>,
+    prev_ws: Option<&'a str>,
+    in_table_body: bool,
+    starts_table: bool,
+}
+
+impl<'a> Collector<'a> {
+    fn node(&mut self, node: SyntaxNodeRef<'a>) {
+        let mut is_entry_pushed = false;
+        for child in node.children() {
+            match child.symbol() {
+                WHITESPACE => {
+                    self.prev_ws = child.leaf_text();
+                    continue;
+                }
+                COMMENT => self.push(child),
+                TABLE_HEADER => {
+                    self.push(child);
+                    self.in_table_body = true;
+                }
+                ENTRY => self.node(child),
+                TABLE | ARRAY_TABLE => {
+                    self.starts_table = true;
+                    self.in_table_body = false;
+                    self.node(child);
+                    self.in_table_body = false;
+                }
+                _ if node.symbol() == ENTRY && !is_entry_pushed => {
+                    self.push(node);
+                    is_entry_pushed = true;
+                }
+                _ => (),
+            }
+            self.prev_ws = None;
+        }
+    }
+
+    fn push(&mut self, node: SyntaxNodeRef<'a>) {
+        let newlines = self.prev_ws.map_or(0, |it| it.matches('\n').count());
+        let trailing = node.symbol() == COMMENT && newlines == 0 && !self.items.is_empty();
+        self.items.push(LineItem {
+            node,
+            trailing,
+            blank_before: newlines > 1,
+            starts_table: !trailing && std::mem::replace(&mut self.starts_table, false),
+            indented: self.in_table_body && node.symbol() != TABLE_HEADER,
+        });
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Pos {
+    /// A byte offset in the text of a line.
+    Text(usize, usize),
+    /// The start of the trailing comment of a line.
+    Comment(usize),
+    LineEnd(usize),
+}
+
+#[derive(Default)]
+struct Line {
+    text: String,
+    comment: Option<String>,
+}
+
+struct Printer<'a> {
+    options: &'a FmtOptions,
+    lines: Vec<Line>,
+    /// Whether arrays are always printed on a single line.
+    flat: bool,
+    /// Whether a flat printer came across a comment.
+    failed: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(options: &'a FmtOptions, flat: bool) -> Printer<'a> {
+        Printer {
+            options,
+            lines: vec![Line::default()],
+            flat,
+            failed: false,
+        }
+    }
+
+    fn entry(&mut self, entry: SyntaxNodeRef) {
+        for child in entry.children() {
+            match child.symbol() {
+                KEY => self.write(&child.get_text()),
+                DOT => self.write("."),
+                EQ => self.write(" = "),
+                VALUE => self.value(child),
+                _ => (),
+            }
+        }
+    }
+
+    fn value(&mut self, value: SyntaxNodeRef) {
+        let child = match value.first_child() {
+            Some(it) => it,
+            None => return,
+        };
+        match child.symbol() {
+            ARRAY => self.array(child),
+            DICT => self.dict(child),
+            _ => self.write(child.leaf_text().unwrap_or("")),
+        }
+    }
+
+    fn dict(&mut self, dict: SyntaxNodeRef) {
+        let entries: Vec<SyntaxNodeRef> =
+            dict.children().filter(|it| it.symbol() == ENTRY).collect();
+        if entries.is_empty() {
+            return self.write("{}");
+        }
+        // Inline tables can't span several lines, whatever their width.
+        let flat = std::mem::replace(&mut self.flat, true);
+        self.write("{ ");
+        for (idx, entry) in entries.into_iter().enumerate() {
+            if idx > 0 {
+                self.write(", ");
+            }
+            self.entry(entry);
+        }
+        self.write(" }");
+        self.flat = flat;
+    }
+
+    fn array(&mut self, array: SyntaxNodeRef) {
+        if self.flat {
+            self.write("[");
+            let values = array.children().filter(|it| it.symbol() == VALUE);
+            for (idx, value) in values.enumerate() {
+                if idx > 0 {
+                    self.write(", ");
+                }
+                self.value(value);
+            }
+            self.failed |= array.children().any(|it| it.symbol() == COMMENT);
+            return self.write("]");
+        }
+        if let Some(text) = self.flat_array(array) {
+            if self.col() + text.chars().count() <= self.options.max_width {
+                return self.write(&text);
+            }
+        }
+
+        let closing_indent = self.indent();
+        let indent = format!(
+            "{}{}",
+            closing_indent,
+            " ".repeat(self.options.indent_width)
+        );
+        let n_values = array.children().filter(|it| it.symbol() == VALUE).count();
+        let mut idx = 0;
+        let mut prev_ws: Option<&str> = None;
+        self.write("[");
+        for child in array.children() {
+            let newlines = prev_ws.map_or(0, |it| it.matches('\n').count());
+            match child.symbol() {
+                WHITESPACE => {
+                    prev_ws = child.leaf_text();
+                    continue;
+                }
+                VALUE => {
+                    self.newlines(newlines);
+                    self.write(&indent);
+                    self.value(child);
+                    idx += 1;
+                    if idx < n_values || self.options.trailing_comma {
+                        self.write(",");
+                    }
+                }
+                COMMENT if newlines == 0 => {
+                    self.lines.last_mut().unwrap().comment = Some(comment_text(child).to_string())
+                }
+                COMMENT => {
+                    self.newlines(newlines);
+                    self.write(&indent);
+                    self.write(comment_text(child));
+                }
+                _ => (),
+            }
+            prev_ws = None;
+        }
+        self.newline();
+        self.write(&closing_indent);
+        self.write("]");
+    }
+
+    /// The array on a single line, `None` if it has comments or multi-line
+    /// strings.
+    fn flat_array(&self, array: SyntaxNodeRef) -> Option<String> {
+        let mut printer = Printer::new(self.options, true);
+        printer.array(array);
+        if printer.failed || printer.lines.len() > 1 {
+            return None;
+        }
+        printer.lines.pop().map(|it| it.text)
+    }
+
+    /// Starts a new line, or leaves a blank line if there was one.
+    fn newlines(&mut self, original: usize) {
+        self.newline();
+        if original > 1 {
+            self.newline();
+        }
+    }
+
+    fn newline(&mut self) {
+        self.lines.push(Line::default());
+    }
+
+    fn write(&mut self, text: &str) {
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or("");
+        self.lines.last_mut().unwrap().text.push_str(first);
+        for line in lines {
+            let last = self.lines.last_mut().unwrap();
+            if last.text.ends_with('\r') {
+                last.text.pop();
+            }
+            self.lines.push(Line {
+                text: line.to_string(),
+                comment: None,
+            });
+        }
+    }
+
+    fn pos(&self) -> Pos {
+        Pos::Text(self.lines.len() - 1, self.lines.last().unwrap().text.len())
+    }
+
+    fn col(&self) -> usize {
+        self.lines.last().unwrap().text.chars().count()
+    }
+
+    fn indent(&self) -> String {
+        let text = &self.lines.last().unwrap().text;
+        text[..text.len() - text.trim_start_matches(' ').len()].to_string()
+    }
+}
+
+/// The columns of the trailing comments, the same for adjacent lines with
+/// comments if they are aligned.
+fn comment_columns(lines: &[Line], align: bool) -> Vec<usize> {
+    let mut res: Vec<usize> = lines.iter().map(|it| it.text.chars().count() + 1).collect();
+    if !align {
+        return res;
+    }
+    let mut start = 0;
+    while start < lines.len() {
+        let len = lines[start..]
+            .iter()
+            .take_while(|it| it.comment.is_some())
+            .count();
+        let end = start + len.max(1);
+        let column = res[start..end].iter().copied().max().unwrap_or(0);
+        res[start..end].iter_mut().for_each(|it| *it = column);
+        start = end;
+    }
+    res
+}
+
+fn comment_text<'a>(comment: SyntaxNodeRef<'a>) -> &'a str {
+    comment.leaf_text().unwrap_or("").trim_end()
+}
+
+/// The range of the entry without its comments.
+fn entry_range(entry: SyntaxNodeRef) -> TextRange {
+    let mut children = entry
+        .children()
+        .filter(|it| it.symbol() != WHITESPACE && it.symbol() != COMMENT)
+        .map(|it| it.range());
+    let first = children.next().unwrap_or_else(|| entry.range());
+    let last = children.last().unwrap_or(first);
+    first.extend_to(&last)
+}
@@ -5,19 +5,34 @@ uncover::define_uncover_macros!(enable_if(cfg!(debug_assertions)));
 mod chunked_text;
 mod rtree;
 mod parser;
-// mod model;
+mod model;
 // mod visitor;
 mod validator;
-// mod edit;
+mod spanned;
+mod edit;
+mod path;
+mod reparse;
+mod style;
+mod transform;
 
 pub mod ast;
+pub mod datetime;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod fmt;
+#[cfg(feature = "serde")]
+pub mod ser;
 pub mod symbol;
 
 use std::{num::NonZeroU8, marker::PhantomData};
 
-// pub use edit::{IntoValue, Position};
+pub use edit::{Edit, IntoValue, Position, ValueError};
 pub use rowan::{SmolStr, TextRange, TextUnit, WalkEvent};
-// pub use model::{Item, Map};
+pub use datetime::TomlDateTime;
+pub use model::{Item, Map};
+pub use path::PathError;
+pub use reparse::TextEdit;
+pub use spanned::Spanned;
 pub use rtree::{SyntaxNode, SyntaxNodeRef, RefRoot, OwnedRoot, SyntaxNodeChildren, TreeRoot, TomTypes};
 pub(crate) use rtree::GreenBuilder;
 pub(crate) use chunked_text::ChunkedText;
@@ -35,13 +50,52 @@ impl Symbol {
 pub struct SyntaxError {
     range: TextRange,
     message: String,
+    secondary_range: Option<TextRange>,
+    code: ErrorCode,
+}
+
+/// What kind of mistake a `SyntaxError` is about. Unlike the messages, the
+/// codes don't change between versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The text is not lexically or grammatically TOML.
+    Syntax,
+    /// A missing or a forbidden newline.
+    Newline,
+    InvalidString,
+    InvalidNumber,
+    InvalidDateTime,
+    /// A key or a table which is already defined.
+    Redefinition,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::Syntax => "syntax",
+            ErrorCode::Newline => "newline",
+            ErrorCode::InvalidString => "invalid-string",
+            ErrorCode::InvalidNumber => "invalid-number",
+            ErrorCode::InvalidDateTime => "invalid-date-time",
+            ErrorCode::Redefinition => "redefinition",
+        }
+    }
 }
 
 impl SyntaxError {
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
     pub fn range(&self) -> TextRange {
         self.range
     }
 
+    /// For redefinition errors, the range of the original definition.
+    pub fn secondary_range(&self) -> Option<TextRange> {
+        self.secondary_range
+    }
+
     pub fn message(&self) -> &str {
         self.message.as_str()
     }
@@ -50,14 +104,22 @@ impl SyntaxError {
 #[derive(Clone)]
 pub struct TomlDoc {
     root: rtree::SyntaxNode,
+    /// The green tree of `root`, which is needed for editing, as rowan
+    /// doesn't expose the green nodes of a syntax tree.
+    green: rtree::GreenNode,
     validation_errors: Vec<SyntaxError>,
 }
 
 impl TomlDoc {
     pub fn new(text: &str) -> TomlDoc {
-        let root = parser::parse(text);
+        let (green, errors) = parser::parse(text);
+        TomlDoc::from_green(green, errors)
+    }
+
+    pub(crate) fn from_green(green: rtree::GreenNode, errors: Vec<SyntaxError>) -> TomlDoc {
         let mut doc = TomlDoc {
-            root,
+            root: SyntaxNode::new(green.clone(), errors),
+            green,
             validation_errors: Vec::new(),
         };
 
@@ -67,20 +129,20 @@ impl TomlDoc {
         doc
     }
 
-    pub fn cst(&self) -> SyntaxNodeRef {
+    pub fn cst(&self) -> SyntaxNodeRef<'_> {
         self.root.borrowed()
     }
 
-    pub fn ast(&self) -> ast::Doc {
+    pub fn ast(&self) -> ast::Doc<'_> {
         ast::Doc::cast(self.cst()).unwrap()
     }
     // pub(crate) fn replace_with(&self, replacement: GreenNode) -> GreenNode {
     //     self.0.replace_with(replacement)
     // }
 
-    // pub fn model(&self) -> Map {
-    //     model::from_doc(self)
-    // }
+    pub fn model(&self) -> Map<'_> {
+        model::from_doc(self)
+    }
 
     pub fn errors(&self) -> Vec<SyntaxError> {
         self.root
@@ -120,8 +182,12 @@ impl TomlDoc {
             let text = self.cst().get_text();
             buff += "\n";
             for e in errors {
-                let text = &text[e.range];
-                buff += &format!("error@{:?} {:?}: {}\n", e.range(), text, e.message());
+                let error_text = &text[e.range];
+                buff += &format!("error@{:?} {:?}: {}\n", e.range(), error_text, e.message());
+                if let Some(range) = e.secondary_range() {
+                    let text = &text[range];
+                    buff += &format!("  first defined here@{:?} {:?}\n", range, text);
+                }
             }
         }
         buff
@@ -153,11 +219,6 @@ impl<'a, A: AstNode<'a>> Iterator for AstChildren<'a, A> {
     type Item = A;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(node) = self.inner.next() {
-            if let Some(a) = A::cast(node) {
-                return Some(a);
-            }
-        }
-        None
+        self.inner.by_ref().find_map(A::cast)
     }
 }
@@ -1,54 +1,83 @@
-//! FIXME: write short doc here
+//! Semantic model of a TOML document.
+//!
+//! `Map` and `Item` describe the values a document defines, after dotted
+//! keys, `[table]` and `[[array-table]]` sections are merged together. Each
+//! map entry remembers the `ast::Key`s which contributed to it, so that it is
+//! possible to go back from a value to the syntax which defined it.
 
 use std::{
-    collections::BTreeMap,
-    fmt,
+    collections::{btree_map, BTreeMap},
+    fmt, iter,
 };
-use {TomlDoc, CstNode, ast, visitor::{visitor, process_children}};
 
-pub enum Item {
-    Map(Map),
-    Array(Vec<Item>),
+use crate::{ast, SyntaxNodeRef, TomlDateTime, TomlDoc};
+
+#[derive(Debug)]
+pub enum Item<'a> {
+    Map(Map<'a>),
+    Array(Vec<Item<'a>>),
     Integer(i64),
     Float(f64),
     Bool(bool),
-    DateTime,
+    DateTime(TomlDateTime),
     String(String),
 }
 
-pub struct Map {
-    map: BTreeMap<String, (Vec<ast::Key>, Item)>,
+#[derive(Debug)]
+pub struct Map<'a> {
+    map: BTreeMap<String, (Vec<ast::Key<'a>>, Item<'a>)>,
 }
 
-impl Map {
-    fn new() -> Map {
-        Map { map: BTreeMap::new() }
-    }
-
-    pub fn iter(&self) -> impl Iterator<Item=(&str, &Item)> {
-        self.map.iter()
-            .map(|(k, (_, v))| (k.as_str(), v))
+impl<'a> Map<'a> {
+    fn new() -> Map<'a> {
+        Map {
+            map: BTreeMap::new(),
+        }
     }
 
-    pub fn into_iter(self) -> impl Iterator<Item=(String, Item)> {
-        self.map.into_iter()
-            .map(|(k, (_, v))| (k, v))
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Item<'a>)> {
+        self.map.iter().map(|(k, (_, v))| (k.as_str(), v))
     }
 
     pub fn is_empty(&self) -> bool {
         self.map.is_empty()
     }
 
-    pub fn get(&self, key: &str) -> Option<&Item> {
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Item<'a>> {
         self.map.get(key).map(|(_, i)| i)
     }
 
-    pub fn get_keys(&self, key: &str) -> Vec<ast::Key> {
-        self.map.get(key).map(|(k, _)| k).into_iter().flat_map(|keys| keys.iter().cloned()).collect()
+    pub(crate) fn remove(&mut self, key: &str) -> Option<Item<'a>> {
+        self.map.remove(key).map(|(_, i)| i)
+    }
+
+    /// Returns all the keys which define or extend the value under `key`,
+    /// in the document order.
+    pub fn get_keys(&self, key: &str) -> Vec<ast::Key<'a>> {
+        self.map
+            .get(key)
+            .map(|(keys, _)| keys.clone())
+            .unwrap_or_default()
     }
 }
 
-impl fmt::Display for Map {
+type IntoIterFn<'a> = fn((String, (Vec<ast::Key<'a>>, Item<'a>))) -> (String, Item<'a>);
+
+impl<'a> IntoIterator for Map<'a> {
+    type Item = (String, Item<'a>);
+    type IntoIter =
+        iter::Map<btree_map::IntoIter<String, (Vec<ast::Key<'a>>, Item<'a>)>, IntoIterFn<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter().map(|(k, (_, v))| (k, v))
+    }
+}
+
+impl fmt::Display for Map<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         f.write_str("{")?;
         let mut first = true;
@@ -66,14 +95,21 @@ impl fmt::Display for Map {
     }
 }
 
-impl Item {
-    pub fn as_map(&self) -> Option<&Map> {
+impl<'a> Item<'a> {
+    pub fn as_map(&self) -> Option<&Map<'a>> {
         match self {
             Item::Map(m) => Some(m),
             _ => None,
         }
     }
 
+    pub fn as_array(&self) -> Option<&[Item<'a>]> {
+        match self {
+            Item::Array(a) => Some(a.as_slice()),
+            _ => None,
+        }
+    }
+
     pub fn as_str(&self) -> Option<&str> {
         match self {
             Item::String(s) => Some(s.as_str()),
@@ -101,9 +137,16 @@ impl Item {
             _ => None,
         }
     }
+
+    pub fn as_date_time(&self) -> Option<TomlDateTime> {
+        match self {
+            Item::DateTime(v) => Some(*v),
+            _ => None,
+        }
+    }
 }
 
-impl fmt::Display for Item {
+impl fmt::Display for Item<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
             Item::Map(map) => map.fmt(f)?,
@@ -122,97 +165,125 @@ impl fmt::Display for Item {
             Item::Integer(value) => value.fmt(f)?,
             Item::Float(value) => value.fmt(f)?,
             Item::Bool(value) => value.fmt(f)?,
-            Item::DateTime => f.write_str("FIXME:date-time")?,
+            Item::DateTime(value) => value.fmt(f)?,
             Item::String(value) => fmt::Debug::fmt(value, f)?,
         }
         Ok(())
     }
 }
 
-pub(crate) fn from_doc(doc: &TomlDoc) -> Map {
+pub(crate) fn from_doc(doc: &TomlDoc) -> Map<'_> {
     let mut root = Item::Map(Map::new());
-    fill(doc, doc.cst(), &mut root);
+    fill(doc.cst(), &mut root);
     match root {
         Item::Map(map) => map,
-        _ => unreachable!()
+        _ => unreachable!(),
     }
 }
 
-fn fill(doc: &TomlDoc, node: CstNode, item: &mut Item) {
-    let v = visitor(item)
-        .visit::<ast::Entry, _>(|item, entry| {
-            match insert_into(doc, *item, entry.keys(doc)) {
-                Some(tbl) => *tbl = from_value(doc, entry.value(doc)),
-                None => return,
+/// Returns the value of the `key = value` entry among the keys which define
+/// a model item, if the item is written inline.
+pub(crate) fn entry_value<'a>(keys: &[ast::Key<'a>]) -> Option<ast::Value<'a>> {
+    keys.iter().find_map(|&key| {
+        let entry = ast::Entry::cast(key.syntax().parent()?)?;
+        if entry.keys().last().map(|it| it.syntax()) != Some(key.syntax()) {
+            return None;
+        }
+        Some(entry.value())
+    })
+}
+
+/// Whether the key is the last one in a `[[header]]`.
+#[cfg(feature = "serde")]
+pub(crate) fn is_array_table_key(key: ast::Key) -> bool {
+    let header = match key.syntax().parent().and_then(ast::TableHeader::cast) {
+        Some(it) => it,
+        None => return false,
+    };
+    let is_array_table = header
+        .syntax()
+        .parent()
+        .and_then(ast::ArrayTable::cast)
+        .is_some();
+    is_array_table && header.keys().last().map(|it| it.syntax()) == Some(key.syntax())
+}
+
+fn fill<'a>(node: SyntaxNodeRef<'a>, item: &mut Item<'a>) {
+    for child in node.children() {
+        if let Some(entry) = ast::Entry::cast(child) {
+            if let Some(value) = from_value(entry.value()) {
+                if let Some(tbl) = insert_into(item, entry.keys()) {
+                    *tbl = value;
+                }
             }
-        })
-        .visit::<ast::Table, _>(|item, table| {
-            match insert_into(doc, *item, table.header(doc).keys(doc)) {
-                Some(tbl) => fill(doc, table.cst(), tbl),
-                None => return,
+        } else if let Some(table) = ast::Table::cast(child) {
+            if let Some(tbl) = insert_into(item, table.header().keys()) {
+                fill(table.syntax(), tbl);
             }
-        })
-        .visit::<ast::ArrayTable, _>(|item, table| {
-            match insert_into(doc, *item, table.header(doc).keys(doc)) {
-                Some(tbl) => {
-                    let mut new_item = Item::Map(Map::new());
-                    fill(doc, table.cst(), &mut new_item);
-
-                    match tbl {
-                        Item::Map(map) if map.is_empty() => (),
-                        Item::Array(array) => {
-                            array.push(new_item);
-                            return;
-                        }
-                        _ => return,
-                    }
-                    *tbl = Item::Array(vec![new_item])
+        } else if let Some(table) = ast::ArrayTable::cast(child) {
+            if let Some(tbl) = insert_into(item, table.header().keys()) {
+                let mut new_item = Item::Map(Map::new());
+                fill(table.syntax(), &mut new_item);
+                match tbl {
+                    Item::Map(map) if map.is_empty() => *tbl = Item::Array(vec![new_item]),
+                    Item::Array(array) => array.push(new_item),
+                    _ => (),
                 }
-                None => return,
             }
-        });
-    process_children(node, doc, v);
+        }
+    }
 }
 
-fn insert_into<'a>(
-    doc: &TomlDoc,
-    item: &'a mut Item,
-    keys: impl Iterator<Item=ast::Key>,
-) -> Option<&'a mut Item> {
+/// Walks (and creates, if necessary) the tables designated by `keys`.
+///
+/// As per spec, a key which refers to an array of tables refers to the most
+/// recently defined element of the array. Keys with syntax errors, like in
+/// `[a.]`, designate nothing.
+fn insert_into<'a, 'i>(
+    item: &'i mut Item<'a>,
+    keys: impl Iterator<Item = ast::Key<'a>>,
+) -> Option<&'i mut Item<'a>> {
+    let keys = keys
+        .map(|key| Some((key, key.try_name()?)))
+        .collect::<Option<Vec<_>>>()?;
+    if keys.is_empty() {
+        return None;
+    }
     let mut curr = item;
-    for key in keys {
-        let prev = curr; // nll shenanigans
-        match prev {
-            Item::Map(map) => {
-                let key_name = key.name(doc).to_string();
-                let mut entry = map.map.entry(key_name).or_insert_with(|| {
-                    (vec![], Item::Map(Map::new()))
-                });
-                entry.0.push(key);
-                curr = &mut entry.1;
-            }
-            _ => {
-                // FIXME:
-                return None;
-            }
+    for (key, name) in keys {
+        if let Item::Array(array) = curr {
+            curr = array.last_mut()?;
+        }
+        let map = match curr {
+            Item::Map(map) => map,
+            _ => return None,
         };
+        let entry = map
+            .map
+            .entry(name.into_owned())
+            .or_insert_with(|| (Vec::new(), Item::Map(Map::new())));
+        entry.0.push(key);
+        curr = &mut entry.1;
     }
     Some(curr)
 }
 
-fn from_value(doc: &TomlDoc, value: ast::Value) -> Item {
-    match value.kind(doc) {
-        ast::ValueKind::Array(a) => Item::Array(
-            a.values(doc).map(|val| from_value(doc, val)).collect(),
-        ),
+fn from_value(value: ast::Value<'_>) -> Option<Item<'_>> {
+    // Values with syntax errors don't contribute to the model.
+    let res = match value.try_kind()? {
+        ast::ValueKind::Array(a) => Item::Array(a.values().filter_map(from_value).collect()),
         ast::ValueKind::Dict(d) => {
             let mut map = Item::Map(Map::new());
-            fill(doc, d.cst(), &mut map);
+            fill(d.syntax(), &mut map);
             map
         }
-        ast::ValueKind::Number(n) => Item::Integer(n.value(doc)),
-        ast::ValueKind::Bool(b) => Item::Bool(b.value(doc)),
-        ast::ValueKind::DateTime(_) => Item::DateTime,
-        ast::ValueKind::StringLit(s) => Item::String(s.value(doc).to_string()),
-    }
+        ast::ValueKind::Number(n) => match n.value().ok()? {
+            ast::NumberValue::Integer(v) => Item::Integer(v),
+            ast::NumberValue::Float(v) => Item::Float(v),
+        },
+        ast::ValueKind::Bool(b) => Item::Bool(b.value()),
+        ast::ValueKind::DateTime(d) => Item::DateTime(d.value().ok()?),
+        ast::ValueKind::StringLit(s) => Item::String(s.value().into_owned()),
+    };
+    Some(res)
 }
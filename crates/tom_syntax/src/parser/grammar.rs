@@ -66,16 +66,7 @@ impl<'t, 's> Parser<'t, 's> {
             panic!("bumping past EOF");
         }
         let pos = self.tokens.significant[self.pos];
-        self.sink.token(pos, None);
-        self.pos += 1;
-    }
-
-    fn bump_remap(&mut self, s: Symbol) {
-        if self.pos == self.tokens.significant.len() {
-            panic!("bumping past EOF");
-        }
-        let pos = self.tokens.significant[self.pos];
-        self.sink.token(pos, Some(s));
+        self.sink.token(pos);
         self.pos += 1;
     }
 
@@ -132,8 +123,7 @@ impl<'s, 't> Parser<'s, 't> {
     fn entries(&mut self) {
         while self.current() != EOF && self.current() != L_BRACK {
             match self.current() {
-                BARE_KEY | BARE_KEY_OR_NUMBER | BARE_KEY_OR_DATE | BASIC_STRING
-                | LITERAL_STRING => self.entry(),
+                BARE_KEY | BASIC_STRING | LITERAL_STRING => self.entry(),
                 _ => self.bump_error("expected a key"),
             }
         }
@@ -172,7 +162,11 @@ impl<'s, 't> Parser<'s, 't> {
             // foo = 92
             // 92 = 92
             // 1914-08-26 = 92
-            BARE_KEY | BARE_KEY_OR_NUMBER | BARE_KEY_OR_DATE => self.bump_remap(BARE_KEY),
+            // 0xff = 92
+            // -inf = 92
+            // true = 92
+            // 1.2 = 92
+            BARE_KEY => self.bump(),
             // test-key-str
             // "foo" = 92
             // 'bar' = 92
@@ -188,14 +182,28 @@ impl<'s, 't> Parser<'s, 't> {
             // test-val-num
             // a = 92
             // b = 8.5
-            BARE_KEY_OR_NUMBER | NUMBER => self.bump_remap(NUMBER),
+            // c = -1_000
+            // d = 5e+22
+            // e = 1e06
+            // f = 0xDEAD_beef
+            // g = 0o755
+            // h = 0b1101_0110
+            // i = inf
+            // j = +inf
+            // k = -nan
+            NUMBER => self.bump(),
             // test-val-bool
             // a = true
             // b = false
             BOOL => self.bump(),
+            // test-val-date-time
             // a = 1914-08-26
             // b = 1979-05-27T07:32:00-08:00
-            BARE_KEY_OR_DATE | DATE_TIME => self.bump_remap(DATE_TIME),
+            // c = 1979-05-27 07:32:00Z
+            // d = 1979-05-27t07:32:00.999999z
+            // e = 1979-05-27 07:32:00
+            // f = 07:32:00.5
+            DATE_TIME => self.bump(),
             // test-val-str
             // a = "hello\nworld"
             // b = """
@@ -1,9 +1,11 @@
-//! FIXME: write short doc here
+//! Hand-written TOML lexer.
+//!
+//! TOML is not context-free at the token level: `1979-05-27` is a bare key
+//! on the left of `=` and a date on the right. The lexer tracks whether it
+//! is at a key or at a value position (using the nesting of brackets and
+//! braces), so that the parser always sees the right token.
 
-use m_lexer::{Lexer, LexerBuilder, TokenKind};
-use lazy_static::lazy_static;
-
-use crate::{symbol, Symbol, TextRange, TextUnit};
+use crate::{symbol::*, ErrorCode, Symbol, SyntaxError, TextRange, TextUnit};
 
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct Token {
@@ -13,10 +15,7 @@ pub(crate) struct Token {
 
 impl Token {
     pub fn is_significant(self) -> bool {
-        match self.symbol {
-            symbol::WHITESPACE | symbol::COMMENT => false,
-            _ => true,
-        }
+        !matches!(self.symbol, WHITESPACE | COMMENT)
     }
 }
 
@@ -24,19 +23,22 @@ impl Token {
 pub(crate) struct Tokens {
     pub raw_tokens: Vec<Token>,
     pub significant: Vec<usize>,
+    pub errors: Vec<SyntaxError>,
 }
 
 pub(crate) fn tokenize(input: &str) -> Tokens {
-    let mut raw_tokens = Vec::new();
-    let mut offset = TextUnit::from(0);
-    for t in LEXER.tokenize(input) {
-        let len = TextUnit::from(t.len as u32);
-        raw_tokens.push(Token {
-            symbol: Symbol::new(t.kind.0),
-            range: TextRange::offset_len(offset, len),
-        });
-        offset += len;
+    let mut lexer = Lexer {
+        input,
+        pos: 0,
+        tokens: Vec::new(),
+        errors: Vec::new(),
+        stack: Vec::new(),
+        at_value: false,
+    };
+    while lexer.pos < input.len() {
+        lexer.token();
     }
+    let raw_tokens = lexer.tokens;
     let significant = raw_tokens
         .iter()
         .enumerate()
@@ -46,78 +48,396 @@ pub(crate) fn tokenize(input: &str) -> Tokens {
     Tokens {
         raw_tokens,
         significant,
+        errors: lexer.errors,
+    }
+}
+
+/// Lexes the first token of `input`, as if it was at a value position if
+/// `at_value` is set. Returns `None` if the token has errors.
+pub(crate) fn first_token(input: &str, at_value: bool) -> Option<Token> {
+    let mut lexer = Lexer {
+        input,
+        pos: 0,
+        tokens: Vec::new(),
+        errors: Vec::new(),
+        stack: Vec::new(),
+        at_value,
+    };
+    lexer.token();
+    if !lexer.errors.is_empty() {
+        return None;
     }
+    lexer.tokens.pop()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Context {
+    TableHeader,
+    Array,
+    Dict,
 }
 
-lazy_static! {
-    static ref LEXER: Lexer = lexer();
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+    tokens: Vec<Token>,
+    errors: Vec<SyntaxError>,
+    stack: Vec<Context>,
+    /// Whether the next token starts a value rather than a key.
+    at_value: bool,
 }
 
-fn lexer() -> Lexer {
-    fn t(s: Symbol) -> TokenKind {
-        TokenKind(s.0.get() as u16)
-    }
-
-    LexerBuilder::new()
-        .error_token(t(symbol::ERROR))
-        .tokens(&[
-            (t(symbol::EQ), r"="),
-            (t(symbol::DOT), r"\."),
-            (t(symbol::COMMA), r","),
-            (t(symbol::L_BRACK), r"\["),
-            (t(symbol::R_BRACK), r"\]"),
-            (t(symbol::L_CURLY), r"\{"),
-            (t(symbol::R_CURLY), r"\}"),
-            (t(symbol::WHITESPACE), r"\s+"),
-            (t(symbol::COMMENT), r"#.*"),
-            (t(symbol::BARE_KEY_OR_NUMBER), r"[0-9]+"),
-            (
-                t(symbol::BARE_KEY_OR_DATE),
-                r"[0-9]{4}-[0-9]{2}-[0-9]{2}[Zz]?",
-            ),
-            (
-                t(symbol::BASIC_STRING),
-                r#"(?x)
-                    " ([^\r\n"\\] | \\.)* "
-                "#,
-            ),
-            (
-                t(symbol::MULTILINE_BASIC_STRING),
-                r#"(?x)
-                    """ ([^"] | \\. | "[^"] | ""[^"])* """
-                "#,
-            ),
-            (
-                t(symbol::LITERAL_STRING),
-                r#"(?x)
-                    ' ([^\r\n'] | \\.)* '
-                "#,
-            ),
-            (
-                t(symbol::MULTILINE_LITERAL_STRING),
-                r#"(?x)
-                    ''' ([^'] | \\. | '[^'] | ''[^'])* '''
-                "#,
-            ),
-            (t(symbol::BOOL), r"(false|true)"),
-            (
-                t(symbol::NUMBER),
-                r"(?x)
-                    [-+]?
-                    (0|[1-9](_?[0-9])*) # no leading zeros
-                    (\.[0-9](_?[0-9])*)?
-                    ([eE][-+]?[1-9](_?[0-9])*)?
-                ",
-            ),
-            (
-                t(symbol::DATE_TIME),
-                r"(?x)
-                    ( [0-9]{4}-[0-9]{2}-[0-9]{2} ([Tt]([0-9]{2}:[0-9]{2}:[0-9]{2}(\.[0-9]+)?))?
-                    | ([0-9]{2}:[0-9]{2}:[0-9]{2}(\.[0-9]+)?))
-                    ([Zz]|[+-][0-9]{2}:[0-9]{2})?
-                ",
-            ),
-            (t(symbol::BARE_KEY), r"[0-9_\-a-zA-Z]+"),
-        ])
-        .build()
+impl Lexer<'_> {
+    fn token(&mut self) {
+        let start = self.pos;
+        let symbol = match self.current() {
+            b' ' | b'\t' | b'\n' => self.whitespace(),
+            b'\r' if self.nth(1) == Some(b'\n') => self.whitespace(),
+            b'#' => self.comment(),
+            b'=' => {
+                self.pos += 1;
+                // `[a = 1` is a broken header rather than an entry.
+                if self.stack.last() != Some(&Context::TableHeader) {
+                    self.at_value = true;
+                }
+                EQ
+            }
+            b'.' => {
+                self.pos += 1;
+                DOT
+            }
+            b',' => {
+                self.pos += 1;
+                match self.stack.last() {
+                    Some(Context::Array) => self.at_value = true,
+                    Some(Context::Dict) => self.at_value = false,
+                    _ => (),
+                }
+                COMMA
+            }
+            b'[' => {
+                self.pos += 1;
+                let context = if self.at_value {
+                    Context::Array
+                } else {
+                    Context::TableHeader
+                };
+                self.stack.push(context);
+                L_BRACK
+            }
+            b']' => {
+                self.pos += 1;
+                if let Some(Context::Array) | Some(Context::TableHeader) = self.stack.last() {
+                    self.stack.pop();
+                }
+                self.at_value = false;
+                R_BRACK
+            }
+            b'{' => {
+                self.pos += 1;
+                self.stack.push(Context::Dict);
+                self.at_value = false;
+                L_CURLY
+            }
+            b'}' => {
+                self.pos += 1;
+                if self.stack.last() == Some(&Context::Dict) {
+                    self.stack.pop();
+                }
+                self.at_value = false;
+                R_CURLY
+            }
+            b'"' => self.basic_string(),
+            b'\'' => self.literal_string(),
+            b if self.at_value && is_value_char(b) => {
+                let symbol = self.value();
+                self.at_value = false;
+                symbol
+            }
+            b if is_bare_key_char(b) => {
+                self.eat_while(is_bare_key_char);
+                BARE_KEY
+            }
+            _ => {
+                // The error takes the place of the value, like `?` in
+                // `a = ?`.
+                let symbol = self.error_token();
+                self.at_value = false;
+                symbol
+            }
+        };
+        self.tokens.push(Token {
+            symbol,
+            range: range(start, self.pos),
+        });
+    }
+
+    fn whitespace(&mut self) -> Symbol {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b' ') | Some(b'\t') | Some(b'\n') => self.pos += 1,
+                Some(b'\r') if self.nth(1) == Some(b'\n') => self.pos += 2,
+                _ => break,
+            }
+        }
+        if self.input[start..self.pos].contains('\n') {
+            // Table headers must fit into a single line.
+            while let Some(Context::TableHeader) = self.stack.last() {
+                self.stack.pop();
+            }
+        }
+        WHITESPACE
+    }
+
+    fn comment(&mut self) -> Symbol {
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => break,
+                Some(b'\r') if self.nth(1) == Some(b'\n') => break,
+                Some(_) => self.bump_char("control characters are not allowed in comments"),
+            }
+        }
+        COMMENT
+    }
+
+    fn basic_string(&mut self) -> Symbol {
+        let start = self.pos;
+        if self.at_str("\"\"\"") {
+            self.pos += 3;
+            loop {
+                match self.peek() {
+                    None => {
+                        self.error(start, self.pos, "unterminated multiline string");
+                        break;
+                    }
+                    Some(b'"') if self.at_str("\"\"\"") => {
+                        self.pos += 3;
+                        // Up to two quotes are allowed right before the
+                        // closing delimiter.
+                        for _ in 0..2 {
+                            if self.peek() == Some(b'"') {
+                                self.pos += 1;
+                            }
+                        }
+                        break;
+                    }
+                    Some(b'\\') => {
+                        self.pos += 1;
+                        if self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                            self.bump_char("control characters must be escaped");
+                        }
+                    }
+                    Some(b'\n') => self.pos += 1,
+                    Some(b'\r') if self.nth(1) == Some(b'\n') => self.pos += 2,
+                    Some(_) => self.bump_char("control characters must be escaped"),
+                }
+            }
+            self.at_value = false;
+            return MULTILINE_BASIC_STRING;
+        }
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => {
+                    self.error(start, self.pos, "unterminated string");
+                    break;
+                }
+                Some(b'\r') if self.nth(1) == Some(b'\n') => {
+                    self.error(start, self.pos, "unterminated string");
+                    break;
+                }
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    if self.peek().is_some_and(|b| b != b'\n' && b != b'\r') {
+                        self.bump_char("control characters must be escaped");
+                    }
+                }
+                Some(_) => self.bump_char("control characters must be escaped"),
+            }
+        }
+        self.at_value = false;
+        BASIC_STRING
+    }
+
+    fn literal_string(&mut self) -> Symbol {
+        let start = self.pos;
+        if self.at_str("'''") {
+            self.pos += 3;
+            loop {
+                match self.peek() {
+                    None => {
+                        self.error(start, self.pos, "unterminated multiline string");
+                        break;
+                    }
+                    Some(b'\'') if self.at_str("'''") => {
+                        self.pos += 3;
+                        for _ in 0..2 {
+                            if self.peek() == Some(b'\'') {
+                                self.pos += 1;
+                            }
+                        }
+                        break;
+                    }
+                    Some(b'\n') => self.pos += 1,
+                    Some(b'\r') if self.nth(1) == Some(b'\n') => self.pos += 2,
+                    Some(_) => {
+                        self.bump_char("control characters are not allowed in literal strings")
+                    }
+                }
+            }
+            self.at_value = false;
+            return MULTILINE_LITERAL_STRING;
+        }
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                None | Some(b'\n') => {
+                    self.error(start, self.pos, "unterminated string");
+                    break;
+                }
+                Some(b'\r') if self.nth(1) == Some(b'\n') => {
+                    self.error(start, self.pos, "unterminated string");
+                    break;
+                }
+                Some(b'\'') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(_) => self.bump_char("control characters are not allowed in literal strings"),
+            }
+        }
+        self.at_value = false;
+        LITERAL_STRING
+    }
+
+    /// Lexes a number, a bool or a date-time. The value itself is validated
+    /// later, by the `ast` layer.
+    fn value(&mut self) -> Symbol {
+        let start = self.pos;
+        self.eat_while(is_value_char);
+        // `1979-05-27 07:32:00`: a space is allowed as a date-time separator.
+        let is_date = |text: &[u8]| {
+            text.len() == 10
+                && text[4] == b'-'
+                && text[7] == b'-'
+                && text[..4].iter().all(u8::is_ascii_digit)
+        };
+        let is_time = |text: &[u8]| {
+            text.len() >= 3 && text[2] == b':' && text[..2].iter().all(u8::is_ascii_digit)
+        };
+        let bytes = self.input.as_bytes();
+        if is_date(&bytes[start..self.pos])
+            && self.peek() == Some(b' ')
+            && is_time(&bytes[self.pos + 1..(self.pos + 4).min(bytes.len())])
+        {
+            self.pos += 1;
+            self.eat_while(is_value_char);
+        }
+
+        let text = &bytes[start..self.pos];
+        match text {
+            b"true" | b"false" => BOOL,
+            b"inf" | b"nan" => NUMBER,
+            _ if text.len() >= 5 && text[4] == b'-' && text[..4].iter().all(u8::is_ascii_digit) => {
+                DATE_TIME
+            }
+            _ if is_time(text) => DATE_TIME,
+            [b'0'..=b'9', ..] | [b'+', ..] | [b'-', ..] => NUMBER,
+            // Not a value at all, let the parser report it.
+            _ => BARE_KEY,
+        }
+    }
+
+    fn error_token(&mut self) -> Symbol {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if self.pos != start && is_token_start(b) {
+                break;
+            }
+            self.pos += utf8_len(b);
+        }
+        let text = &self.input[start..self.pos];
+        if text.chars().any(is_control) {
+            self.error(start, self.pos, "control characters are not allowed");
+        }
+        ERROR
+    }
+
+    /// Bumps the current char, reporting it if it is a control character.
+    fn bump_char(&mut self, message: &str) {
+        let start = self.pos;
+        let c = self.input[start..].chars().next().unwrap();
+        self.pos += c.len_utf8();
+        if is_control(c) {
+            self.error(start, self.pos, message)
+        }
+    }
+
+    fn eat_while(&mut self, p: impl Fn(u8) -> bool) {
+        while self.peek().is_some_and(&p) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&mut self, start: usize, end: usize, message: &str) {
+        self.errors.push(SyntaxError {
+            range: range(start, end),
+            message: message.into(),
+            secondary_range: None,
+            code: ErrorCode::Syntax,
+        })
+    }
+
+    fn at_str(&self, s: &str) -> bool {
+        self.input[self.pos..].starts_with(s)
+    }
+
+    fn current(&self) -> u8 {
+        self.input.as_bytes()[self.pos]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.nth(0)
+    }
+
+    fn nth(&self, n: usize) -> Option<u8> {
+        self.input.as_bytes().get(self.pos + n).copied()
+    }
+}
+
+fn range(start: usize, end: usize) -> TextRange {
+    TextRange::from_to(TextUnit::from(start as u32), TextUnit::from(end as u32))
+}
+
+fn is_bare_key_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+}
+
+fn is_value_char(b: u8) -> bool {
+    is_bare_key_char(b) || b == b'+' || b == b'.' || b == b':'
+}
+
+fn is_token_start(b: u8) -> bool {
+    is_value_char(b) || b"=,[]{}\"'# \t\n\r".contains(&b)
+}
+
+/// Control characters, except for tab, are not allowed anywhere outside
+/// of whitespace.
+fn is_control(c: char) -> bool {
+    matches!(c, '\u{0}'..='\u{8}' | '\u{a}'..='\u{1f}' | '\u{7f}')
+}
+
+fn utf8_len(first_byte: u8) -> usize {
+    match first_byte {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4,
+    }
 }
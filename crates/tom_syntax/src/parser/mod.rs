@@ -3,9 +3,18 @@
 mod grammar;
 mod lexer;
 
-use crate::{symbol::*, SyntaxNode, GreenBuilder, Symbol, SmolStr, TextRange, SyntaxError};
+use crate::{
+    symbol::*, rtree::GreenNode, ErrorCode, GreenBuilder, Symbol, SmolStr, TextRange, TextUnit, SyntaxError,
+};
+
+/// Lexes the first token of `input`, returning its symbol and length if it
+/// has no errors.
+pub(crate) fn lex_first_token(input: &str, at_value: bool) -> Option<(Symbol, TextUnit)> {
+    let token = lexer::first_token(input, at_value)?;
+    Some((token.symbol, token.range.len()))
+}
 
-pub(crate) fn parse(input: &str) -> SyntaxNode {
+pub(crate) fn parse(input: &str) -> (GreenNode, Vec<SyntaxError>) {
     let tokens = lexer::tokenize(input);
     let mut sink = EventSink::new(input, &tokens);
     {
@@ -17,7 +26,7 @@ pub(crate) fn parse(input: &str) -> SyntaxNode {
         parser.parse();
     }
     let green = sink.builder.finish();
-    SyntaxNode::new(green, sink.errors)
+    (green, sink.errors)
 }
 
 struct Parser<'s, 't: 's> {
@@ -41,7 +50,7 @@ impl<'t> EventSink<'t> {
             text,
             tokens,
             builder: GreenBuilder::new(),
-            errors: Vec::new(),
+            errors: tokens.errors.clone(),
         }
     }
 
@@ -49,7 +58,7 @@ impl<'t> EventSink<'t> {
         let ws = self.whitespace();
         let n = self.leading_ws(ws, s);
         for _ in 0..(ws.len() - n) {
-            self.bump(None)
+            self.bump()
         }
         self.builder.start_internal(s);
     }
@@ -58,16 +67,15 @@ impl<'t> EventSink<'t> {
         let ws = self.whitespace();
         let n = self.trailing_ws(ws, s);
         for _ in 0..n {
-            self.bump(None)
+            self.bump()
         }
         self.builder.finish_internal();
     }
 
-    fn token(&mut self, pos: usize, s: Option<Symbol>) {
-        while self.pos < pos {
-            self.bump(None)
+    fn token(&mut self, pos: usize) {
+        while self.pos <= pos {
+            self.bump()
         }
-        self.bump(s);
     }
 
     fn error(&mut self, message: impl Into<String>) {
@@ -75,6 +83,8 @@ impl<'t> EventSink<'t> {
             self.errors.push(SyntaxError {
                 range: TextRange::from_to(0.into(), 0.into()),
                 message: message.into(),
+                secondary_range: None,
+                code: ErrorCode::Syntax,
             });
             return;
         }
@@ -101,16 +111,21 @@ impl<'t> EventSink<'t> {
         self.errors.push(SyntaxError {
             range: tok.range,
             message: message.into(),
+            secondary_range: None,
+            code: ErrorCode::Syntax,
         })
     }
 
     fn leading_ws(&self, ws: &[lexer::Token], s: Symbol) -> usize {
         match s {
             DOC => ws.len(),
-            ENTRY | TABLE => {
+            ENTRY | TABLE | ARRAY_TABLE => {
                 let mut adj_comments = 0;
                 for (i, token) in ws.iter().rev().enumerate() {
                     match token.symbol {
+                        // A comment at the end of the previous line belongs
+                        // to that line.
+                        COMMENT if !self.starts_line(ws, ws.len() - 1 - i) => break,
                         COMMENT => {
                             adj_comments = i + 1;
                         }
@@ -129,6 +144,18 @@ impl<'t> EventSink<'t> {
         }
     }
 
+    /// Whether the token `ws[idx]` is the first one on its line.
+    fn starts_line(&self, ws: &[lexer::Token], idx: usize) -> bool {
+        match idx.checked_sub(1) {
+            Some(prev) => {
+                let prev = &ws[prev];
+                prev.symbol == WHITESPACE
+                    && (self.text[prev.range].contains('\n') || prev.range.start() == 0.into())
+            }
+            None => ws[idx].range.start() == 0.into(),
+        }
+    }
+
     fn trailing_ws(&self, ws: &[lexer::Token], s: Symbol) -> usize {
         match s {
             DOC => ws.len(),
@@ -166,11 +193,10 @@ impl<'t> EventSink<'t> {
         &self.tokens.raw_tokens[start..end]
     }
 
-    fn bump(&mut self, s: Option<Symbol>) {
+    fn bump(&mut self) {
         let t = self.tokens.raw_tokens[self.pos];
-        let s = s.unwrap_or(t.symbol);
         let text: SmolStr = self.text[t.range].into();
-        self.builder.leaf(s, text);
+        self.builder.leaf(t.symbol, text);
         self.pos += 1;
     }
 }
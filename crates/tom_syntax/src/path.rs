@@ -0,0 +1,609 @@
+//! Access to values by dotted key paths, like `dependencies.serde.version`
+//! or `bin[2].name`.
+//!
+//! A path is resolved through the semantic model, so it doesn't matter
+//! whether a table is defined with a `[header]`, with dotted keys or inline.
+//! The keys of a path are bare or quoted, as in TOML, and `[n]` selects an
+//! element of an array or of an array of tables.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    ast, edit::escaped_key, model::entry_value, symbol::*, Edit, IntoValue, Item, Position,
+    SyntaxNodeRef, TextEdit, TomlDoc,
+};
+
+impl TomlDoc {
+    /// Returns the value at `path`, or `None` if there's no such value or
+    /// the path is malformed.
+    pub fn get(&self, path: &str) -> Option<Item<'_>> {
+        let segments = parse(path).ok()?;
+        match resolve(self, &segments).ok()? {
+            Resolved::Found(item, _) => Some(item),
+            Resolved::Missing { .. } => None,
+        }
+    }
+
+    /// Sets the value at `path`, creating the missing tables.
+    ///
+    /// A new key is added to the table which already holds its nearest
+    /// existing parent, using dotted keys if necessary. Tables which don't
+    /// exist at all are created as new `[table]` sections. A document with
+    /// errors can't be edited.
+    pub fn set(&mut self, path: &str, value: impl IntoValue) -> Result<(), PathError> {
+        let segments = parse(path)?;
+        self.check_no_errors()?;
+        let text = value
+            .value_text()
+            .map_err(|err| PathError::new(err.to_string()))?;
+        let doc = {
+            let mut edit = self.start_edit();
+            match resolve(self, &segments)? {
+                Resolved::Found(_, Place::Value(old)) => {
+                    let new = edit.new_value_from_text(&text);
+                    edit.replace(old, new.borrowed());
+                }
+                Resolved::Found(_, Place::Table(_)) => {
+                    return Err(PathError::new(format!("`{}` is a table", path)));
+                }
+                Resolved::Missing { place, rest } => {
+                    let rest = rest
+                        .iter()
+                        .map(|segment| match segment {
+                            Segment::Key(name) => Ok(name.as_str()),
+                            Segment::Index(_) => Err(PathError::new(format!(
+                                "`{}` doesn't exist and can't be created",
+                                path
+                            ))),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    insert(self, &mut edit, place, &rest, &text)
+                }
+            }
+            edit.finish_edit_no_reparse()
+        };
+        *self = doc;
+        Ok(())
+    }
+
+    /// Removes the value at `path`, together with all the sections and
+    /// entries which define it. Returns `false` if there was no such value.
+    /// A document with errors can't be edited.
+    pub fn remove(&mut self, path: &str) -> Result<bool, PathError> {
+        let segments = parse(path)?;
+        self.check_no_errors()?;
+        let doc = {
+            let mut edit = self.start_edit();
+            match resolve(self, &segments)? {
+                Resolved::Found(_, place) => remove(&mut edit, place),
+                Resolved::Missing { .. } => return Ok(false),
+            }
+            edit.finish_edit_no_reparse()
+        };
+        *self = doc;
+        Ok(true)
+    }
+
+    /// Returns the keys which define or extend the value at `path`, in the
+    /// document order, like the `a` of `[a]`, of `[a.b]` and of `a.c = 1`.
+    /// The keys of an element of an array of tables are its `[[header]]` and
+    /// the headers of its tables.
+    pub fn get_keys(&self, path: &str) -> Vec<ast::Key<'_>> {
+        match parse(path) {
+            Ok(segments) => keys_at(self, &segments),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Returns the edits which rename the `key` to `new_name`, together with
+    /// all the keys which refer to the same value, in the document order.
+    /// The new keys are quoted if `new_name` isn't a valid bare key. Renaming
+    /// a key of an array of tables renames the whole array. A document with
+    /// errors can't be renamed in.
+    pub fn rename_key(&self, key: ast::Key, new_name: &str) -> Result<Vec<TextEdit>, PathError> {
+        self.check_no_errors()?;
+        let mut path =
+            key_path(key).ok_or_else(|| PathError::new("the key is malformed".to_string()))?;
+        while let Some(Segment::Index(_)) = path.last() {
+            path.pop();
+        }
+        let mut new_path = path.clone();
+        *new_path.last_mut().unwrap() = Segment::Key(new_name.to_string());
+        if self.get(&render(&new_path)).is_some() && key.name() != new_name {
+            let message = format!("`{}` already exists", render(&new_path));
+            return Err(PathError::new(message));
+        }
+        let keys = keys_at(self, &path);
+        let new_key = escaped_key(new_name);
+        let edits = keys
+            .into_iter()
+            .map(|it| TextEdit::replace(it.syntax().range(), new_key.clone()))
+            .collect();
+        Ok(edits)
+    }
+
+    fn check_no_errors(&self) -> Result<(), PathError> {
+        if self.errors().is_empty() {
+            Ok(())
+        } else {
+            Err(PathError::new("the document has errors".to_string()))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PathError {
+    message: String,
+}
+
+impl PathError {
+    pub(crate) fn new(message: String) -> PathError {
+        PathError { message }
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for PathError {}
+
+fn keys_at<'a>(doc: &'a TomlDoc, segments: &[Segment]) -> Vec<ast::Key<'a>> {
+    let mut item = Item::Map(doc.model());
+    let mut keys = Vec::new();
+    for (idx, segment) in segments.iter().enumerate() {
+        match (segment, item) {
+            (Segment::Key(name), Item::Map(mut map)) => {
+                keys = map.get_keys(name);
+                item = match map.remove(name) {
+                    Some(it) => it,
+                    None => return Vec::new(),
+                };
+            }
+            (&Segment::Index(i), Item::Array(items)) => {
+                let element = render(&segments[..=idx]);
+                keys.retain(|key| key.path().as_ref() == Some(&element));
+                item = match items.into_iter().nth(i) {
+                    Some(it) => it,
+                    None => return Vec::new(),
+                };
+            }
+            _ => return Vec::new(),
+        }
+    }
+    keys
+}
+
+impl<'a> ast::Key<'a> {
+    /// Returns the path of the value which the key defines or extends, in
+    /// the syntax of `TomlDoc::get`. The path is resolved by walking up the
+    /// tree, so `d` in `c.d = 1` under `[a.b]` is at `a.b.c.d`, and `host`
+    /// in the fourth `[[servers]]` is at `servers[3].host`. Returns `None`
+    /// if a key on the way is malformed.
+    pub fn path(self) -> Option<String> {
+        Some(render(&key_path(self)?))
+    }
+}
+
+impl TomlDoc {
+    /// Returns the `[tables]` and the `[[array tables]]` of the document
+    /// with their paths, in the document order, like `servers[1].disks[0]`
+    /// for the first `[[servers.disks]]` after the second `[[servers]]`.
+    /// The tables with malformed headers are left out.
+    pub fn table_paths(&self) -> Vec<(ast::TableHeader<'_>, String)> {
+        table_paths(self.cst())
+            .map(|(header, path)| (header, render(&path)))
+            .collect()
+    }
+}
+
+fn key_path(key: ast::Key) -> Option<Vec<Segment>> {
+    // The segments from the key up to the root.
+    let mut rev = Vec::new();
+    let mut node = key.syntax();
+    while let Some(parent) = node.parent() {
+        match parent.symbol() {
+            ENTRY => {
+                let keys: Vec<ast::Key> = parent.children().filter_map(ast::Key::cast).collect();
+                // The keys after `node` are nested in it.
+                let len = keys
+                    .iter()
+                    .position(|it| it.syntax() == node)
+                    .map_or(keys.len(), |it| it + 1);
+                for key in keys[..len].iter().rev() {
+                    rev.push(Segment::Key(key.try_name()?.into_owned()));
+                }
+            }
+            ARRAY => {
+                let idx = parent
+                    .children()
+                    .filter(|it| it.symbol() == VALUE)
+                    .position(|it| it == node)
+                    .unwrap();
+                rev.push(Segment::Index(idx));
+            }
+            TABLE_HEADER => {
+                let header = ast::TableHeader::cast(parent).unwrap();
+                let len = header.keys().position(|it| it.syntax() == node).unwrap() + 1;
+                rev.extend(header_path(header, len)?.into_iter().rev());
+                break;
+            }
+            TABLE | ARRAY_TABLE => {
+                let header = parent.children().find_map(ast::TableHeader::cast).unwrap();
+                rev.extend(header_path(header, usize::MAX)?.into_iter().rev());
+                break;
+            }
+            _ => (),
+        }
+        node = parent;
+    }
+    rev.reverse();
+    Some(rev)
+}
+
+/// The path of the first `len` keys of a `[header]`, with the index of the
+/// element for each array of tables.
+fn header_path(header: ast::TableHeader, len: usize) -> Option<Vec<Segment>> {
+    let root = header.syntax().ancestors().last().unwrap();
+    let (_, mut path) = table_paths(root).find(|(it, _)| it.syntax() == header.syntax())?;
+    let mut keys = 0;
+    let end = path
+        .iter()
+        .position(|it| match it {
+            Segment::Key(_) if keys == len => true,
+            Segment::Key(_) => {
+                keys += 1;
+                false
+            }
+            Segment::Index(_) => false,
+        })
+        .unwrap_or(path.len());
+    path.truncate(end);
+    Some(path)
+}
+
+/// The headers of the tables of a document with their paths, which have
+/// the index of the element for each array of tables.
+pub(crate) fn table_paths(root: SyntaxNodeRef) -> impl Iterator<Item = (ast::TableHeader, Vec<Segment>)> {
+    // The index of the last element of each array of tables so far.
+    let mut indices: HashMap<Vec<String>, usize> = HashMap::new();
+    root.children().filter_map(move |table| {
+        let header = table.children().find_map(ast::TableHeader::cast)?;
+        let keys = header
+            .keys()
+            .map(|it| it.try_name().map(|it| it.into_owned()))
+            .collect::<Option<Vec<_>>>()?;
+        if keys.is_empty() {
+            return None;
+        }
+        if table.symbol() == ARRAY_TABLE {
+            // `[[a.b]]` is an array in the last element of `a`.
+            indices.retain(|it, _| !(it.len() > keys.len() && it.starts_with(&keys)));
+            let element = indices.get(&keys).map_or(0, |it| it + 1);
+            indices.insert(keys.clone(), element);
+        }
+        let mut path = Vec::new();
+        for (idx, key) in keys.iter().enumerate() {
+            path.push(Segment::Key(key.clone()));
+            if let Some(&element) = indices.get(&keys[..=idx]) {
+                path.push(Segment::Index(element));
+            }
+        }
+        Some((header, path))
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Segment::Key(name) => f.write_str(&escaped_key(name)),
+            Segment::Index(idx) => write!(f, "[{}]", idx),
+        }
+    }
+}
+
+pub(crate) fn parse(path: &str) -> Result<Vec<Segment>, PathError> {
+    let error = || PathError::new(format!("invalid path: `{}`", path));
+    let mut segments = Vec::new();
+    let mut chars = path.chars().peekable();
+    loop {
+        let name = match chars.next().ok_or_else(error)? {
+            quote @ '"' | quote @ '\'' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next().ok_or_else(error)? {
+                        c if c == quote => break,
+                        '\\' if quote == '"' => {
+                            name.push('\\');
+                            name.push(chars.next().ok_or_else(error)?);
+                        }
+                        c => name.push(c),
+                    }
+                }
+                if quote == '"' {
+                    ast::unescape_basic(&name).ok_or_else(error)?
+                } else {
+                    name
+                }
+            }
+            c if is_bare_key_char(c) => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek().filter(|&&c| is_bare_key_char(c)) {
+                    name.push(c);
+                    chars.next();
+                }
+                name
+            }
+            _ => return Err(error()),
+        };
+        segments.push(Segment::Key(name));
+        while chars.peek() == Some(&'[') {
+            chars.next();
+            let mut idx = String::new();
+            loop {
+                match chars.next().ok_or_else(error)? {
+                    ']' => break,
+                    c => idx.push(c),
+                }
+            }
+            segments.push(Segment::Index(idx.parse().map_err(|_| error())?));
+        }
+        match chars.next() {
+            None => return Ok(segments),
+            Some('.') => (),
+            Some(_) => return Err(error()),
+        }
+    }
+}
+
+/// The text of the path, the inverse of `parse`.
+fn render(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(idx, it)| match (idx, it) {
+            (0, _) | (_, Segment::Index(_)) => it.to_string(),
+            _ => format!(".{}", it),
+        })
+        .collect()
+}
+
+fn is_bare_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Where an item is defined in the document.
+enum Place<'a> {
+    /// A non-inline table, defined by the keys (none for the document).
+    Table(Vec<ast::Key<'a>>),
+    /// An entry's value or an element of an inline array.
+    Value(ast::Value<'a>),
+}
+
+enum Resolved<'a, 's> {
+    Found(Item<'a>, Place<'a>),
+    /// The path is missing starting from `rest`, `place` is the table which
+    /// would contain `rest`.
+    Missing {
+        place: Place<'a>,
+        rest: &'s [Segment],
+    },
+}
+
+fn resolve<'a, 's>(
+    doc: &'a TomlDoc,
+    segments: &'s [Segment],
+) -> Result<Resolved<'a, 's>, PathError> {
+    let mut item = Item::Map(doc.model());
+    let mut place = Place::Table(Vec::new());
+    for (idx, segment) in segments.iter().enumerate() {
+        let prefix = || render(&segments[..idx]);
+        match segment {
+            Segment::Key(name) => {
+                let mut map = match item {
+                    Item::Map(map) => map,
+                    _ => return Err(PathError::new(format!("`{}` is not a table", prefix()))),
+                };
+                let keys = map.get_keys(name);
+                item = match map.remove(name) {
+                    Some(it) => it,
+                    None => {
+                        return Ok(Resolved::Missing {
+                            place,
+                            rest: &segments[idx..],
+                        })
+                    }
+                };
+                place = match entry_value(&keys) {
+                    Some(value) => Place::Value(value),
+                    None => Place::Table(keys),
+                };
+            }
+            &Segment::Index(i) => {
+                let items = match item {
+                    Item::Array(items) => items,
+                    _ => return Err(PathError::new(format!("`{}` is not an array", prefix()))),
+                };
+                let len = items.len();
+                item = match items.into_iter().nth(i) {
+                    Some(it) => it,
+                    None => {
+                        return Err(PathError::new(format!(
+                            "index {} is out of bounds of `{}`, which has {} elements",
+                            i,
+                            prefix(),
+                            len
+                        )))
+                    }
+                };
+                place = match place {
+                    Place::Value(value) => match value.kind() {
+                        ast::ValueKind::Array(array) => {
+                            Place::Value(array.values().nth(i).unwrap())
+                        }
+                        _ => unreachable!(),
+                    },
+                    Place::Table(mut keys) => {
+                        // The `[[header]]` of the element and the headers of
+                        // its tables.
+                        let element = render(&segments[..=idx]);
+                        keys.retain(|key| key.path().as_ref() == Some(&element));
+                        Place::Table(keys)
+                    }
+                };
+            }
+        }
+    }
+    Ok(Resolved::Found(item, place))
+}
+
+/// Inserts `keys = value` into the table at `place`.
+fn insert<'a>(doc: &'a TomlDoc, edit: &mut Edit<'a>, place: Place<'a>, keys: &[&str], value: &str) {
+    let (name, path) = keys.split_last().unwrap();
+    let defs = match place {
+        Place::Value(dict) => {
+            let dict = match dict.kind() {
+                ast::ValueKind::Dict(it) => it,
+                _ => unreachable!(),
+            };
+            let entry = edit.new_entry_from_text(&format!("{} = {}", render_keys(keys), value));
+            let anchor = dict
+                .entries()
+                .last()
+                .map_or(dict.syntax().first_child().unwrap(), |it| it.syntax());
+            return edit.insert(entry.borrowed(), Position::After(anchor));
+        }
+        Place::Table(defs) => defs,
+    };
+
+    let header = defs.iter().find_map(|&key| {
+        let header = key.syntax().parent().and_then(ast::TableHeader::cast)?;
+        if header.keys().last()?.syntax() == key.syntax() {
+            Some(header)
+        } else {
+            None
+        }
+    });
+    if let Some(header) = header {
+        let section = header.syntax().parent().unwrap();
+        let entry = edit.new_entry_from_text(&format!("{} = {}", render_keys(keys), value));
+        let anchor = section
+            .children()
+            .filter_map(ast::Entry::cast)
+            .last()
+            .map_or(header.syntax(), |it| it.syntax());
+        return edit.insert(entry.borrowed(), Position::After(anchor));
+    }
+
+    if defs.is_empty() {
+        let root = doc.cst();
+        if path.is_empty() {
+            let entry = edit.new_entry_from_text(&format!("{} = {}", escaped_key(name), value));
+            let position = match doc.ast().entries().last() {
+                Some(last) => Position::After(last.syntax()),
+                None => Position::PrependTo(root),
+            };
+            edit.insert(entry.borrowed(), position);
+        } else {
+            let text = format!("[{}]\n{} = {}", render_keys(path), escaped_key(name), value);
+            insert_section(doc, edit, &text);
+        }
+        return;
+    }
+
+    let dotted = defs
+        .iter()
+        .rev()
+        .find_map(|&key| Some((ast::Entry::cast(key.syntax().parent()?)?, key)));
+    if let Some((entry, key)) = dotted {
+        let prefix = key_prefix(entry.keys(), key);
+        let text = format!("{}.{} = {}", prefix, render_keys(keys), value);
+        let new_entry = edit.new_entry_from_text(&text);
+        return edit.insert(new_entry.borrowed(), Position::After(entry.syntax()));
+    }
+
+    // The table is only a prefix of headers, like `a` in `[a.b]`.
+    let key = defs[0];
+    let header = ast::TableHeader::cast(key.syntax().parent().unwrap()).unwrap();
+    let mut header_keys = key_prefix(header.keys(), key);
+    if !path.is_empty() {
+        header_keys = format!("{}.{}", header_keys, render_keys(path));
+    }
+    let text = format!("[{}]\n{} = {}", header_keys, escaped_key(name), value);
+    insert_section(doc, edit, &text);
+}
+
+/// Appends a new section to the end of the document.
+fn insert_section<'a>(doc: &'a TomlDoc, edit: &mut Edit<'a>, text: &str) {
+    let root = doc.cst();
+    let table = edit.new_table_from_text(text);
+    let last = root
+        .children()
+        .filter(|it| it.symbol() != WHITESPACE)
+        .last();
+    let position = match last {
+        Some(last) => Position::After(last),
+        None => Position::AppendTo(root),
+    };
+    edit.insert(table.borrowed(), position);
+}
+
+fn remove<'a>(edit: &mut Edit<'a>, place: Place<'a>) {
+    let keys = match place {
+        Place::Value(value) => {
+            let parent = value.syntax().parent().unwrap();
+            let node = if parent.symbol() == ARRAY {
+                value.syntax()
+            } else {
+                parent
+            };
+            return edit.detach(node);
+        }
+        Place::Table(keys) => keys,
+    };
+    let mut nodes: Vec<SyntaxNodeRef<'a>> = Vec::new();
+    for key in keys {
+        let parent = key.syntax().parent().unwrap();
+        let node = match parent.symbol() {
+            TABLE_HEADER => parent.parent().unwrap(),
+            _ => parent,
+        };
+        if !nodes.contains(&node) {
+            nodes.push(node);
+        }
+    }
+    for node in nodes {
+        edit.detach(node);
+    }
+}
+
+/// The text of the keys up to and including `last`.
+fn key_prefix<'a>(keys: impl Iterator<Item = ast::Key<'a>>, last: ast::Key<'a>) -> String {
+    let mut res = Vec::new();
+    for key in keys {
+        res.push(key.syntax().get_text());
+        if key.syntax() == last.syntax() {
+            break;
+        }
+    }
+    res.join(".")
+}
+
+fn render_keys(keys: &[&str]) -> String {
+    keys.iter()
+        .map(|it| escaped_key(it))
+        .collect::<Vec<_>>()
+        .join(".")
+}
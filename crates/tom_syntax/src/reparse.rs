@@ -0,0 +1,129 @@
+//! Incremental reparsing.
+//!
+//! `TomlDoc::reparse` applies a `TextEdit` without parsing the whole text
+//! again. An edit inside a string, a comment or a number relexes just that
+//! token, other edits reparse the smallest entry or table around them on its
+//! own. Either way, the new green node is spliced into the old tree. Edits
+//! which could change the structure around them, like the ones which touch
+//! the boundaries of a node, and documents with syntax errors fall back to a
+//! full reparse.
+
+use crate::{parser, rtree::GreenNode, symbol::*, SmolStr, SyntaxNodeRef, TextRange, TextUnit, TomlDoc};
+
+/// A replacement of a range of the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub delete: TextRange,
+    pub insert: String,
+}
+
+impl TextEdit {
+    pub fn replace(range: TextRange, text: impl Into<String>) -> TextEdit {
+        TextEdit {
+            delete: range,
+            insert: text.into(),
+        }
+    }
+
+    pub fn insert(offset: TextUnit, text: impl Into<String>) -> TextEdit {
+        TextEdit::replace(TextRange::offset_len(offset, 0.into()), text)
+    }
+
+    pub fn delete(range: TextRange) -> TextEdit {
+        TextEdit::replace(range, String::new())
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut buff = String::with_capacity(text.len() + self.insert.len());
+        buff.push_str(&text[..self.delete.start().to_usize()]);
+        buff.push_str(&self.insert);
+        buff.push_str(&text[self.delete.end().to_usize()..]);
+        buff
+    }
+}
+
+impl TomlDoc {
+    /// Returns the document with the edit applied, the same as `TomlDoc::new`
+    /// of the edited text.
+    pub fn reparse(&self, edit: TextEdit) -> TomlDoc {
+        let green = if self.root.root_data().is_empty() {
+            relex_token(self.cst(), &edit).or_else(|| reparse_block(self.cst(), &edit))
+        } else {
+            None
+        };
+        match green {
+            Some(green) => TomlDoc::from_green(green, Vec::new()),
+            None => TomlDoc::new(&edit.apply(&self.cst().get_text())),
+        }
+    }
+}
+
+fn relex_token(root: SyntaxNodeRef, edit: &TextEdit) -> Option<GreenNode> {
+    let token = root.covering_node(edit.delete);
+    match token.symbol() {
+        BASIC_STRING
+        | MULTILINE_BASIC_STRING
+        | LITERAL_STRING
+        | MULTILINE_LITERAL_STRING
+        | COMMENT
+        | NUMBER => (),
+        _ => return None,
+    }
+    if !is_strictly_inside(edit.delete, token.range()) {
+        return None;
+    }
+    let text = edited_text(token, edit);
+    // The lexer looks past the end of some tokens, like for the quotes right
+    // after the closing `"""`.
+    let mut input = text.clone();
+    input.push_str(next_leaf(token).and_then(|it| it.leaf_text()).unwrap_or(""));
+    let (symbol, len) = parser::lex_first_token(&input, token.symbol() == NUMBER)?;
+    if symbol != token.symbol() || len != TextUnit::of_str(&text) {
+        return None;
+    }
+    Some(token.replace_with(GreenNode::new_leaf(symbol, SmolStr::new(text))))
+}
+
+fn reparse_block(root: SyntaxNodeRef, edit: &TextEdit) -> Option<GreenNode> {
+    let node = root.covering_node(edit.delete).ancestors().find(|node| {
+        let is_block = match node.symbol() {
+            // The lexer treats the entries of inline tables differently.
+            ENTRY => node.parent().map(|it| it.symbol()) != Some(DICT),
+            TABLE | ARRAY_TABLE => true,
+            _ => false,
+        };
+        is_block && is_strictly_inside(edit.delete, node.range())
+    })?;
+    let (green, errors) = parser::parse(&edited_text(node, edit));
+    if !errors.is_empty() {
+        return None;
+    }
+    match green.children() {
+        [block] if block.kind() == node.symbol() => Some(node.replace_with(block.clone())),
+        _ => None,
+    }
+}
+
+fn edited_text(node: SyntaxNodeRef, edit: &TextEdit) -> String {
+    let edit = TextEdit::replace(edit.delete - node.range().start(), edit.insert.as_str());
+    edit.apply(&node.get_text())
+}
+
+/// The edit doesn't touch the first and the last char of the node, so that
+/// the tokens around the node stay the same.
+fn is_strictly_inside(range: TextRange, node: TextRange) -> bool {
+    node.start() < range.start() && range.end() < node.end()
+}
+
+fn next_leaf(node: SyntaxNodeRef) -> Option<SyntaxNodeRef> {
+    let mut next = node.ancestors().find_map(|it| it.next_sibling())?;
+    loop {
+        if next.is_leaf() {
+            return Some(next);
+        }
+        next = match next.first_child() {
+            Some(child) => child,
+            None => next.ancestors().find_map(|it| it.next_sibling())?,
+        };
+    }
+}
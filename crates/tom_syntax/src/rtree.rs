@@ -112,9 +112,10 @@ impl<R: TreeRoot<TomTypes>> SyntaxNode<R> {
     pub(crate) fn root_data(&self) -> &Vec<SyntaxError> {
         self.0.root_data()
     }
-    // pub(crate) fn replace_with(&self, replacement: GreenNode) -> GreenNode {
-    //     self.0.replace_with(replacement)
-    // }
+    /// Returns the green tree of the root with this node replaced.
+    pub(crate) fn replace_with(&self, replacement: GreenNode) -> GreenNode {
+        self.0.replace_with(replacement)
+    }
     pub fn borrowed<'a>(&'a self) -> SyntaxNode<RefRoot<'a>> {
         SyntaxNode(self.0.borrowed())
     }
@@ -179,9 +180,8 @@ fn generate<'a, T: 'a, F: Fn(&T) -> Option<T> + 'a>(
     step: F,
 ) -> impl Iterator<Item = T> + 'a {
     ::std::iter::repeat(()).scan(seed, move |state, ()| {
-        state.take().map(|curr| {
-            *state = step(&curr);
-            curr
+        state.take().inspect(|curr| {
+            *state = step(curr);
         })
     })
 }
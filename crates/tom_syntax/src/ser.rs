@@ -0,0 +1,1114 @@
+//! Serializes Rust values onto an existing `TomlDoc` with serde.
+//!
+//! `update_doc` doesn't print the document from scratch. The value is first
+//! serialized into a plain tree, which is then compared with the semantic
+//! model of the document. Only the differences are written back, as text
+//! edits, so comments, key order, whitespace and the style of untouched
+//! values are preserved.
+
+use std::{convert::TryFrom, fmt};
+
+use serde::ser::{self, Serialize};
+
+use crate::{
+    ast,
+    model::{entry_value, is_array_table_key},
+    edit::{basic_string, escaped_key, float, is_literal},
+    symbol::*,
+    IntoValue, Item, Map, SyntaxNodeRef, TextRange, TextUnit, TomlDateTime, TomlDoc,
+};
+
+/// Serializes `value` onto `doc`, changing only the parts of the document
+/// which differ from `value`.
+///
+/// Keys which are present in the document but not in `value` are kept,
+/// unless `value` explicitly sets them to `None`. This holds for the inline
+/// tables too, which are rewritten if keys are added to or removed from them.
+pub fn update_doc<T: Serialize + ?Sized>(doc: &mut TomlDoc, value: &T) -> Result<(), Error> {
+    if let Some(error) = doc.errors().into_iter().next() {
+        return Err(Error {
+            message: error.message().to_string(),
+        });
+    }
+    let fields = match value.serialize(ValueSerializer)? {
+        Value::Table(fields) => fields,
+        _ => {
+            return Err(ser::Error::custom(
+                "only structs and maps can be serialized as a document",
+            ))
+        }
+    };
+    let text = {
+        let text = doc.cst().get_text();
+        let model = doc.model();
+        let mut updater = Updater {
+            text: &text,
+            doc: doc.cst(),
+            edits: Vec::new(),
+            removed: Vec::new(),
+        };
+        updater.table(&model, &[], &[], &fields);
+        updater.finish()
+    };
+    *doc = TomlDoc::new(&text);
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+}
+
+impl Error {
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// A serialized value, before it is written to the document.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    /// Fields, in the order of serialization.
+    Table(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    /// `Option::None`, removes the key from the document.
+    None,
+}
+
+impl Value {
+    /// Whether the value is written as a `[table]` or as `[[tables]]` when
+    /// it is not nested in an inline value.
+    fn is_section(&self) -> bool {
+        match self {
+            Value::Table(_) => true,
+            Value::Array(values) => is_array_of_tables(values),
+            _ => false,
+        }
+    }
+}
+
+fn is_array_of_tables(values: &[Value]) -> bool {
+    !values.is_empty() && values.iter().all(|it| matches!(it, Value::Table(_)))
+}
+
+struct Updater<'t, 'a> {
+    text: &'t str,
+    doc: SyntaxNodeRef<'a>,
+    edits: Vec<(TextRange, String)>,
+    /// The entries and the sections to remove. Their removals are made in
+    /// `finish`, where the adjacent ones are merged.
+    removed: Vec<SyntaxNodeRef<'a>>,
+}
+
+/// Where the entries of a table live.
+enum Context<'a> {
+    /// The document itself, a `[table]` or an element of `[[tables]]`.
+    Section(SyntaxNodeRef<'a>),
+    /// A table defined with dotted keys, the key is the table's one.
+    Dotted(ast::Entry<'a>, ast::Key<'a>),
+    /// A table defined only as a prefix of headers, like `a` in `[a.b]`.
+    /// Holds the first such header's section.
+    Implicit(SyntaxNodeRef<'a>),
+}
+
+impl<'a> Updater<'_, 'a> {
+    /// Updates a non-inline table, which is defined by `defs` keys (empty
+    /// for the document itself).
+    fn table(
+        &mut self,
+        map: &Map<'a>,
+        defs: &[ast::Key<'a>],
+        path: &[String],
+        fields: &[(String, Value)],
+    ) {
+        // Entries of an implicit table go into a new `[header]`, which must
+        // be created only once.
+        let mut implicit_entries = Vec::new();
+        // Removals go first, so that the insertions know what stays.
+        for (name, value) in fields {
+            if let Some(item) = map.get(name) {
+                let keys = map.get_keys(name);
+                if *value == Value::None || is_rewritten(item, &keys, value) {
+                    self.remove(&keys);
+                }
+            }
+        }
+        for (idx, (name, value)) in fields.iter().enumerate() {
+            let mut path = path.to_vec();
+            path.push(name.clone());
+            let item = match map.get(name) {
+                Some(it) => it,
+                None => {
+                    if *value != Value::None {
+                        self.insert(map, defs, &path, fields, idx, &mut implicit_entries);
+                    }
+                    continue;
+                }
+            };
+            let keys = map.get_keys(name);
+            if *value == Value::None {
+                continue;
+            }
+            if let Some(ast_value) = entry_value(&keys) {
+                self.value(item, ast_value, value);
+                continue;
+            }
+            match (item, value) {
+                (Item::Map(map), Value::Table(fields)) => self.table(map, &keys, &path, fields),
+                (Item::Array(items), Value::Array(values)) if is_array_of_tables(values) => {
+                    self.array_of_tables(items, &keys, &path, values)
+                }
+                // A new section goes in place of the old ones, the end of the
+                // parent table may be among them.
+                _ if value.is_section() && self.replace_sections(&keys, &path, value) => (),
+                _ => self.insert(map, defs, &path, fields, idx, &mut implicit_entries),
+            }
+        }
+        if !implicit_entries.is_empty() {
+            if let Context::Implicit(section) = self.context(defs) {
+                let mut text = format!("[{}]\n", render_path(path));
+                for entry in implicit_entries {
+                    text += &entry;
+                    text += "\n";
+                }
+                if !self.is_removed(section) {
+                    text += "\n";
+                }
+                self.insert_at(section.range().start(), text);
+            }
+        }
+    }
+
+    /// Updates an inline value, keeping as much of its formatting as
+    /// possible.
+    fn value(&mut self, item: &Item<'a>, ast_value: ast::Value<'a>, value: &Value) {
+        if equal(item, value) {
+            return;
+        }
+        match (item, ast_value.kind(), value) {
+            (Item::Map(map), ast::ValueKind::Dict(_), Value::Table(fields)) => {
+                // Only the values of the existing keys are updated in place,
+                // anything else rewrites the whole inline table.
+                let entries: Option<Vec<_>> = fields
+                    .iter()
+                    .map(|(name, value)| {
+                        let ast_value = entry_value(&map.get_keys(name))?;
+                        Some((map.get(name)?, ast_value, value))
+                    })
+                    .collect();
+                match entries {
+                    Some(ref entries) if entries.iter().all(|(_, _, it)| **it != Value::None) => {
+                        for &(item, ast_value, value) in entries {
+                            self.value(item, ast_value, value);
+                        }
+                    }
+                    _ => {
+                        let text = render_merged(map, fields);
+                        self.edits.push((ast_value.syntax().range(), text));
+                    }
+                }
+            }
+            (Item::Array(items), ast::ValueKind::Array(array), Value::Array(values))
+                if items.len() == values.len() =>
+            {
+                let elements = items.iter().zip(array.values()).zip(values);
+                for ((item, ast_value), value) in elements {
+                    self.value(item, ast_value, value);
+                }
+            }
+            _ => self.replace(ast_value, value),
+        }
+    }
+
+    fn array_of_tables(
+        &mut self,
+        items: &[Item<'a>],
+        keys: &[ast::Key<'a>],
+        path: &[String],
+        values: &[Value],
+    ) {
+        // Each `[[header]]` starts a new element, the keys of its subtables
+        // follow it.
+        let mut elements: Vec<Vec<ast::Key<'a>>> = Vec::new();
+        for &key in keys {
+            match elements.last_mut() {
+                Some(element) if !is_array_table_key(key) => element.push(key),
+                _ => elements.push(vec![key]),
+            }
+        }
+        for (idx, value) in values.iter().enumerate() {
+            match (items.get(idx), elements.get(idx), value) {
+                (Some(Item::Map(map)), Some(element), Value::Table(fields)) => {
+                    self.table(map, element, path, fields)
+                }
+                _ => {
+                    let end = self.sections_end(keys);
+                    let text = render_section(path, &Value::Array(vec![value.clone()]));
+                    self.insert_section(end, text);
+                }
+            }
+        }
+        for element in elements.iter().skip(values.len()) {
+            self.remove(element);
+        }
+    }
+
+    /// Inserts a new key, next to the existing keys which precede or follow
+    /// it in `fields`.
+    fn insert(
+        &mut self,
+        map: &Map<'a>,
+        defs: &[ast::Key<'a>],
+        path: &[String],
+        fields: &[(String, Value)],
+        idx: usize,
+        implicit_entries: &mut Vec<String>,
+    ) {
+        let (name, value) = &fields[idx];
+        let context = self.context(defs);
+        if value.is_section() {
+            if let Context::Section(_) | Context::Implicit(_) = context {
+                let end = self.sections_end(defs);
+                self.insert_section(end, render_section(path, value));
+                return;
+            }
+        }
+        let entry = format!("{} = {}", escaped_key(name), render_inline(value, None));
+
+        let existing = |(name, value): &(String, Value)| {
+            if *value == Value::None || value.is_section() {
+                return None;
+            }
+            entry_anchor(self.text, map, name).filter(|(it, _)| !self.is_removed(it.syntax()))
+        };
+        if let Some((anchor, prefix)) = fields[..idx].iter().rev().find_map(existing) {
+            let text = format!("\n{}{}{}", self.indent(anchor.syntax()), prefix, entry);
+            return self.insert_at(anchor.syntax().range().end(), text);
+        }
+        if let Some((anchor, prefix)) = fields[idx + 1..].iter().find_map(existing) {
+            let text = format!("{}{}\n{}", prefix, entry, self.indent(anchor.syntax()));
+            return self.insert_at(anchor.syntax().range().start(), text);
+        }
+
+        match context {
+            Context::Section(section) => self.insert_entry(section, entry),
+            Context::Dotted(anchor, key) => {
+                let first_key = anchor.keys().next().unwrap();
+                let prefix = &self.text[TextRange::from_to(
+                    first_key.syntax().range().start(),
+                    key.syntax().range().end(),
+                )];
+                let entry = format!("{}.{}", prefix, entry);
+                // The entry which defines the table may be going away.
+                if self.is_removed(anchor.syntax()) {
+                    let section = anchor.syntax().parent().unwrap();
+                    return self.insert_entry(section, entry);
+                }
+                let text = format!("\n{}{}", self.indent(anchor.syntax()), entry);
+                self.insert_at(anchor.syntax().range().end(), text);
+            }
+            Context::Implicit(_) => implicit_entries.push(entry),
+        }
+    }
+
+    /// Inserts an entry after the last entry of the section which stays.
+    fn insert_entry(&mut self, section: SyntaxNodeRef<'a>, entry: String) {
+        let last_entry = section
+            .children()
+            .filter_map(ast::Entry::cast)
+            .filter(|it| !self.is_removed(it.syntax()))
+            .last();
+        if let Some(last_entry) = last_entry {
+            let text = format!("\n{}{}", self.indent(last_entry.syntax()), entry);
+            self.insert_at(last_entry.syntax().range().end(), text);
+        } else if let Some(header) = section.children().find_map(ast::TableHeader::cast) {
+            self.insert_at(header.syntax().range().end(), format!("\n{}", entry));
+        } else {
+            let has_content = section
+                .children()
+                .any(|it| it.symbol() != WHITESPACE && !self.is_removed(it));
+            let separator = if has_content { "\n\n" } else { "\n" };
+            self.insert_at(0.into(), format!("{}{}", entry, separator));
+        }
+    }
+
+    fn context(&self, defs: &[ast::Key<'a>]) -> Context<'a> {
+        if defs.is_empty() {
+            return Context::Section(self.doc);
+        }
+        for &key in defs {
+            if let Some(header) = key.syntax().parent().and_then(ast::TableHeader::cast) {
+                if header.keys().last().map(|it| it.syntax()) == Some(key.syntax()) {
+                    return Context::Section(header.syntax().parent().unwrap());
+                }
+            }
+        }
+        let dotted = defs
+            .iter()
+            .rev()
+            .find_map(|&key| Some((ast::Entry::cast(key.syntax().parent()?)?, key)));
+        if let Some((entry, key)) = dotted {
+            return Context::Dotted(entry, key);
+        }
+        let header = defs[0].syntax().parent().unwrap();
+        Context::Implicit(header.parent().unwrap())
+    }
+
+    /// The end of the last section which contributes to the table defined
+    /// by `defs`, or the end of the document.
+    fn sections_end(&self, defs: &[ast::Key<'a>]) -> Option<TextUnit> {
+        let last_section = defs
+            .iter()
+            .rev()
+            .find_map(|key| key.syntax().parent().and_then(ast::TableHeader::cast));
+        match last_section {
+            Some(header) => Some(header.syntax().parent().unwrap().range().end()),
+            None => self
+                .doc
+                .children()
+                .filter(|it| it.symbol() != WHITESPACE)
+                .last()
+                .map(|it| it.range().end()),
+        }
+    }
+
+    /// Inserts a section at `end`, or at the start of an empty document.
+    fn insert_section(&mut self, end: Option<TextUnit>, text: String) {
+        match end {
+            Some(end) => self.insert_at(end, format!("\n\n{}", text)),
+            None => self.insert_at(0.into(), format!("{}\n", text)),
+        }
+    }
+
+    /// Removes the entries and the sections defined by `keys`.
+    fn remove(&mut self, keys: &[ast::Key<'a>]) {
+        for node in defining_nodes(keys) {
+            if !self.removed.contains(&node) {
+                self.removed.push(node);
+            }
+        }
+    }
+
+    /// Whether the node is removed, by itself or with a section.
+    fn is_removed(&self, node: SyntaxNodeRef) -> bool {
+        let range = node.range();
+        self.removed.iter().any(|it| {
+            let it = it.range();
+            it.start() <= range.start() && range.end() <= it.end()
+        })
+    }
+
+    /// Replaces the entries and the sections defined by `keys` with the
+    /// section of `value`, which goes where the first of the old sections
+    /// is. Returns `false` if there are no old sections.
+    fn replace_sections(&mut self, keys: &[ast::Key<'a>], path: &[String], value: &Value) -> bool {
+        let nodes = defining_nodes(keys);
+        let first = match nodes.iter().position(|it| it.symbol() != ENTRY) {
+            Some(it) => it,
+            None => return false,
+        };
+        for (idx, &node) in nodes.iter().enumerate() {
+            if idx == first {
+                self.edits.push((node.range(), render_section(path, value)));
+            } else if !self.removed.contains(&node) {
+                self.removed.push(node);
+            }
+        }
+        true
+    }
+
+    /// The edits which remove the nodes, one for each run of nodes with
+    /// only whitespace between them.
+    fn removals(&self) -> Vec<(TextRange, String)> {
+        let mut nodes = self.removed.clone();
+        nodes.sort_by_key(|it| it.range().start());
+        let mut runs: Vec<(SyntaxNodeRef<'a>, SyntaxNodeRef<'a>)> = Vec::new();
+        for node in nodes {
+            match runs.last_mut() {
+                // Already removed with its section.
+                Some((_, last)) if node.range().end() <= last.range().end() => (),
+                Some((_, last))
+                    if self.text[TextRange::from_to(last.range().end(), node.range().start())]
+                        .trim()
+                        .is_empty() =>
+                {
+                    *last = node
+                }
+                _ => runs.push((node, node)),
+            }
+        }
+        runs.into_iter()
+            .map(|(first, last)| self.removal(first, last))
+            .collect()
+    }
+
+    /// The edit which removes the nodes from `first` to `last`, together
+    /// with their lines.
+    fn removal(&self, first: SyntaxNodeRef<'a>, last: SyntaxNodeRef<'a>) -> (TextRange, String) {
+        let bytes = self.text.as_bytes();
+        let mut start = first.range().start().to_usize();
+        let mut end = last.range().end().to_usize();
+        while start > 0 && matches!(bytes[start - 1], b' ' | b'\t') {
+            start -= 1;
+        }
+        let mut replacement = String::new();
+        if last.symbol() == ENTRY {
+            while end < bytes.len() && matches!(bytes[end], b' ' | b'\t') {
+                end += 1;
+            }
+            // Remove the line break before the entry, so that the end of the
+            // section stays in place for insertions.
+            if self.text[..start].ends_with('\n') {
+                start -= 1;
+                if self.text[..start].ends_with('\r') {
+                    start -= 1;
+                }
+            } else {
+                if self.text[end..].starts_with('\r') {
+                    end += 1;
+                }
+                if self.text[end..].starts_with('\n') {
+                    end += 1;
+                }
+            }
+        } else {
+            while end < bytes.len() && bytes[end].is_ascii_whitespace() {
+                end += 1;
+            }
+            // For the last section, remove the blank lines before it instead.
+            if end == bytes.len() {
+                while start > 0 && bytes[start - 1].is_ascii_whitespace() {
+                    start -= 1;
+                }
+                if start > 0 && self.text.ends_with('\n') {
+                    replacement.push('\n');
+                }
+            }
+        }
+        let range = TextRange::from_to((start as u32).into(), (end as u32).into());
+        (range, replacement)
+    }
+
+    fn replace(&mut self, ast_value: ast::Value<'a>, value: &Value) {
+        let text = render_inline(value, Some(ast_value));
+        self.edits.push((ast_value.syntax().range(), text));
+    }
+
+    fn insert_at(&mut self, offset: TextUnit, text: String) {
+        self.edits
+            .push((TextRange::offset_len(offset, 0.into()), text));
+    }
+
+    /// The whitespace before the node, if the node starts a line.
+    fn indent(&self, node: SyntaxNodeRef) -> &str {
+        let start = node.range().start().to_usize();
+        let line_start = self.text[..start].rfind('\n').map_or(0, |it| it + 1);
+        let indent = &self.text[line_start..start];
+        if indent.chars().all(|c| c == ' ' || c == '\t') {
+            indent
+        } else {
+            ""
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let removals = self.removals();
+        // An insertion next to a removed node goes where the removal starts.
+        for (range, _) in self.edits.iter_mut() {
+            let inside = removals
+                .iter()
+                .find(|(it, _)| it.start() < range.start() && range.start() < it.end());
+            if let (true, Some((removal, _))) = (range.is_empty(), inside) {
+                *range = TextRange::offset_len(removal.start(), 0.into());
+            }
+        }
+        self.edits.extend(removals);
+        // Insertions go before the removals which start at the same offset,
+        // and are applied in the order they were made.
+        self.edits
+            .sort_by_key(|(range, _)| (range.start(), range.end()));
+        let mut res = String::new();
+        let mut pos = 0;
+        for (range, text) in self.edits {
+            let start = range.start().to_usize();
+            assert!(start >= pos, "conflicting changes at offset {}", start);
+            res += &self.text[pos..start];
+            res += &text;
+            pos = range.end().to_usize();
+        }
+        res += &self.text[pos..];
+        res
+    }
+}
+
+/// Whether the item is removed and inserted again, rather than updated in
+/// place, when it changes to `value`.
+fn is_rewritten(item: &Item, keys: &[ast::Key], value: &Value) -> bool {
+    if entry_value(keys).is_some() {
+        return false;
+    }
+    match (item, value) {
+        (Item::Map(_), Value::Table(_)) => false,
+        (Item::Array(_), Value::Array(values)) if is_array_of_tables(values) => false,
+        _ => !value.is_section() || defining_nodes(keys).iter().all(|it| it.symbol() == ENTRY),
+    }
+}
+
+/// The entries and the sections which the keys are in, in the document
+/// order.
+fn defining_nodes<'a>(keys: &[ast::Key<'a>]) -> Vec<SyntaxNodeRef<'a>> {
+    let mut nodes: Vec<SyntaxNodeRef<'a>> = Vec::new();
+    for key in keys {
+        let parent = match key.syntax().parent() {
+            Some(it) => it,
+            None => continue,
+        };
+        let node = match parent.symbol() {
+            TABLE_HEADER => parent.parent().unwrap(),
+            _ => parent,
+        };
+        if !nodes.contains(&node) {
+            nodes.push(node);
+        }
+    }
+    nodes
+}
+
+/// Finds the `key = value` entry which defines `name` directly in a section,
+/// together with the text of the dotted keys before `name` in it.
+fn entry_anchor<'a>(text: &str, map: &Map<'a>, name: &str) -> Option<(ast::Entry<'a>, String)> {
+    map.get_keys(name)
+        .into_iter()
+        .filter_map(|key| {
+            let entry = ast::Entry::cast(key.syntax().parent()?)?;
+            let parent = entry.syntax().parent()?;
+            if parent.symbol() == DICT {
+                return None;
+            }
+            let first_key = entry.keys().next()?;
+            let prefix = TextRange::from_to(
+                first_key.syntax().range().start(),
+                key.syntax().range().start(),
+            );
+            Some((entry, text[prefix].to_string()))
+        })
+        .last()
+}
+
+/// Whether updating the map with the fields changes nothing: the keys which
+/// are missing from the fields are kept, like in the `[tables]`.
+fn equal(item: &Item, value: &Value) -> bool {
+    match (item, value) {
+        (Item::Map(map), Value::Table(fields)) => {
+            fields
+                .iter()
+                .all(|(name, value)| match (map.get(name), value) {
+                    (None, Value::None) => true,
+                    (Some(item), value) if *value != Value::None => equal(item, value),
+                    _ => false,
+                })
+        }
+        (Item::Array(items), Value::Array(values)) => {
+            items.len() == values.len() && items.iter().zip(values).all(|(i, v)| equal(i, v))
+        }
+        (Item::Integer(a), Value::Integer(b)) => a == b,
+        (Item::Integer(a), Value::Float(b)) => *a as f64 == *b,
+        (Item::Float(a), Value::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (Item::Bool(a), Value::Bool(b)) => a == b,
+        (Item::String(a), Value::String(b)) => a == b,
+        (Item::DateTime(a), Value::String(b)) => b.parse::<TomlDateTime>().ok() == Some(*a),
+        _ => false,
+    }
+}
+
+/// Renders the map updated with the fields as an inline table. The existing
+/// keys keep their order and, if unchanged, their text, the new ones go next
+/// to the keys which precede or follow them in the fields, like in `insert`.
+fn render_merged(map: &Map, fields: &[(String, Value)]) -> String {
+    let mut names: Vec<&str> = map.iter().map(|(name, _)| name).collect();
+    names.sort_by_key(|name| {
+        map.get_keys(name)
+            .first()
+            .map(|it| it.syntax().range().start())
+    });
+    let mut entries = Vec::new();
+    for name in names {
+        let item = map.get(name).unwrap();
+        let ast_value = entry_value(&map.get_keys(name));
+        let text = match fields.iter().find(|(it, _)| it == name) {
+            Some((_, Value::None)) => continue,
+            Some((_, value)) if !equal(item, value) => match (item, value) {
+                (Item::Map(map), Value::Table(fields)) => render_merged(map, fields),
+                _ => render_inline(value, ast_value),
+            },
+            _ => match ast_value {
+                Some(it) => it.syntax().get_text(),
+                // The values of a document always fit into TOML.
+                None => item.value_text().unwrap(),
+            },
+        };
+        entries.push((name, format!("{} = {}", escaped_key(name), text)));
+    }
+    for (idx, (name, value)) in fields.iter().enumerate() {
+        if *value == Value::None || map.get(name).is_some() {
+            continue;
+        }
+        let position = |name: &String| entries.iter().position(|(it, _)| it == name);
+        let position = match fields[..idx].iter().rev().find_map(|(it, _)| position(it)) {
+            Some(it) => it + 1,
+            None => fields[idx + 1..]
+                .iter()
+                .find_map(|(it, _)| position(it))
+                .unwrap_or(entries.len()),
+        };
+        let entry = format!("{} = {}", escaped_key(name), render_inline(value, None));
+        entries.insert(position, (name, entry));
+    }
+    if entries.is_empty() {
+        "{}".to_string()
+    } else {
+        let entries: Vec<String> = entries.into_iter().map(|(_, it)| it).collect();
+        format!("{{ {} }}", entries.join(", "))
+    }
+}
+
+/// Renders a value as a `[header]` or as `[[headers]]`, with all the nested
+/// tables.
+fn render_section(path: &[String], value: &Value) -> String {
+    let mut buf = String::new();
+    match value {
+        Value::Table(fields) => render_table(path, fields, "", &mut buf),
+        Value::Array(values) => {
+            for value in values {
+                if let Value::Table(fields) = value {
+                    render_table(path, fields, "[", &mut buf);
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+    buf.trim_end().to_string()
+}
+
+fn render_table(path: &[String], fields: &[(String, Value)], bracket: &str, buf: &mut String) {
+    let header = render_path(path);
+    let close = if bracket.is_empty() { "]" } else { "]]" };
+    let is_entry = |value: &Value| *value != Value::None && !value.is_section();
+    // Like `[a]` in `[a.b]`, the header of a table without entries is
+    // implied by the headers of its subtables.
+    let is_implied = bracket.is_empty()
+        && !fields.iter().any(|(_, value)| is_entry(value))
+        && fields.iter().any(|(_, value)| value.is_section());
+    if !is_implied {
+        if !buf.is_empty() {
+            buf.push('\n');
+        }
+        *buf += &format!("{}[{}{}\n", bracket, header, close);
+    }
+    for (name, value) in fields {
+        if is_entry(value) {
+            *buf += &format!("{} = {}\n", escaped_key(name), render_inline(value, None));
+        }
+    }
+    for (name, value) in fields {
+        let mut path = path.to_vec();
+        path.push(name.clone());
+        match value {
+            Value::Table(fields) => render_table(&path, fields, "", buf),
+            Value::Array(values) if value.is_section() => {
+                for value in values {
+                    if let Value::Table(fields) = value {
+                        render_table(&path, fields, "[", buf);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn render_path(path: &[String]) -> String {
+    path.iter()
+        .map(|it| escaped_key(it))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Renders a value as an inline one. `old` is the value being replaced, its
+/// style is preserved when possible.
+fn render_inline(value: &Value, old: Option<ast::Value>) -> String {
+    let old_symbol = old
+        .and_then(|it| it.syntax().first_child())
+        .map(|it| it.symbol());
+    match value {
+        Value::Table(fields) => {
+            let entries: Vec<String> = fields
+                .iter()
+                .filter(|(_, value)| *value != Value::None)
+                .map(|(name, value)| {
+                    format!("{} = {}", escaped_key(name), render_inline(value, None))
+                })
+                .collect();
+            if entries.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", entries.join(", "))
+            }
+        }
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(|it| render_inline(it, None)).collect();
+            format!("[{}]", values.join(", "))
+        }
+        Value::Integer(value) => value.to_string(),
+        Value::Float(value) => float(*value),
+        Value::Bool(value) => value.to_string(),
+        Value::String(value) => match old_symbol {
+            Some(DATE_TIME) if value.parse::<TomlDateTime>().is_ok() => value.clone(),
+            Some(LITERAL_STRING) if is_literal(value) => format!("'{}'", value),
+            _ => basic_string(value),
+        },
+        Value::None => unreachable!(),
+    }
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeTable;
+    type SerializeStruct = SerializeTable;
+    type SerializeStructVariant = SerializeVariant<SerializeTable>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(Value::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Err(ser::Error::custom(
+                "integer is out of range of a 64-bit signed integer",
+            )),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::Array(
+            v.iter().map(|&b| Value::Integer(b.into())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Err(ser::Error::custom("unit values are not supported in TOML"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let value = value.serialize(self)?;
+        Ok(Value::Table(vec![(variant.to_string(), value)]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeArray>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeTable, Error> {
+        Ok(SerializeTable {
+            fields: Vec::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeTable, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeTable>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeArray {
+    values: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        match value.serialize(ValueSerializer)? {
+            Value::None => Err(ser::Error::custom("arrays can't contain `None`")),
+            value => {
+                self.values.push(value);
+                Ok(())
+            }
+        }
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Array(self.values))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeTable {
+    fields: Vec<(String, Value)>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeTable {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(ValueSerializer)? {
+            Value::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(ser::Error::custom("map keys must be strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        let value = value.serialize(ValueSerializer)?;
+        self.fields.push((key, value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Table(self.fields))
+    }
+}
+
+impl ser::SerializeStruct for SerializeTable {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let value = value.serialize(ValueSerializer)?;
+        self.fields.push((key.to_string(), value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Wraps the value of an enum variant into a single-key table.
+struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &str, value: Value) -> Value {
+        Value::Table(vec![(variant.to_string(), value)])
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let value = ser::SerializeSeq::end(self.inner)?;
+        Ok(Self::wrap(self.variant, value))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeTable> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let value = ser::SerializeMap::end(self.inner)?;
+        Ok(Self::wrap(self.variant, value))
+    }
+}
@@ -0,0 +1,159 @@
+//! A deserialized value together with its location in the document.
+
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops,
+};
+
+use crate::TextRange;
+
+/// A value which remembers the ranges of the key and of the value it was
+/// deserialized from.
+///
+/// Comparison and hashing look only at the value itself.
+#[derive(Clone)]
+pub struct Spanned<T> {
+    key_range: Option<TextRange>,
+    value_range: TextRange,
+    value: T,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(key_range: Option<TextRange>, value_range: TextRange, value: T) -> Spanned<T> {
+        Spanned {
+            key_range,
+            value_range,
+            value,
+        }
+    }
+
+    /// The range of the key which defines the value. The first one, if the
+    /// value is defined by several keys, like a table with subtables.
+    ///
+    /// Elements of inline arrays and the document itself have no key.
+    pub fn key_range(&self) -> Option<TextRange> {
+        self.key_range
+    }
+
+    /// The range of the value. For tables defined with a `[header]`, this is
+    /// the range of the header's key.
+    pub fn value_range(&self) -> TextRange {
+        self.value_range
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> ops::Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> ops::DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(range) = self.key_range {
+            write!(f, "{:?}: ", range)?;
+        }
+        write!(f, "{:?} ", self.value_range)?;
+        self.value.fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Spanned<T>) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: Hash> Hash for Spanned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+/// `Spanned` is deserialized as a struct with a magic name, which `de`
+/// recognizes and fills with the ranges.
+#[cfg(feature = "serde")]
+pub(crate) mod de {
+    use std::{fmt, marker::PhantomData};
+
+    use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+
+    use super::Spanned;
+    use crate::{TextRange, TextUnit};
+
+    pub(crate) const NAME: &str = "$__tom_private_Spanned";
+    pub(crate) const KEY_START: &str = "$__tom_private_key_start";
+    pub(crate) const KEY_END: &str = "$__tom_private_key_end";
+    pub(crate) const VALUE_START: &str = "$__tom_private_value_start";
+    pub(crate) const VALUE_END: &str = "$__tom_private_value_end";
+    pub(crate) const VALUE: &str = "$__tom_private_value";
+    pub(crate) const FIELDS: &[&str] = &[KEY_START, KEY_END, VALUE_START, VALUE_END, VALUE];
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for Spanned<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Spanned<T>, D::Error> {
+            deserializer.deserialize_struct(NAME, FIELDS, SpannedVisitor(PhantomData))
+        }
+    }
+
+    struct SpannedVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for SpannedVisitor<T> {
+        type Value = Spanned<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a TOML value")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Spanned<T>, A::Error> {
+            let mut key_start = None;
+            let mut key_end = None;
+            let mut value_start = None;
+            let mut value_end = None;
+            while let Some(field) = map.next_key::<String>()? {
+                match field.as_str() {
+                    KEY_START => key_start = Some(map.next_value::<u32>()?),
+                    KEY_END => key_end = Some(map.next_value::<u32>()?),
+                    VALUE_START => value_start = Some(map.next_value::<u32>()?),
+                    VALUE_END => value_end = Some(map.next_value::<u32>()?),
+                    VALUE => {
+                        let range = |start: Option<u32>, end: Option<u32>| {
+                            Some(TextRange::from_to(
+                                TextUnit::from(start?),
+                                TextUnit::from(end?),
+                            ))
+                        };
+                        let value_range = range(value_start, value_end)
+                            .ok_or_else(|| de::Error::missing_field(VALUE_START))?;
+                        let value = map.next_value()?;
+                        return Ok(Spanned::new(range(key_start, key_end), value_range, value));
+                    }
+                    _ => return Err(de::Error::unknown_field(&field, FIELDS)),
+                }
+            }
+            Err(de::Error::missing_field(VALUE))
+        }
+    }
+}
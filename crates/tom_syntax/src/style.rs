@@ -0,0 +1,324 @@
+//! Formatting conventions of a document.
+//!
+//! `Style` is inferred from an existing document and describes the
+//! whitespace which is expected between two adjacent nodes, so that the
+//! edited parts of a document look like the rest of it.
+
+use crate::{rtree::GreenNode, symbol::*, SmolStr, Symbol, SyntaxNodeRef};
+
+#[derive(Debug, Clone)]
+pub(crate) struct Style {
+    newline: &'static str,
+    /// Indentation of the entries of `[table]` sections.
+    entry_indent: String,
+    /// Blank lines before a `[table]` section.
+    blank_lines: usize,
+    before_eq: String,
+    after_eq: String,
+    /// Whitespace inside the braces of non-empty inline tables.
+    dict_padding: String,
+    /// Whitespace after the commas of inline tables.
+    dict_comma: String,
+    /// Whitespace inside the brackets of single-line arrays.
+    array_padding: String,
+    /// Whether the last element of a multi-line array has a comma.
+    trailing_comma: bool,
+}
+
+/// The node whose children are spaced.
+pub(crate) enum Parent {
+    Doc,
+    Table,
+    Entry,
+    Dict,
+    Array(ArrayLayout),
+    Other,
+}
+
+pub(crate) enum ArrayLayout {
+    SingleLine,
+    /// One element per line. `trailing_comma` is `None` if the array has no
+    /// elements to tell.
+    MultiLine {
+        indent: String,
+        closing_indent: String,
+        trailing_comma: Option<bool>,
+    },
+}
+
+impl Style {
+    pub(crate) fn infer(doc: SyntaxNodeRef) -> Style {
+        let mut newline = None;
+        let mut entry_indent = None;
+        let mut blank_lines = None;
+        let mut eq = None;
+        let mut dict_padding = None;
+        let mut dict_comma = None;
+        let mut array_padding = None;
+        let mut trailing_comma = None;
+        for node in doc.descendants() {
+            let prev_ws = node
+                .prev_sibling()
+                .filter(|it| it.symbol() == WHITESPACE)
+                .and_then(|it| it.leaf_text());
+            match node.symbol() {
+                WHITESPACE if newline.is_none() => {
+                    let text = node.leaf_text().unwrap();
+                    if text.contains("\r\n") {
+                        newline = Some("\r\n");
+                    } else if text.contains('\n') {
+                        newline = Some("\n");
+                    }
+                }
+                ENTRY => {
+                    let parent = node.parent().map(|it| it.symbol());
+                    let in_table = parent == Some(TABLE) || parent == Some(ARRAY_TABLE);
+                    if let (true, None, Some(ws)) = (in_table, &entry_indent, prev_ws) {
+                        if let Some(idx) = ws.rfind('\n') {
+                            entry_indent = Some(ws[idx + 1..].to_string());
+                        }
+                    }
+                    if eq.is_none() {
+                        eq = node
+                            .children()
+                            .find(|it| it.symbol() == EQ)
+                            .map(|it| (ws_text(it.prev_sibling()), ws_text(it.next_sibling())));
+                    }
+                }
+                TABLE | ARRAY_TABLE if blank_lines.is_none() => {
+                    let has_prev = node.prev_sibling().and_then(|it| it.prev_sibling());
+                    if let (Some(ws), Some(_)) = (prev_ws, has_prev) {
+                        blank_lines = Some(ws.matches('\n').count().saturating_sub(1));
+                    }
+                }
+                DICT => {
+                    if dict_padding.is_none() && has_child(node, ENTRY) {
+                        let first = node.first_child().unwrap();
+                        dict_padding = Some(ws_text(first.next_sibling()));
+                    }
+                    if dict_comma.is_none() {
+                        dict_comma = comma_ws(node);
+                    }
+                }
+                ARRAY => {
+                    let children: Vec<GreenNode> = node.children().map(green_leaf).collect();
+                    match array_layout(&children) {
+                        ArrayLayout::SingleLine
+                            if array_padding.is_none() && has_child(node, VALUE) =>
+                        {
+                            let first = node.first_child().unwrap();
+                            array_padding = Some(ws_text(first.next_sibling()));
+                        }
+                        ArrayLayout::MultiLine {
+                            trailing_comma: Some(it),
+                            ..
+                        } if trailing_comma.is_none() => trailing_comma = Some(it),
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+        let (before_eq, after_eq) = eq.unwrap_or_else(|| (" ".to_string(), " ".to_string()));
+        Style {
+            newline: newline.unwrap_or("\n"),
+            entry_indent: entry_indent.unwrap_or_default(),
+            blank_lines: blank_lines.unwrap_or(1),
+            before_eq,
+            after_eq,
+            dict_padding: dict_padding.unwrap_or_else(|| " ".to_string()),
+            dict_comma: dict_comma.unwrap_or_else(|| " ".to_string()),
+            array_padding: array_padding.unwrap_or_default(),
+            trailing_comma: trailing_comma.unwrap_or(true),
+        }
+    }
+
+    /// The style of the entries of an inline table, which follow the
+    /// table's own entries if it has any.
+    pub(crate) fn in_dict(&self, dict: SyntaxNodeRef) -> Style {
+        let mut style = self.clone();
+        let eq = dict
+            .children()
+            .filter(|it| it.symbol() == ENTRY)
+            .find_map(|it| it.children().find(|it| it.symbol() == EQ));
+        if let Some(eq) = eq {
+            style.before_eq = ws_text(eq.prev_sibling());
+            style.after_eq = ws_text(eq.next_sibling());
+        }
+        if let Some(ws) = comma_ws(dict) {
+            style.dict_comma = ws;
+        }
+        style
+    }
+
+    pub(crate) fn newline(&self) -> &'static str {
+        self.newline
+    }
+
+    pub(crate) fn parent(&self, symbol: Symbol, children: &[GreenNode]) -> Parent {
+        match symbol {
+            DOC => Parent::Doc,
+            TABLE | ARRAY_TABLE => Parent::Table,
+            ENTRY => Parent::Entry,
+            DICT => Parent::Dict,
+            ARRAY => Parent::Array(array_layout(children)),
+            _ => Parent::Other,
+        }
+    }
+
+    /// Whether a new last element of a multi-line array gets a comma.
+    pub(crate) fn trailing_comma(&self, layout: &ArrayLayout) -> bool {
+        match layout {
+            ArrayLayout::SingleLine => false,
+            ArrayLayout::MultiLine { trailing_comma, .. } => {
+                trailing_comma.unwrap_or(self.trailing_comma)
+            }
+        }
+    }
+
+    /// The whitespace between adjacent children of `parent`, `None` if the
+    /// style has no opinion. `right` is `None` after the last child.
+    pub(crate) fn ws_between(
+        &self,
+        parent: &Parent,
+        left: Symbol,
+        right: Option<Symbol>,
+    ) -> Option<String> {
+        let nl = self.newline;
+        let ws = match (parent, left, right) {
+            (Parent::Doc, ENTRY, Some(ENTRY)) | (Parent::Doc, _, None) => nl.to_string(),
+            (Parent::Doc, _, Some(TABLE)) | (Parent::Doc, _, Some(ARRAY_TABLE)) => {
+                nl.repeat(self.blank_lines + 1)
+            }
+            (Parent::Table, TABLE_HEADER, Some(ENTRY)) | (Parent::Table, ENTRY, Some(ENTRY)) => {
+                format!("{}{}", nl, self.entry_indent)
+            }
+            (Parent::Entry, KEY, Some(EQ)) => self.before_eq.clone(),
+            (Parent::Entry, EQ, Some(VALUE)) => self.after_eq.clone(),
+            (Parent::Dict, L_CURLY, Some(ENTRY)) | (Parent::Dict, ENTRY, Some(R_CURLY)) => {
+                self.dict_padding.clone()
+            }
+            (Parent::Dict, COMMA, Some(ENTRY)) => self.dict_comma.clone(),
+            (Parent::Dict, ENTRY, Some(COMMA)) | (Parent::Array(_), VALUE, Some(COMMA)) => {
+                String::new()
+            }
+            (Parent::Array(layout), _, Some(right)) => match layout {
+                ArrayLayout::SingleLine => match (left, right) {
+                    (L_BRACK, VALUE) | (VALUE, R_BRACK) => self.array_padding.clone(),
+                    (COMMA, VALUE) => " ".to_string(),
+                    _ => return None,
+                },
+                ArrayLayout::MultiLine {
+                    indent,
+                    closing_indent,
+                    ..
+                } => match (left, right) {
+                    (L_BRACK, VALUE) | (COMMA, VALUE) => format!("{}{}", nl, indent),
+                    (VALUE, R_BRACK) | (COMMA, R_BRACK) => format!("{}{}", nl, closing_indent),
+                    _ => return None,
+                },
+            },
+            _ => return None,
+        };
+        Some(ws)
+    }
+
+    /// Respaces a new node according to the style.
+    pub(crate) fn restyle(&self, green: &GreenNode) -> GreenNode {
+        if let Some(text) = green.leaf_text() {
+            if green.kind() == WHITESPACE && text.contains('\n') {
+                return self.whitespace(text);
+            }
+            return green.clone();
+        }
+        let children: Vec<GreenNode> = green.children().iter().map(|it| self.restyle(it)).collect();
+        let parent = self.parent(green.kind(), &children);
+        let mut res: Vec<GreenNode> = Vec::with_capacity(children.len());
+        for (idx, child) in children.iter().enumerate() {
+            if child.kind() == WHITESPACE {
+                let left = res.last().map(|it| it.kind());
+                let right = children.get(idx + 1).map(|it| it.kind());
+                if let (Some(left), Some(right)) = (left, right) {
+                    if right != WHITESPACE {
+                        if let Some(ws) = self.ws_between(&parent, left, Some(right)) {
+                            if !ws.is_empty() {
+                                res.push(self.whitespace(&ws));
+                            }
+                            continue;
+                        }
+                    }
+                }
+            } else if let Some(left) = res.last().map(|it| it.kind()) {
+                if left != WHITESPACE {
+                    match self.ws_between(&parent, left, Some(child.kind())) {
+                        Some(ws) if !ws.is_empty() => res.push(self.whitespace(&ws)),
+                        _ => (),
+                    }
+                }
+            }
+            res.push(child.clone());
+        }
+        GreenNode::new_branch(green.kind(), res.into_boxed_slice())
+    }
+
+    /// A whitespace leaf with the style's line endings.
+    pub(crate) fn whitespace(&self, text: &str) -> GreenNode {
+        let text = text.replace("\r\n", "\n").replace('\n', self.newline);
+        GreenNode::new_leaf(WHITESPACE, SmolStr::new(text))
+    }
+}
+
+fn array_layout(children: &[GreenNode]) -> ArrayLayout {
+    let is_multi_line = children
+        .iter()
+        .any(|it| it.kind() == WHITESPACE && it.leaf_text().unwrap().contains('\n'));
+    if !is_multi_line {
+        return ArrayLayout::SingleLine;
+    }
+    let indent_before = |symbol: Symbol| {
+        let idx = children.iter().position(|it| it.kind() == symbol)?;
+        let ws = children[..idx].last()?.leaf_text()?;
+        Some(ws[ws.rfind('\n')? + 1..].to_string())
+    };
+    let closing_indent = indent_before(R_BRACK).unwrap_or_default();
+    let indent = indent_before(VALUE).unwrap_or_else(|| format!("{}    ", closing_indent));
+    let last = children
+        .iter()
+        .rev()
+        .map(|it| it.kind())
+        .find(|&it| it != WHITESPACE && it != R_BRACK);
+    let trailing_comma = match last {
+        Some(COMMA) => Some(true),
+        Some(VALUE) => Some(false),
+        _ => None,
+    };
+    ArrayLayout::MultiLine {
+        indent,
+        closing_indent,
+        trailing_comma,
+    }
+}
+
+fn has_child(node: SyntaxNodeRef, symbol: Symbol) -> bool {
+    node.children().any(|it| it.symbol() == symbol)
+}
+
+/// The whitespace after the first comma of an inline table.
+fn comma_ws(dict: SyntaxNodeRef) -> Option<String> {
+    let comma = dict.children().find(|it| it.symbol() == COMMA)?;
+    Some(ws_text(comma.next_sibling()))
+}
+
+fn ws_text(node: Option<SyntaxNodeRef>) -> String {
+    node.filter(|it| it.symbol() == WHITESPACE)
+        .and_then(|it| it.leaf_text())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// A stand-in for a node which has only the kind and the text of leaves,
+/// enough to compute the layout of an array.
+fn green_leaf(node: SyntaxNodeRef) -> GreenNode {
+    let text = node.leaf_text().unwrap_or("");
+    GreenNode::new_leaf(node.symbol(), SmolStr::new(text))
+}
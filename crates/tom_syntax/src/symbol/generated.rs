@@ -30,38 +30,34 @@ pub(crate) const SYMBOLS: &[SymbolInfo] = &[
     SymbolInfo("LITERAL_STRING"),
     SymbolInfo("MULTILINE_LITERAL_STRING"),
     SymbolInfo("DATE_TIME"),
-    SymbolInfo("BARE_KEY_OR_NUMBER"),
-    SymbolInfo("BARE_KEY_OR_DATE"),
     SymbolInfo("EOF"),
 ];
 
-pub const ERROR: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(1) });
-pub const WHITESPACE: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(2) });
-pub const COMMENT: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(3) });
-pub const DOC: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(4) });
-pub const ENTRY: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(5) });
-pub const KEY: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(6) });
-pub const VALUE: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(7) });
-pub const ARRAY: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(8) });
-pub const DICT: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(9) });
-pub const TABLE_HEADER: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(10) });
-pub const TABLE: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(11) });
-pub const ARRAY_TABLE: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(12) });
-pub const EQ: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(13) });
-pub const DOT: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(14) });
-pub const COMMA: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(15) });
-pub const L_BRACK: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(16) });
-pub const R_BRACK: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(17) });
-pub const L_CURLY: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(18) });
-pub const R_CURLY: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(19) });
-pub const NUMBER: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(20) });
-pub const BOOL: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(21) });
-pub const BARE_KEY: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(22) });
-pub const BASIC_STRING: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(23) });
-pub const MULTILINE_BASIC_STRING: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(24) });
-pub const LITERAL_STRING: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(25) });
-pub const MULTILINE_LITERAL_STRING: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(26) });
-pub const DATE_TIME: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(27) });
-pub const BARE_KEY_OR_NUMBER: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(28) });
-pub const BARE_KEY_OR_DATE: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(29) });
-pub const EOF: Symbol = Symbol(unsafe { NonZeroU8::new_unchecked(30) });
+pub const ERROR: Symbol = Symbol(NonZeroU8::new(1).unwrap());
+pub const WHITESPACE: Symbol = Symbol(NonZeroU8::new(2).unwrap());
+pub const COMMENT: Symbol = Symbol(NonZeroU8::new(3).unwrap());
+pub const DOC: Symbol = Symbol(NonZeroU8::new(4).unwrap());
+pub const ENTRY: Symbol = Symbol(NonZeroU8::new(5).unwrap());
+pub const KEY: Symbol = Symbol(NonZeroU8::new(6).unwrap());
+pub const VALUE: Symbol = Symbol(NonZeroU8::new(7).unwrap());
+pub const ARRAY: Symbol = Symbol(NonZeroU8::new(8).unwrap());
+pub const DICT: Symbol = Symbol(NonZeroU8::new(9).unwrap());
+pub const TABLE_HEADER: Symbol = Symbol(NonZeroU8::new(10).unwrap());
+pub const TABLE: Symbol = Symbol(NonZeroU8::new(11).unwrap());
+pub const ARRAY_TABLE: Symbol = Symbol(NonZeroU8::new(12).unwrap());
+pub const EQ: Symbol = Symbol(NonZeroU8::new(13).unwrap());
+pub const DOT: Symbol = Symbol(NonZeroU8::new(14).unwrap());
+pub const COMMA: Symbol = Symbol(NonZeroU8::new(15).unwrap());
+pub const L_BRACK: Symbol = Symbol(NonZeroU8::new(16).unwrap());
+pub const R_BRACK: Symbol = Symbol(NonZeroU8::new(17).unwrap());
+pub const L_CURLY: Symbol = Symbol(NonZeroU8::new(18).unwrap());
+pub const R_CURLY: Symbol = Symbol(NonZeroU8::new(19).unwrap());
+pub const NUMBER: Symbol = Symbol(NonZeroU8::new(20).unwrap());
+pub const BOOL: Symbol = Symbol(NonZeroU8::new(21).unwrap());
+pub const BARE_KEY: Symbol = Symbol(NonZeroU8::new(22).unwrap());
+pub const BASIC_STRING: Symbol = Symbol(NonZeroU8::new(23).unwrap());
+pub const MULTILINE_BASIC_STRING: Symbol = Symbol(NonZeroU8::new(24).unwrap());
+pub const LITERAL_STRING: Symbol = Symbol(NonZeroU8::new(25).unwrap());
+pub const MULTILINE_LITERAL_STRING: Symbol = Symbol(NonZeroU8::new(26).unwrap());
+pub const DATE_TIME: Symbol = Symbol(NonZeroU8::new(27).unwrap());
+pub const EOF: Symbol = Symbol(NonZeroU8::new(28).unwrap());
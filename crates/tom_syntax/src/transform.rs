@@ -0,0 +1,272 @@
+//! Sorting and reordering of the parts of a document.
+//!
+//! The transforms only permute siblings, so the moved nodes keep their text
+//! exactly, and the whitespace and the commas between them stay in place.
+//! The comments right above an entry or a table belong to it, as does the
+//! comment at the end of the line of an entry, so they move along. Other
+//! comments don't move.
+
+use std::{borrow::Cow, cmp::Ordering};
+
+use crate::{
+    ast::{self, EntryOwner},
+    path::{self, Segment},
+    symbol::*,
+    datetime::{Date, Time},
+    Edit, PathError, SyntaxNodeRef, TomlDateTime,
+};
+
+impl<'a> Edit<'a> {
+    /// Sorts the entries of a table, an inline table or of the top level of
+    /// a document by their keys. With `by_groups`, blank lines and comments
+    /// which aren't attached to an entry split the entries into groups,
+    /// which are sorted separately.
+    pub fn sort_entries(&mut self, owner: impl EntryOwner<'a>, by_groups: bool) {
+        let mut groups = vec![Vec::new()];
+        for child in owner.syntax().children() {
+            if let Some(entry) = ast::Entry::cast(child) {
+                groups.last_mut().unwrap().push(entry);
+            } else if by_groups && is_group_separator(child) {
+                groups.push(Vec::new());
+            }
+        }
+        for group in groups {
+            let keys = group
+                .iter()
+                .map(|entry| entry.keys().map(ast::Key::try_name).collect::<Vec<_>>());
+            let mut sorted: Vec<_> = keys.zip(group.iter().copied()).collect();
+            sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+            self.permute(group, sorted.into_iter().map(|(_, entry)| entry));
+        }
+    }
+
+    /// Sorts the values of an array. Booleans go first, then numbers,
+    /// date-times, strings and the arrays and the inline tables, which are
+    /// compared by their text. Offset date-times go before the local ones
+    /// and are compared in UTC. The comments in the array don't move.
+    pub fn sort_values(&mut self, array: ast::Array<'a>) {
+        let values: Vec<_> = array.values().collect();
+        let mut sorted: Vec<_> = values.iter().map(|&it| (ValueKey::new(it), it)).collect();
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.permute(values, sorted.into_iter().map(|(_, value)| value));
+    }
+
+    /// Reorders the `[tables]` and the `[[array tables]]` of a document to
+    /// follow the `order` of dotted keys. A table goes where the shortest of
+    /// the keys which are a prefix of its own is, so `[dependencies.serde]`
+    /// goes with `dependencies`, and the tables with no such key go last.
+    /// Otherwise, the tables keep their relative order.
+    ///
+    /// The tables of an element of an array of tables, like `[bin.test]`
+    /// after `[[bin]]`, go where the element goes, even if their own order
+    /// is given, or they'd end up in a different element. Likewise, a table
+    /// nested in one defined with dotted keys, like `[a.b.c]` in `b.d = 1`
+    /// of `[a]`, stays after the table with the dotted keys.
+    pub fn reorder_tables(&mut self, order: &[&str]) -> Result<(), PathError> {
+        let order = order
+            .iter()
+            .map(|&key| {
+                path::parse(key)?
+                    .into_iter()
+                    .map(|segment| match segment {
+                        Segment::Key(name) => Ok(name),
+                        Segment::Index(_) => {
+                            Err(PathError::new(format!("invalid table key: `{}`", key)))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let root = self.doc.cst();
+        let tables: Vec<_> = root
+            .children()
+            .filter(|it| it.symbol() == TABLE || it.symbol() == ARRAY_TABLE)
+            .collect();
+        let paths: Vec<_> = path::table_paths(root).collect();
+        let path_of = |table: SyntaxNodeRef<'a>| {
+            paths
+                .iter()
+                .find(|(header, _)| header.syntax().parent() == Some(table))
+                .map_or(&[][..], |(_, path)| path.as_slice())
+        };
+        let mut sorted: Vec<_> = tables
+            .iter()
+            .map(|&table| {
+                let path = path_of(table);
+                // The table is ranked by the outermost element of an array
+                // of tables it's in, if any, which is itself ranked by its
+                // `[[header]]`.
+                let element = path[..path.len().saturating_sub(1)]
+                    .iter()
+                    .position(|it| matches!(it, Segment::Index(_)))
+                    .map_or(path, |idx| &path[..idx]);
+                let keys: Vec<_> = element
+                    .iter()
+                    .filter_map(|it| match it {
+                        Segment::Key(name) => Some(name.clone()),
+                        Segment::Index(_) => None,
+                    })
+                    .collect();
+                let rank = order
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, prefix)| keys.starts_with(prefix))
+                    .min_by_key(|(_, prefix)| prefix.len())
+                    .map_or(order.len(), |(idx, _)| idx);
+                (rank, table)
+            })
+            .collect();
+        sorted.sort_by_key(|&(rank, _)| rank);
+
+        // The tables which a table must follow, those defining a table it's
+        // nested in with dotted keys.
+        let dotted_tables: Vec<_> = tables
+            .iter()
+            .map(|&table| {
+                let prefix = path_of(table);
+                table
+                    .children()
+                    .filter_map(ast::Entry::cast)
+                    .filter(|entry| entry.keys().nth(1).is_some())
+                    .filter_map(|entry| entry.keys().next()?.try_name())
+                    .map(|name| {
+                        let mut path = prefix.to_vec();
+                        path.push(Segment::Key(name.into_owned()));
+                        path
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let predecessors = |table: SyntaxNodeRef<'a>| {
+            let path = path_of(table);
+            tables
+                .iter()
+                .zip(&dotted_tables)
+                .filter(move |(_, dotted)| dotted.iter().any(|it| path.starts_with(it)))
+                .map(|(&it, _)| it)
+        };
+        let mut res = Vec::new();
+        let mut pending = Vec::new();
+        for (_, table) in sorted {
+            pending.push(table);
+            while let Some(idx) = pending
+                .iter()
+                .position(|&it| predecessors(it).all(|prev| res.contains(&prev)))
+            {
+                res.push(pending.remove(idx));
+            }
+        }
+        res.extend(pending);
+        self.permute(tables, res.into_iter());
+        Ok(())
+    }
+
+    /// Puts the `new` nodes in the places of the `old` ones.
+    fn permute<N>(&mut self, old: Vec<N>, new: impl Iterator<Item = N>)
+    where
+        N: Into<SyntaxNodeRef<'a>>,
+    {
+        for (old, new) in old.into_iter().zip(new) {
+            let (old, new) = (old.into(), new.into());
+            if old != new {
+                self.replace(old, new);
+            }
+        }
+    }
+}
+
+fn is_group_separator(node: SyntaxNodeRef) -> bool {
+    match node.symbol() {
+        WHITESPACE => node.leaf_text().unwrap().matches('\n').count() >= 2,
+        COMMENT => true,
+        _ => false,
+    }
+}
+
+/// What the values of an array are sorted by.
+#[derive(Debug)]
+enum ValueKey<'a> {
+    Bool(bool),
+    Number(f64, &'a str),
+    /// The kind of the date-time and its seconds and nanoseconds, see
+    /// `date_time_key`.
+    DateTime(Option<(u8, i64, u32)>, &'a str),
+    String(Cow<'a, str>),
+    Other(String),
+}
+
+impl<'a> ValueKey<'a> {
+    fn new(value: ast::Value<'a>) -> ValueKey<'a> {
+        match value.kind() {
+            ast::ValueKind::Bool(it) => ValueKey::Bool(it.value()),
+            ast::ValueKind::Number(it) => {
+                let number = it.value().map_or(f64::NAN, |it| it.as_f64());
+                ValueKey::Number(number, it.text())
+            }
+            ast::ValueKind::DateTime(it) => {
+                ValueKey::DateTime(it.value().ok().map(date_time_key), it.text())
+            }
+            ast::ValueKind::StringLit(it) => ValueKey::String(it.value()),
+            ast::ValueKind::Array(_) | ast::ValueKind::Dict(_) => {
+                ValueKey::Other(value.syntax().get_text())
+            }
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            ValueKey::Bool(_) => 0,
+            ValueKey::Number(..) => 1,
+            ValueKey::DateTime(..) => 2,
+            ValueKey::String(_) => 3,
+            ValueKey::Other(_) => 4,
+        }
+    }
+
+    fn cmp(&self, other: &ValueKey) -> Ordering {
+        match (self, other) {
+            (ValueKey::Bool(a), ValueKey::Bool(b)) => a.cmp(b),
+            (ValueKey::Number(a, a_text), ValueKey::Number(b, b_text)) => {
+                a.total_cmp(b).then_with(|| a_text.cmp(b_text))
+            }
+            (ValueKey::DateTime(a, a_text), ValueKey::DateTime(b, b_text)) => {
+                a.cmp(b).then_with(|| a_text.cmp(b_text))
+            }
+            (ValueKey::String(a), ValueKey::String(b)) => a.cmp(b),
+            (ValueKey::Other(a), ValueKey::Other(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+/// Returns the kind of the date-time, offset ones first, and its seconds
+/// since 1970 (since midnight for local times) with the nanoseconds. The
+/// offset date-times are compared in UTC.
+fn date_time_key(value: TomlDateTime) -> (u8, i64, u32) {
+    let seconds = |time: Time| {
+        i64::from(time.hour()) * 3600 + i64::from(time.minute()) * 60 + i64::from(time.second())
+    };
+    match value {
+        TomlDateTime::OffsetDateTime(date, time, offset) => {
+            let utc = days(date) * 86400 + seconds(time) - i64::from(offset.minutes()) * 60;
+            (0, utc, time.nanosecond())
+        }
+        TomlDateTime::LocalDateTime(date, time) => {
+            (1, days(date) * 86400 + seconds(time), time.nanosecond())
+        }
+        TomlDateTime::LocalDate(date) => (2, days(date) * 86400, 0),
+        TomlDateTime::LocalTime(time) => (3, seconds(time), time.nanosecond()),
+    }
+}
+
+/// The number of days since 1970-01-01.
+fn days(date: Date) -> i64 {
+    let (month, day) = (i64::from(date.month()), i64::from(date.day()));
+    // Years start in March, so that the leap day is the last one.
+    let year = i64::from(date.year()) - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
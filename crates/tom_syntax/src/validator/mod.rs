@@ -1,6 +1,8 @@
 //! FIXME: write short doc here
 
-use crate::{SyntaxNodeRef, SyntaxError, TextRange, TomlDoc, ChunkedText, ast};
+mod redefinitions;
+
+use crate::{ErrorCode, SyntaxNodeRef, SyntaxError, TextRange, TomlDoc, ChunkedText, ast};
 
 pub(crate) fn validate(doc: &TomlDoc) -> Vec<SyntaxError> {
     let mut errors = Vec::new();
@@ -27,8 +29,19 @@ pub(crate) fn validate(doc: &TomlDoc) -> Vec<SyntaxError> {
             check_table(&mut errors, table)
         } else if let Some(table) = ast::ArrayTable::cast(node) {
             check_table(&mut errors, table)
+        } else if let Some(lit) = ast::StringLit::cast(node) {
+            lit.check(&mut errors)
+        } else if let Some(number) = ast::Number::cast(node) {
+            if let Err(error) = number.value() {
+                errors.push(error)
+            }
+        } else if let Some(date_time) = ast::DateTime::cast(node) {
+            if let Err(error) = date_time.value() {
+                errors.push(error)
+            }
         }
     }
+    redefinitions::check(doc, &mut errors);
     errors
 }
 
@@ -87,6 +100,8 @@ fn check_new_line<'a>(
         errors.push(SyntaxError {
             range,
             message: msg.into(),
+            secondary_range: None,
+            code: ErrorCode::Newline,
         });
     }
 }
@@ -0,0 +1,260 @@
+//! Checks that no key or table is defined twice.
+//!
+//! The rules are those of TOML 1.0: a key can be assigned only once, a table
+//! can be declared with a `[header]` only once, tables created by dotted keys
+//! can't be reopened with a header (and vice versa), and inline tables and
+//! arrays are frozen once defined.
+
+use std::collections::BTreeMap;
+
+use crate::{ast, ErrorCode, SyntaxError, SyntaxNodeRef, TomlDoc};
+
+pub(super) fn check(doc: &TomlDoc, errors: &mut Vec<SyntaxError>) {
+    let mut defs = Defs {
+        defs: vec![Def::new(DefKind::Table, None, String::new())],
+        errors,
+    };
+    let root = 0;
+    let mut section = root;
+    for child in doc.cst().children() {
+        if let Some(entry) = ast::Entry::cast(child) {
+            defs.entry(section, entry);
+        } else if let Some(table) = ast::Table::cast(child) {
+            section = defs.header(root, table.header(), false);
+            for entry in table.entries() {
+                defs.entry(section, entry);
+            }
+        } else if let Some(table) = ast::ArrayTable::cast(child) {
+            section = defs.header(root, table.header(), true);
+            for entry in table.entries() {
+                defs.entry(section, entry);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DefKind {
+    /// A table, created by a `[header]`.
+    Table,
+    /// A table, created as a prefix of a `[header]`, like `a` in `[a.b]`.
+    ImplicitTable,
+    /// A table, created by a dotted key, like `a` in `a.b = 1`.
+    DottedTable,
+    InlineTable,
+    ArrayOfTables,
+    Value,
+}
+
+struct Def<'a> {
+    kind: DefKind,
+    key: Option<ast::Key<'a>>,
+    /// Dotted path from the root, for error messages.
+    path: String,
+    children: BTreeMap<String, usize>,
+    /// Elements of an array of tables.
+    elements: Vec<usize>,
+}
+
+impl<'a> Def<'a> {
+    fn new(kind: DefKind, key: Option<ast::Key<'a>>, path: String) -> Def<'a> {
+        Def {
+            kind,
+            key,
+            path,
+            children: BTreeMap::new(),
+            elements: Vec::new(),
+        }
+    }
+}
+
+struct Defs<'a, 'e> {
+    defs: Vec<Def<'a>>,
+    errors: &'e mut Vec<SyntaxError>,
+}
+
+impl<'a> Defs<'a, '_> {
+    /// Processes a `[header]` or a `[[header]]`, returning the table for the
+    /// entries of the section. On error, a fresh detached table is returned,
+    /// so that the entries are still checked against each other.
+    fn header(&mut self, root: usize, header: ast::TableHeader<'a>, array: bool) -> usize {
+        let keys = match valid_keys(header.keys()) {
+            Some(it) => it,
+            None => return self.detached(String::new()),
+        };
+        let (last, prefix) = match keys.split_last() {
+            Some(it) => it,
+            None => return self.detached(String::new()),
+        };
+        let mut path = String::new();
+        let mut curr = root;
+        for &key in prefix {
+            push_segment(&mut path, key);
+            curr = match self.child(curr, key) {
+                None => self.insert(curr, key, DefKind::ImplicitTable),
+                Some(def) => match self.defs[def].kind {
+                    DefKind::Table | DefKind::ImplicitTable | DefKind::DottedTable => def,
+                    DefKind::ArrayOfTables => *self.defs[def].elements.last().unwrap(),
+                    DefKind::InlineTable | DefKind::Value => {
+                        self.error(key, def, defined_as_message(self.defs[def].kind, &path));
+                        return self.detached(path);
+                    }
+                },
+            }
+        }
+        push_segment(&mut path, *last);
+        let def = match self.child(curr, *last) {
+            None if array => {
+                let def = self.insert(curr, *last, DefKind::ArrayOfTables);
+                return self.push_element(def);
+            }
+            None => return self.insert(curr, *last, DefKind::Table),
+            Some(def) => def,
+        };
+        let message = match (self.defs[def].kind, array) {
+            (DefKind::ImplicitTable, false) => {
+                self.defs[def].kind = DefKind::Table;
+                self.defs[def].key = Some(*last);
+                return def;
+            }
+            (DefKind::ArrayOfTables, true) => return self.push_element(def),
+            (DefKind::Table, false) => format!("table `{}` is already defined", path),
+            (kind, _) => defined_as_message(kind, &path),
+        };
+        self.error(*last, def, message);
+        self.detached(path)
+    }
+
+    fn entry(&mut self, table: usize, entry: ast::Entry<'a>) {
+        let keys = match valid_keys(entry.keys()) {
+            Some(it) => it,
+            None => return,
+        };
+        let (last, prefix) = match keys.split_last() {
+            Some(it) => it,
+            None => return,
+        };
+        let mut path = self.defs[table].path.clone();
+        let mut curr = table;
+        for &key in prefix {
+            push_segment(&mut path, key);
+            curr = match self.child(curr, key) {
+                None => self.insert(curr, key, DefKind::DottedTable),
+                Some(def) => match self.defs[def].kind {
+                    DefKind::DottedTable => def,
+                    DefKind::Table | DefKind::ImplicitTable => {
+                        let message = format!("cannot extend table `{}` with dotted keys", path);
+                        self.error(key, def, message);
+                        return;
+                    }
+                    kind => {
+                        self.error(key, def, defined_as_message(kind, &path));
+                        return;
+                    }
+                },
+            }
+        }
+        push_segment(&mut path, *last);
+        if let Some(def) = self.child(curr, *last) {
+            self.error(*last, def, format!("duplicate key `{}`", path));
+            return;
+        }
+        let value = entry.value();
+        match value_child(value).and_then(ast::Dict::cast) {
+            Some(dict) => {
+                let def = self.insert(curr, *last, DefKind::InlineTable);
+                self.dict(def, dict);
+            }
+            None => {
+                self.insert(curr, *last, DefKind::Value);
+                self.value(value, &path);
+            }
+        }
+    }
+
+    /// Checks the inline tables nested in a value.
+    fn value(&mut self, value: ast::Value<'a>, path: &str) {
+        let child = match value_child(value) {
+            Some(it) => it,
+            None => return,
+        };
+        if let Some(dict) = ast::Dict::cast(child) {
+            let def = self.detached(path.to_string());
+            self.dict(def, dict);
+        } else if let Some(array) = ast::Array::cast(child) {
+            for value in array.values() {
+                self.value(value, path);
+            }
+        }
+    }
+
+    fn dict(&mut self, table: usize, dict: ast::Dict<'a>) {
+        for entry in dict.entries() {
+            self.entry(table, entry);
+        }
+    }
+
+    fn child(&self, parent: usize, key: ast::Key<'a>) -> Option<usize> {
+        self.defs[parent].children.get(key.name().as_ref()).copied()
+    }
+
+    fn insert(&mut self, parent: usize, key: ast::Key<'a>, kind: DefKind) -> usize {
+        let idx = self.defs.len();
+        let mut path = self.defs[parent].path.clone();
+        push_segment(&mut path, key);
+        self.defs.push(Def::new(kind, Some(key), path));
+        self.defs[parent]
+            .children
+            .insert(key.name().into_owned(), idx);
+        idx
+    }
+
+    fn push_element(&mut self, array: usize) -> usize {
+        let idx = self.detached(self.defs[array].path.clone());
+        self.defs[array].elements.push(idx);
+        idx
+    }
+
+    fn detached(&mut self, path: String) -> usize {
+        let idx = self.defs.len();
+        self.defs.push(Def::new(DefKind::Table, None, path));
+        idx
+    }
+
+    fn error(&mut self, key: ast::Key<'a>, original: usize, message: String) {
+        self.errors.push(SyntaxError {
+            range: key.syntax().range(),
+            message,
+            secondary_range: self.defs[original].key.map(|it| it.syntax().range()),
+            code: ErrorCode::Redefinition,
+        })
+    }
+}
+
+fn defined_as_message(kind: DefKind, path: &str) -> String {
+    match kind {
+        DefKind::Table | DefKind::ImplicitTable => {
+            format!("`{}` is already defined as a table", path)
+        }
+        DefKind::DottedTable => format!("table `{}` is already defined by dotted keys", path),
+        DefKind::InlineTable => format!("cannot extend inline table `{}`", path),
+        DefKind::ArrayOfTables => format!("`{}` is already defined as an array of tables", path),
+        DefKind::Value => format!("`{}` is already defined as a value", path),
+    }
+}
+
+/// Returns `None` if some of the keys have syntax errors.
+fn valid_keys<'a>(keys: impl Iterator<Item = ast::Key<'a>>) -> Option<Vec<ast::Key<'a>>> {
+    keys.map(|key| key.try_name().map(|_| key)).collect()
+}
+
+fn value_child(value: ast::Value) -> Option<SyntaxNodeRef> {
+    value.syntax().first_child()
+}
+
+fn push_segment(path: &mut String, key: ast::Key) {
+    if !path.is_empty() {
+        path.push('.');
+    }
+    path.push_str(&key.name());
+}
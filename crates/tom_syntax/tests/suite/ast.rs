@@ -1,4 +1,7 @@
-use tom_syntax::ast;
+use tom_syntax::{
+    ast::{self, NumberValue},
+    ErrorCode, TextRange, TomlDateTime, TomlDoc,
+};
 use crate::{find, toml};
 
 #[test]
@@ -16,37 +19,179 @@ fn string_escaping_trivial() {
 fn bool_value() {
     let doc = toml(r"foo = true");
     let lit: ast::Bool = find(&doc);
-    assert_eq!(lit.value(), true);
+    assert!(lit.value());
 
     let doc = toml(r"foo = false");
     let lit: ast::Bool = find(&doc);
-    assert_eq!(lit.value(), false);
+    assert!(!lit.value());
 }
 
 #[test]
-#[ignore]
 fn int_value() {
     let doc = toml(r"foo = 92");
     let lit: ast::Number = find(&doc);
-    assert_eq!(lit.value(), 92);
+    assert_eq!(lit.value().unwrap(), NumberValue::Integer(92));
+}
+
+#[test]
+fn number_values() {
+    fn check(text: &str, expected: NumberValue) {
+        let doc = toml(&format!("foo = {}", text));
+        let lit: ast::Number = find(&doc);
+        assert_eq!(lit.value().unwrap(), expected, "{}", text);
+    }
+
+    check("1_000", NumberValue::Integer(1000));
+    check("-17", NumberValue::Integer(-17));
+    check("+0", NumberValue::Integer(0));
+    check("-9_223_372_036_854_775_808", NumberValue::Integer(i64::MIN));
+    check("8.5", NumberValue::Float(8.5));
+    check("-0.01", NumberValue::Float(-0.01));
+    check("5e+22", NumberValue::Float(5e22));
+    check("6.626e-34", NumberValue::Float(6.626e-34));
+    check(
+        "224_617.445_991_228",
+        NumberValue::Float(224_617.445_991_228),
+    );
+    check("1e06", NumberValue::Float(1e6));
+    check("0xDEAD_beef", NumberValue::Integer(0xdead_beef));
+    check("0o755", NumberValue::Integer(0o755));
+    check("0b1101_0110", NumberValue::Integer(0b1101_0110));
+    check("+inf", NumberValue::Float(f64::INFINITY));
+    check("-inf", NumberValue::Float(f64::NEG_INFINITY));
+
+    let doc = toml("foo = -nan");
+    let lit: ast::Number = find(&doc);
+    assert!(lit.value().unwrap().as_f64().is_nan());
+}
+
+#[test]
+fn number_errors() {
+    fn check(text: &str, message: &str) {
+        let doc = TomlDoc::new(&format!("foo = {}", text));
+        let lit: ast::Number = find(&doc);
+        let err = lit.value().unwrap_err();
+        assert_eq!(err.message(), message, "{}", text);
+        assert_eq!(err.range(), lit.syntax().range());
+        assert!(!doc.errors().is_empty());
+    }
+
+    check(
+        "9223372036854775808",
+        "integer is out of range of a 64-bit signed integer",
+    );
+    check(
+        "-9_223_372_036_854_775_809",
+        "integer is out of range of a 64-bit signed integer",
+    );
 }
 
 #[test]
-#[ignore]
 fn date_time_value() {
+    use tom_syntax::datetime::{Date, Offset, Time};
+
     let doc = toml(r"foo = 1979-05-27T00:32:00.999999-07:00");
     let lit: ast::DateTime = find(&doc);
-    assert_eq!(lit.value(), ::std::time::UNIX_EPOCH);
+    assert_eq!(
+        lit.value().unwrap(),
+        TomlDateTime::OffsetDateTime(
+            Date::new(1979, 5, 27).unwrap(),
+            Time::new(0, 32, 0, 999_999_000).unwrap(),
+            Offset::Minutes(-7 * 60),
+        )
+    );
+
+    let date = Date::new(2000, 2, 29).unwrap();
+    assert_eq!((date.year(), date.month(), date.day()), (2000, 2, 29));
+    let time = Time::new(23, 59, 60, 5).unwrap();
+    assert_eq!(
+        (time.hour(), time.minute(), time.second(), time.nanosecond()),
+        (23, 59, 60, 5)
+    );
+
+    fn check(text: &str, expected: &str) {
+        let value: TomlDateTime = text.parse().unwrap();
+        assert_eq!(value.to_string(), expected, "{}", text);
+    }
+
+    check("1979-05-27T07:32:00Z", "1979-05-27T07:32:00Z");
+    check("1979-05-27t07:32:00z", "1979-05-27T07:32:00Z");
+    check("1979-05-27 07:32:00+05:30", "1979-05-27T07:32:00+05:30");
+    check("1979-05-27T07:32:00.5", "1979-05-27T07:32:00.5");
+    check("1979-05-27", "1979-05-27");
+    check("00:32:00.123456789", "00:32:00.123456789");
+    check("2000-02-29", "2000-02-29");
+}
+
+#[test]
+fn date_time_errors() {
+    fn check(text: &str, message: &str) {
+        let doc = TomlDoc::new(&format!("foo = {}", text));
+        let lit: ast::DateTime = find(&doc);
+        let err = lit.value().unwrap_err();
+        assert_eq!(err.message(), message, "{}", text);
+        assert_eq!(err.range(), lit.syntax().range());
+        assert!(!doc.errors().is_empty());
+    }
+
+    check("1979-13-27", "month must be in 1..=12");
+    check("1979-02-30", "day is out of range for month");
+    check("1900-02-29", "day is out of range for month");
+    check("1979-05-27T24:00:00", "hour must be in 0..=23");
+    check("1979-05-27T07:60:00Z", "minute must be in 0..=59");
 }
 
 #[test]
-#[ignore]
 fn string_escaping_escape_sequences() {
     let doc = toml(r#"foo = "hello\nworld""#);
     let lit: ast::StringLit = find(&doc);
     assert_eq!(lit.value(), "hello\nworld");
 }
 
+#[test]
+fn string_escaping_unicode() {
+    let doc = toml(r#"foo = "\u00e9\U0001F600""#);
+    let lit: ast::StringLit = find(&doc);
+    assert_eq!(lit.value(), "\u{e9}\u{1F600}");
+}
+
+#[test]
+fn string_multiline() {
+    let doc = toml("foo = \"\"\"\nhello\\\n    world\\t\"\"\"");
+    let lit: ast::StringLit = find(&doc);
+    assert_eq!(lit.value(), "helloworld\t");
+
+    let doc = toml("foo = \"\"\"\nhello \\\n\u{a0}\u{2003}world\"\"\"");
+    let lit: ast::StringLit = find(&doc);
+    assert_eq!(lit.value(), "hello \u{a0}\u{2003}world");
+
+    let doc = toml("foo = '''\r\nhello\\n'''");
+    let lit: ast::StringLit = find(&doc);
+    assert_eq!(lit.value(), "hello\\n");
+
+    let doc = toml("foo = 'C:\\Users'");
+    let lit: ast::StringLit = find(&doc);
+    assert_eq!(lit.value(), "C:\\Users");
+}
+
+#[test]
+fn string_invalid_escape() {
+    let doc = TomlDoc::new(r#"foo = "a\qb\uD800""#);
+    let lit: ast::StringLit = find(&doc);
+    assert_eq!(lit.value(), "a\u{FFFD}b\u{FFFD}");
+    let err = lit.try_value().unwrap_err();
+    assert_eq!(err.message(), "invalid escape sequence");
+    assert_eq!(err.range(), TextRange::from_to(8.into(), 10.into()));
+    let errors: Vec<_> = doc.errors().iter().map(|it| it.range()).collect();
+    assert_eq!(
+        errors,
+        vec![
+            TextRange::from_to(8.into(), 10.into()),
+            TextRange::from_to(11.into(), 17.into())
+        ]
+    );
+}
+
 #[test]
 fn key_name() {
     let doc = toml(r#"foo = false"#);
@@ -63,9 +208,107 @@ fn key_name() {
 }
 
 #[test]
-#[ignore]
 fn key_name_with_escape() {
     let doc = toml(r#""hello\nworld" = false"#);
     let key: ast::Key = find(&doc);
     assert_eq!(key.name(), "hello\nworld");
 }
+
+#[test]
+fn string_unterminated() {
+    let doc = TomlDoc::new("foo = \"hé\n");
+    let lit: ast::StringLit = find(&doc);
+    assert_eq!(lit.value(), "hé");
+    assert_eq!(doc.errors()[0].message(), "unterminated string");
+    assert_eq!(doc.errors()[0].range(), lit.syntax().range());
+}
+
+#[test]
+fn comments() {
+    let doc = toml(
+        "# about a\n# more\na = 1 # one\n\n# detached\n\nb = 2\n# about t\n[t] # header\n  # about c\n  c = 3\n",
+    );
+    let contents = |it: &mut dyn Iterator<Item = ast::Comment>| -> Vec<String> {
+        it.map(|it| it.content().to_string()).collect()
+    };
+    let entries: Vec<ast::Entry> = doc.ast().entries().collect();
+    assert_eq!(contents(&mut entries[0].leading_comments()), ["about a", "more"]);
+    assert_eq!(entries[0].trailing_comment().unwrap().text(), "# one");
+    assert!(entries[1].leading_comments().next().is_none());
+    assert!(entries[1].trailing_comment().is_none());
+
+    let table = doc.ast().tables().next().unwrap();
+    assert_eq!(contents(&mut table.doc_comments()), ["about t"]);
+    let c = table.entries().next().unwrap();
+    assert_eq!(contents(&mut c.leading_comments()), ["about c"]);
+
+    let doc = toml("[a]\nx = 1\n\n#about b\n[[b]]\n");
+    let b = doc.ast().array_tables().next().unwrap();
+    assert_eq!(contents(&mut b.doc_comments()), ["about b"]);
+}
+
+#[test]
+fn error_codes() {
+    fn check(text: &str, code: ErrorCode) {
+        let doc = TomlDoc::new(text);
+        let codes: Vec<ErrorCode> = doc.errors().iter().map(|it| it.code()).collect();
+        assert_eq!(codes, [code], "{}", text);
+    }
+
+    check("a = ", ErrorCode::Syntax);
+    check("a = 'b", ErrorCode::Syntax);
+    check("a = \"\\q\"", ErrorCode::InvalidString);
+    check("a = 0x", ErrorCode::InvalidNumber);
+    check("a = 1979-02-30", ErrorCode::InvalidDateTime);
+    check("[a] b = 1", ErrorCode::Newline);
+    check("a = 1\na = 2", ErrorCode::Redefinition);
+    assert_eq!(ErrorCode::InvalidDateTime.as_str(), "invalid-date-time");
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn chrono_date_time_conversions() {
+    use std::convert::TryFrom;
+
+    let date = chrono::NaiveDate::from_ymd_opt(1979, 5, 27).unwrap();
+    let value = TomlDateTime::try_from(date).unwrap();
+    assert_eq!(value.to_string(), "1979-05-27");
+    assert_eq!(chrono::NaiveDate::try_from(value).unwrap(), date);
+
+    for year in &[-1, 10_000, 75_535] {
+        let date = chrono::NaiveDate::from_ymd_opt(*year, 1, 1).unwrap();
+        let err = TomlDateTime::try_from(date).unwrap_err();
+        assert_eq!(err.to_string(), "year must be in 0..=9999");
+        let err = TomlDateTime::try_from(date.and_hms_opt(0, 0, 0).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "year must be in 0..=9999");
+    }
+
+    let offset = chrono::FixedOffset::east_opt(5 * 3600 + 30 * 60 + 1).unwrap();
+    let date_time = date
+        .and_hms_opt(7, 32, 0)
+        .unwrap()
+        .and_local_timezone(offset)
+        .unwrap();
+    let err = TomlDateTime::try_from(date_time).unwrap_err();
+    assert_eq!(err.to_string(), "offset must be a whole number of minutes");
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn time_date_time_conversions() {
+    use std::convert::TryFrom;
+
+    let date = time::Date::from_calendar_date(1979, time::Month::May, 27).unwrap();
+    let value = TomlDateTime::try_from(date).unwrap();
+    assert_eq!(value.to_string(), "1979-05-27");
+    assert_eq!(time::Date::try_from(value).unwrap(), date);
+
+    let date = time::Date::from_calendar_date(-1, time::Month::January, 1).unwrap();
+    let err = TomlDateTime::try_from(date).unwrap_err();
+    assert_eq!(err.to_string(), "year must be in 0..=9999");
+
+    let offset = time::UtcOffset::from_hms(5, 30, 1).unwrap();
+    let date_time = time::PrimitiveDateTime::new(date, time::Time::MIDNIGHT).assume_offset(offset);
+    let err = TomlDateTime::try_from(date_time).unwrap_err();
+    assert_eq!(err.to_string(), "offset must be a whole number of minutes");
+}
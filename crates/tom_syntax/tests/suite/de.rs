@@ -0,0 +1,306 @@
+use serde::Deserialize;
+use tom_syntax::{de, Spanned, TextRange};
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Manifest {
+    package: Package,
+    #[serde(default)]
+    bin: Vec<Target>,
+    dependencies: std::collections::BTreeMap<String, Dependency>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Package {
+    name: String,
+    version: String,
+    edition: Option<Edition>,
+    authors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+enum Edition {
+    #[serde(rename = "2015")]
+    E2015,
+    #[serde(rename = "2018")]
+    E2018,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Target {
+    name: String,
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+enum Dependency {
+    Version(String),
+    Detailed { version: String, optional: bool },
+}
+
+#[test]
+fn deserialize_manifest() {
+    let manifest: Manifest = de::from_str(
+        r#"
+[package]
+name = "tom"
+version = "0.1.0"
+edition = "2018"
+authors = ["Aleksey Kladov <aleksey.kladov@gmail.com>"]
+
+[[bin]]
+name = "tom"
+
+[[bin]]
+name = "tomfmt"
+path = "src/fmt.rs"
+
+[dependencies]
+serde = { version = "1.0", optional = true }
+log.version = "0.4"
+log.optional = false
+failure = "0.1"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        manifest,
+        Manifest {
+            package: Package {
+                name: "tom".to_string(),
+                version: "0.1.0".to_string(),
+                edition: Some(Edition::E2018),
+                authors: vec!["Aleksey Kladov <aleksey.kladov@gmail.com>".to_string()],
+            },
+            bin: vec![
+                Target {
+                    name: "tom".to_string(),
+                    path: None
+                },
+                Target {
+                    name: "tomfmt".to_string(),
+                    path: Some("src/fmt.rs".to_string())
+                },
+            ],
+            dependencies: vec![
+                (
+                    "failure".to_string(),
+                    Dependency::Version("0.1".to_string())
+                ),
+                (
+                    "log".to_string(),
+                    Dependency::Detailed {
+                        version: "0.4".to_string(),
+                        optional: false
+                    }
+                ),
+                (
+                    "serde".to_string(),
+                    Dependency::Detailed {
+                        version: "1.0".to_string(),
+                        optional: true
+                    }
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    );
+}
+
+#[test]
+fn deserialize_scalars() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Scalars {
+        int: u8,
+        float: f32,
+        bool: bool,
+        date: String,
+        nested: Vec<Vec<i64>>,
+        tuple: (i32, String),
+        unit: Unit,
+        newtype: Newtype,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Unit {
+        A,
+        B,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Newtype(String);
+
+    let scalars: Scalars = de::from_str(
+        r#"
+int = 0x2a
+float = 1.5
+bool = true
+date = 1979-05-27 07:32:00Z
+nested = [[1, 2], [3]]
+tuple = [1, "one"]
+unit = "B"
+newtype = "new"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        scalars,
+        Scalars {
+            int: 42,
+            float: 1.5,
+            bool: true,
+            date: "1979-05-27T07:32:00Z".to_string(),
+            nested: vec![vec![1, 2], vec![3]],
+            tuple: (1, "one".to_string()),
+            unit: Unit::B,
+            newtype: Newtype("new".to_string()),
+        }
+    );
+}
+
+#[test]
+fn deserialize_enum_variants() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Shape {
+        Circle { radius: f64 },
+        Square(f64),
+        Point,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Shapes {
+        shapes: Vec<Shape>,
+    }
+
+    let shapes: Shapes =
+        de::from_str(r#"shapes = [{ Circle = { radius = 1.0 } }, { Square = 2.0 }, "Point"]"#)
+            .unwrap();
+    assert_eq!(
+        shapes.shapes,
+        vec![
+            Shape::Circle { radius: 1.0 },
+            Shape::Square(2.0),
+            Shape::Point
+        ]
+    );
+}
+
+#[test]
+fn error_ranges() {
+    fn check<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+        text: &str,
+        message: &str,
+        range: &str,
+    ) {
+        let err = de::from_str::<T>(text).unwrap_err();
+        assert_eq!(err.message(), message, "{}", text);
+        let actual = err.range().map(|range| &text[range]);
+        assert_eq!(actual, Some(range), "{}", text);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        #[allow(unused)]
+        port: u16,
+        #[allow(unused)]
+        #[serde(default)]
+        hosts: Vec<String>,
+    }
+
+    check::<Config>(
+        "port = 70000",
+        "invalid value: integer `70000`, expected u16",
+        "70000",
+    );
+    check::<Config>(
+        "port = 80\nhosts = ['a', 2]",
+        "invalid type: integer `2`, expected a string",
+        "2",
+    );
+    check::<Config>("hosts = []", "missing field `port`", "hosts = []");
+    check::<Config>(
+        "port = 80\n\n[hosts]\nname = 'a'",
+        "invalid type: map, expected a sequence",
+        "hosts",
+    );
+    check::<Config>("port = 'x", "unterminated string", "'x");
+
+    #[derive(Debug, Deserialize)]
+    struct Bins {
+        #[allow(unused)]
+        bin: Vec<Config>,
+    }
+
+    check::<Bins>(
+        "[[bin]]\nport = 1\n\n[[bin]]\nhosts = []",
+        "missing field `port`",
+        "bin",
+    );
+    let err = de::from_str::<Bins>("[[bin]]\nport = 1\n\n[[bin]]\nhosts = []").unwrap_err();
+    assert_eq!(err.range(), Some(TextRange::from_to(20.into(), 23.into())));
+}
+
+#[test]
+fn spanned() {
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        server: Spanned<Server>,
+        limits: Limits,
+        backend: Vec<Spanned<Backend>>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Server {
+        port: Spanned<u32>,
+        hosts: Spanned<Vec<Spanned<String>>>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Limits {
+        memory: Spanned<u64>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Backend {
+        addr: Spanned<String>,
+    }
+
+    let text = r#"
+limits = { memory = 1024 }
+
+[server]
+port = 70000
+hosts = ["a", "b"]
+
+[[backend]]
+addr = "127.0.0.1"
+
+[[backend]]
+addr = "localhost"
+"#;
+    let config: Config = de::from_str(text).unwrap();
+    let slice = |range: Option<TextRange>| range.map(|range| &text[range]);
+
+    assert_eq!(*config.server.port, 70000);
+    assert_eq!(slice(config.server.key_range()), Some("server"));
+    assert_eq!(slice(config.server.port.key_range()), Some("port"));
+    assert_eq!(slice(Some(config.server.port.value_range())), Some("70000"));
+
+    let hosts = &config.server.hosts;
+    assert_eq!(slice(Some(hosts.value_range())), Some(r#"["a", "b"]"#));
+    assert_eq!(slice(hosts[1].key_range()), None);
+    assert_eq!(slice(Some(hosts[1].value_range())), Some(r#""b""#));
+
+    let memory = &config.limits.memory;
+    assert_eq!(slice(memory.key_range()), Some("memory"));
+    assert_eq!(slice(Some(memory.value_range())), Some("1024"));
+
+    let backend = &config.backend[1];
+    assert_eq!(backend.key_range(), Some(backend.value_range()));
+    assert!(backend.value_range().start() > config.backend[0].value_range().start());
+    assert_eq!(
+        slice(Some(backend.addr.value_range())),
+        Some(r#""localhost""#)
+    );
+}
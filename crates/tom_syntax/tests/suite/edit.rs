@@ -1,21 +1,20 @@
-use tom_syntax::{ast, Position::*, TomlDoc, TextRange};
-use util::assert_eq_text;
-use ::{check_edit, toml};
+use tom_syntax::{ast, Edit, Position::*, TextRange, TomlDoc};
+use crate::util::assert_eq_text;
+use crate::{check_edit, check_panics, toml};
 
 #[test]
-fn debug_dump_works_during_edit() {
-    let mut toml = toml(
+fn debug_dump_after_edit() {
+    let doc = toml(
         r#"
 foo = "1.0.0"
 "#,
     );
-    toml.start_edit();
-    let v = toml.new_value(92);
-    let root = toml.cst();
-    toml.insert(v, AppendTo(root));
+    let mut edit = doc.start_edit();
+    let v = edit.new_value(92);
+    edit.insert(v.borrowed(), AppendTo(doc.cst()));
+    let doc = edit.finish_edit_no_reparse();
     assert_eq_text(
         r#"
-*modified*
 DOC@[0; 18)
   WHITESPACE@[0; 1)
   ENTRY@[1; 14)
@@ -29,8 +28,9 @@ DOC@[0; 18)
   WHITESPACE@[14; 15)
   VALUE@[15; 17)
     NUMBER@[15; 17) "92"
-  WHITESPACE@[17; 18)"#.trim(),
-        toml.debug().trim(),
+  WHITESPACE@[17; 18)"#
+            .trim(),
+        doc.debug().trim(),
     );
 }
 
@@ -45,7 +45,7 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 "#,
-        |doc, quux, (foo, _, _)| doc.insert(quux, After(foo.into())),
+        |edit, quux, (foo, _, _)| edit.insert(quux, After(foo.into())),
     );
 
     do_check(
@@ -57,7 +57,7 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 "#,
-        |doc, quux, (_, bar, _)| doc.insert(quux, Before(bar.into())),
+        |edit, quux, (_, bar, _)| edit.insert(quux, Before(bar.into())),
     );
 
     do_check(
@@ -68,9 +68,9 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 "#,
-        |doc, quux, _| {
-            let root = doc.ast();
-            doc.insert(quux, PrependTo(root.into()))
+        |edit, quux, (foo, _, _)| {
+            let root = foo.syntax().parent().unwrap();
+            edit.insert(quux, PrependTo(root))
         },
     );
 
@@ -83,15 +83,19 @@ bar = "1.0.0"
 baz = "1.0.0"
 quux = "92"
 "#,
-        |doc, quux, _| {
-            let root = doc.ast();
-            doc.insert(quux, AppendTo(root.into()));
+        |edit, quux, (foo, _, _)| {
+            let root = foo.syntax().parent().unwrap();
+            edit.insert(quux, AppendTo(root))
         },
     );
 
     fn do_check(
         after: &str,
-        f: impl Fn(&mut TomlDoc, ast::Entry, (ast::Entry, ast::Entry, ast::Entry)),
+        f: impl for<'a> Fn(
+            &mut Edit<'a>,
+            ast::Entry,
+            (ast::Entry<'a>, ast::Entry<'a>, ast::Entry<'a>),
+        ),
     ) {
         let before = r#"
 foo = "1.0.0"
@@ -100,17 +104,17 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 "#;
-        check_edit(before, after, |doc| {
-            let quux = doc.new_entry_from_text("quux = \"92\"");
-            let entries: Vec<_> = doc.ast().entries(doc).collect();
-            f(doc, quux, (entries[0], entries[1], entries[2]));
+        check_edit(before, after, |doc, edit| {
+            let quux = edit.new_entry_from_text("quux = \"92\"");
+            let quux = ast::Entry::cast(quux.borrowed()).unwrap();
+            let entries: Vec<_> = doc.ast().entries().collect();
+            f(edit, quux, (entries[0], entries[1], entries[2]));
         })
     }
 }
 
 #[test]
 fn basic_insertion_no_ws() {
-    covers!("basic_insertion_no_ws");
     do_check(
         r#"
 foo = "1.0.0"quux = "92"
@@ -119,7 +123,7 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 "#,
-        |doc, quux, (foo, _, _)| doc.insert(quux, After(foo.into())),
+        |edit, quux, (foo, _, _)| edit.insert(quux, After(foo.into())),
     );
 
     do_check(
@@ -130,7 +134,7 @@ quux = "92"bar = "1.0.0"
 
 baz = "1.0.0"
 "#,
-        |doc, quux, (_, bar, _)| doc.insert(quux, Before(bar.into())),
+        |edit, quux, (_, bar, _)| edit.insert(quux, Before(bar.into())),
     );
 
     do_check(
@@ -141,9 +145,9 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 "#,
-        |doc, quux, _| {
-            let root = doc.cst();
-            doc.insert(quux, PrependTo(root));
+        |edit, quux, (foo, _, _)| {
+            let root = foo.syntax().parent().unwrap();
+            edit.insert(quux, PrependTo(root))
         },
     );
 
@@ -155,15 +159,19 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 quux = "92""#,
-        |doc, quux, _| {
-            let root = doc.cst();
-            doc.insert(quux, AppendTo(root));
+        |edit, quux, (foo, _, _)| {
+            let root = foo.syntax().parent().unwrap();
+            edit.insert(quux, AppendTo(root))
         },
     );
 
     fn do_check(
         after: &str,
-        f: impl Fn(&mut TomlDoc, ast::Entry, (ast::Entry, ast::Entry, ast::Entry)),
+        f: impl for<'a> Fn(
+            &mut Edit<'a>,
+            ast::Entry,
+            (ast::Entry<'a>, ast::Entry<'a>, ast::Entry<'a>),
+        ),
     ) {
         let before = r#"
 foo = "1.0.0"
@@ -172,24 +180,40 @@ bar = "1.0.0"
 
 baz = "1.0.0"
 "#;
-        check_edit(before, after, |doc| {
-            doc.set_smart_ws(false);
-            let quux = doc.new_entry_from_text("quux = \"92\"");
-            let entries: Vec<_> = doc.ast().entries(doc).collect();
-            f(doc, quux, (entries[0], entries[1], entries[2]));
+        check_edit(before, after, |doc, edit| {
+            edit.set_smart_ws(false);
+            let quux = edit.new_entry_from_text("quux = \"92\"");
+            let quux = ast::Entry::cast(quux.borrowed()).unwrap();
+            let entries: Vec<_> = doc.ast().entries().collect();
+            f(edit, quux, (entries[0], entries[1], entries[2]));
         })
     }
 }
 
+#[test]
+fn insertion_order() {
+    check_edit("a = 1\nb = 2\n", "a = 1xy\nb = 2\nzw", |doc, edit| {
+        let root = doc.cst();
+        let a = doc.ast().entries().next().unwrap();
+        let y = edit.new_whitespace("y");
+        let x = edit.new_whitespace("x");
+        edit.insert(y.borrowed(), After(a.into()));
+        edit.insert(x.borrowed(), After(a.into()));
+        let z = edit.new_whitespace("z");
+        let w = edit.new_whitespace("w");
+        edit.insert(z.borrowed(), AppendTo(root));
+        edit.insert(w.borrowed(), AppendTo(root));
+    });
+}
+
 #[test]
 fn basic_deletion() {
     check_edit(
         "foo = true\nbar = false\nbaz = false\n",
         "foo = true\nbaz = false\n",
-        |doc| {
-            let ast = doc.ast();
-            let bar = ast.entries(doc).nth(1).unwrap();
-            doc.detach(bar);
+        |doc, edit| {
+            let bar = doc.ast().entries().nth(1).unwrap();
+            edit.detach(bar);
         },
     )
 }
@@ -199,11 +223,28 @@ fn basic_deletion_no_ws() {
     check_edit(
         "foo = true\nbar = false\nbaz = false\n",
         "foo = true\n\nbaz = false\n",
-        |doc| {
-            doc.set_smart_ws(false);
-            let ast = doc.ast();
-            let bar = ast.entries(doc).nth(1).unwrap();
-            doc.detach(bar);
+        |doc, edit| {
+            edit.set_smart_ws(false);
+            let bar = doc.ast().entries().nth(1).unwrap();
+            edit.detach(bar);
+        },
+    )
+}
+
+#[test]
+fn basic_replacement() {
+    check_edit(
+        "foo = true\nbar = { x = 1 }\n",
+        "foo = true\nbar = { x = 92 }\n",
+        |doc, edit| {
+            let one = doc
+                .cst()
+                .descendants()
+                .filter_map(ast::Value::cast)
+                .nth(2)
+                .unwrap();
+            let new = edit.new_value(92);
+            edit.replace(one, new.borrowed());
         },
     )
 }
@@ -213,48 +254,205 @@ fn test_swap() {
     check_edit(
         "foo = true\nbar = false\n",
         "bar = false\nfoo = true\n",
-        |doc| {
-            let ast = doc.ast();
-            let foo = ast.entries(doc).nth(0).unwrap();
-            let bar = ast.entries(doc).nth(1).unwrap();
-            doc.swap(foo, bar);
+        |doc, edit| {
+            let foo = doc.ast().entries().next().unwrap();
+            let bar = doc.ast().entries().nth(1).unwrap();
+            edit.swap(foo, bar);
         },
     );
 }
 
 #[test]
-fn recalculates_ranges() {
-    let mut doc = TomlDoc::new("foo = 92");
-    let foo = doc.ast().entries(&doc).next().unwrap();
-    assert_eq!(
-        foo.cst().range(&doc),
-        TextRange::from_to(0.into(), 8.into())
-    );
-
-    doc.start_edit();
-    let bar = doc.new_entry_from_text("bar = 62");
-    let root = doc.cst();
-    doc.insert(bar, PrependTo(root));
-    doc.finish_edit_no_reparse();
-    assert_eq!(
-        foo.cst().range(&doc),
-        TextRange::from_to(9.into(), 17.into())
-    );
+fn original_doc_is_unchanged() {
+    let doc = TomlDoc::new("foo = 92");
+    let mut edit = doc.start_edit();
+    let bar = edit.new_entry_from_text("bar = 62");
+    let ws = edit.new_whitespace("\n");
+    edit.insert(ws.borrowed(), PrependTo(doc.cst()));
+    edit.insert(bar.borrowed(), PrependTo(doc.cst()));
+    let new_doc = edit.finish_edit_no_reparse();
+
+    assert_eq!(doc.cst().get_text(), "foo = 92");
+    let foo = doc.ast().entries().next().unwrap();
+    assert_eq!(foo.syntax().range(), TextRange::from_to(0.into(), 8.into()));
+
+    assert_eq!(new_doc.cst().get_text(), "bar = 62\nfoo = 92");
+    let foo = new_doc.ast().entries().nth(1).unwrap();
+    assert_eq!(foo.syntax().range(), TextRange::from_to(9.into(), 17.into()));
 }
 
 #[test]
 fn edit_full_reparse() {
-    let mut doc = TomlDoc::new("");
+    let doc = TomlDoc::new("");
     assert!(doc.errors().is_empty());
 
-    doc.start_edit();
-    let val = doc.new_value_from_text("92");
-    let root = doc.cst();
-    doc.insert(val, PrependTo(root));
-    doc.finish_edit_no_reparse();
+    let mut edit = doc.start_edit();
+    let val = edit.new_value_from_text("92");
+    edit.insert(val.borrowed(), PrependTo(doc.cst()));
+    let doc = edit.finish_edit_no_reparse();
     assert!(doc.errors().is_empty());
 
-    doc.start_edit();
-    doc.finish_edit_full_reparse();
+    let doc = doc.start_edit().finish_edit_full_reparse();
     assert!(!doc.errors().is_empty());
 }
+
+#[test]
+fn edit_keeps_syntax_errors() {
+    let errors = |doc: &TomlDoc| {
+        let errors = doc.errors();
+        errors
+            .iter()
+            .map(|it| (it.range(), it.message().to_string()))
+            .collect::<Vec<_>>()
+    };
+    let doc = TomlDoc::new("a = 1\n[b\nc = 3\n");
+    let expected = errors(&doc);
+    assert!(!expected.is_empty());
+
+    let mut edit = doc.start_edit();
+    let val = edit.new_value(2);
+    edit.replace(doc.ast().entries().next().unwrap().value(), val.borrowed());
+    let doc = edit.finish_edit_no_reparse();
+    assert_eq!(doc.cst().get_text(), "a = 2\n[b\nc = 3\n");
+    assert_eq!(errors(&doc), expected);
+}
+
+#[test]
+fn cant_detach_root() {
+    check_panics(|| {
+        let doc = TomlDoc::new("foo = 92");
+        let mut edit = doc.start_edit();
+        edit.detach(doc.cst());
+    });
+}
+
+#[test]
+fn smart_ws_follows_document_style() {
+    check_set(
+        "[a]\n  x=1\n\n\n[b]\n  y=2\n",
+        &[("a.z", 3), ("c.w", 4)],
+        "[a]\n  x=1\n  z=3\n\n\n[b]\n  y=2\n\n\n[c]\n  w=4\n",
+    );
+    check_set("a = {x = 1}\n", &[("a.y", 2)], "a = {x = 1, y = 2}\n");
+    check_set("a = [{x=1}]\n", &[("a[0].y", 1)], "a = [{x=1, y=1}]\n");
+    check_set("a = {x=1,y=2}\n", &[("a.z", 3)], "a = {x=1,y=2,z=3}\n");
+    check_set(
+        "b = {}\na = {x=1}\n",
+        &[("b.y", 1)],
+        "b = {y = 1}\na = {x=1}\n",
+    );
+    check_set(
+        "a = 1\r\n\r\n[b]\r\nc = 2\r\n",
+        &[("d", 3), ("e.f", 4)],
+        "a = 1\r\nd = 3\r\n\r\n[b]\r\nc = 2\r\n\r\n[e]\r\nf = 4\r\n",
+    );
+}
+
+#[test]
+fn smart_ws_in_arrays() {
+    let append = |before: &str, after: &str| {
+        check_edit(before, after, |doc, edit| {
+            let array = doc
+                .cst()
+                .descendants()
+                .find_map(ast::Array::cast)
+                .unwrap();
+            let last = array.values().last().unwrap();
+            let value = edit.new_value(3);
+            edit.insert(value.borrowed(), After(last.into()));
+        });
+    };
+    append("a = [1, 2]\n", "a = [1, 2, 3]\n");
+    append("a = [ 1, 2 ]\n", "a = [ 1, 2, 3 ]\n");
+    append("a = [\n  1,\n  2,\n]\n", "a = [\n  1,\n  2,\n  3,\n]\n");
+    append("a = [\n    1,\n    2\n]\n", "a = [\n    1,\n    2,\n    3\n]\n");
+
+    check_edit("a = [1]\n", "a = [0, 1]\n", |doc, edit| {
+        let first = doc.cst().descendants().find_map(ast::Array::cast).unwrap();
+        let first = first.values().next().unwrap();
+        let value = edit.new_value(0);
+        edit.insert(value.borrowed(), Before(first.into()));
+    });
+}
+
+#[test]
+fn smart_ws_on_removal() {
+    let check = |before: &str, path: &str, after: &str| {
+        let mut doc = TomlDoc::new(before);
+        assert!(doc.remove(path).unwrap());
+        assert_eq_text(after, &doc.cst().get_text());
+    };
+    check("a = [1, 2, 3]\n", "a[1]", "a = [1, 3]\n");
+    check("a = [1, 2, 3]\n", "a[0]", "a = [2, 3]\n");
+    check("a = [1, 2, 3]\n", "a[2]", "a = [1, 2]\n");
+    check("a = [ 1 ]\n", "a[0]", "a = []\n");
+    check("a = [\n  1,\n  2,\n]\n", "a[1]", "a = [\n  1,\n]\n");
+    check("a = { x = 1, y = 2 }\n", "a.y", "a = { x = 1 }\n");
+    check("a = 1\n\n[b]\nc = 2\n", "b", "a = 1\n");
+    check("[a]\nx = 1\n\n[b]\ny = 2\n", "a", "[b]\ny = 2\n");
+}
+
+#[test]
+fn comments_move_with_their_node() {
+    check_edit(
+        "# a\na = 1 # one\nb = 2\n",
+        "b = 2\n",
+        |doc, edit| {
+            let a = doc.ast().entries().next().unwrap();
+            edit.detach(a);
+        },
+    );
+    check_edit(
+        "a = 1\n\n# t\n[t]\nx = 1\n",
+        "a = 1\n",
+        |doc, edit| {
+            let t = doc.ast().tables().next().unwrap();
+            edit.detach(t);
+        },
+    );
+}
+
+#[test]
+fn set_comments() {
+    check_edit(
+        "a = 1\n[t]\n  # old\n  b = 2 # old\n",
+        "# about a\n#\n# more\na = 1 # one\n[t]\n  # new\n  b = 2\n",
+        |doc, edit| {
+            let a = doc.ast().entries().next().unwrap();
+            edit.set_leading_comments(a, &["about a", "", "more"]);
+            edit.set_trailing_comment(a, Some("one"));
+            let t = doc.ast().tables().next().unwrap();
+            let b = t.entries().next().unwrap();
+            edit.set_leading_comments(b, &["new"]);
+            edit.set_trailing_comment(b, None);
+        },
+    );
+    check_edit(
+        "# old\n[t]\na = 1 # x\n",
+        "[t]\na = 1 # y\n",
+        |doc, edit| {
+            let t = doc.ast().tables().next().unwrap();
+            edit.set_leading_comments(t, &[]);
+            let a = t.entries().next().unwrap();
+            edit.set_trailing_comment(a, Some("y"));
+        },
+    );
+}
+
+#[test]
+fn no_comments_in_inline_tables() {
+    check_panics(|| {
+        let doc = TomlDoc::new("a = { b = 1 }");
+        let b = doc.cst().descendants().filter_map(ast::Entry::cast).nth(1).unwrap();
+        let mut edit = doc.start_edit();
+        edit.set_trailing_comment(b, Some("b"));
+    });
+}
+
+fn check_set(before: &str, values: &[(&str, i64)], after: &str) {
+    let mut doc = TomlDoc::new(before);
+    for &(path, value) in values {
+        doc.set(path, value).unwrap();
+    }
+    assert_eq_text(after, &doc.cst().get_text());
+}
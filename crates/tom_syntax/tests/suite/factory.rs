@@ -1,19 +1,101 @@
-use std::iter;
-use util::assert_eq_text;
-use tom_syntax::{CstNode, TomlDoc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    iter,
+};
+use crate::{check_panics, util::assert_eq_text};
+use tom_syntax::{Edit, SyntaxNode, TomlDateTime, TomlDoc};
 
 #[test]
 fn create_key_with_space() {
-    check(|doc| doc.new_key("foo bar"), "\"foo bar\"");
+    check(|edit| edit.new_key("foo bar"), "\"foo bar\"");
+}
+
+#[test]
+fn create_escaped_value() {
+    check(|edit| edit.new_value("a \u{1}\ttab"), r#""a \u0001\ttab""#);
+}
+
+#[test]
+fn create_string_values() {
+    fn check_string(value: &str, expected: &str) {
+        check(|edit| edit.new_value(value), expected);
+        let doc = TomlDoc::new(&format!("a = {}", expected));
+        assert!(doc.errors().is_empty(), "{}", expected);
+        assert_eq!(doc.get("a").unwrap().as_str(), Some(value));
+    }
+
+    check_string("hello", r#""hello""#);
+    check_string("it's", r#""it's""#);
+    check_string(r#"C:\"quoted""#, r#"'C:\"quoted"'"#);
+    check_string(r#"it's "quoted""#, r#""it's \"quoted\"""#);
+    check_string("two\nlines", "\"\"\"\ntwo\nlines\"\"\"");
+    check_string("a \"quoted\"\n", "'''\na \"quoted\"\n'''");
+    check_string("it's\n\"quoted\"", "'''\nit's\n\"quoted\"'''");
+    check_string("\"\"\"\nx'", "\"\"\"\n\\\"\\\"\"\nx'\"\"\"");
+    check_string("cr\r\nlf", "\"\"\"\ncr\\r\nlf\"\"\"");
+}
+
+#[test]
+fn create_scalar_values() {
+    check(|edit| edit.new_value(1.5), "1.5");
+    check(|edit| edit.new_value(1e100), "1e100");
+    check(|edit| edit.new_value(f64::NEG_INFINITY), "-inf");
+    check(|edit| edit.new_value(f64::NAN), "nan");
+    check(|edit| edit.new_value(-8i8), "-8");
+    check(|edit| edit.new_value(u32::MAX), "4294967295");
+    check(|edit| edit.new_value(i64::MIN), "-9223372036854775808");
+    check(|edit| edit.new_value(92u64), "92");
+    check(|edit| edit.new_value(92usize), "92");
+    check(|edit| edit.new_value(i128::from(i64::MAX)), "9223372036854775807");
+    check(|edit| edit.new_value(92u128), "92");
+    check(|edit| edit.new_value(String::from("s")), r#""s""#);
+    let date: TomlDateTime = "1979-05-27T07:32:00Z".parse().unwrap();
+    check(|edit| edit.new_value(date), "1979-05-27T07:32:00Z");
+}
+
+#[test]
+fn create_out_of_range_integers() {
+    let doc = TomlDoc::new("");
+    check_panics(|| {
+        doc.start_edit().new_value(u64::MAX);
+    });
+    check_panics(|| {
+        doc.start_edit().new_value(vec![i128::MIN]);
+    });
+}
+
+#[test]
+fn create_compound_values() {
+    check(|edit| edit.new_value(vec![1, 2]), "[1, 2]");
+    check(|edit| edit.new_value(Vec::<bool>::new()), "[]");
+    let mut map = BTreeMap::new();
+    map.insert("b c", vec!["x"]);
+    map.insert("a", vec![]);
+    check(|edit| edit.new_value(map), r#"{ a = [], "b c" = ["x"] }"#);
+    let mut map = HashMap::new();
+    map.insert("z".to_string(), 1);
+    map.insert("y".to_string(), 2);
+    check(|edit| edit.new_value(map), "{ y = 2, z = 1 }");
+    check(|edit| edit.new_value(BTreeMap::<&str, i64>::new()), "{}");
+}
+
+#[test]
+fn create_value_from_item() {
+    let doc = TomlDoc::new("[a]\nb = [1, 'x', { c = 1.5 }]\nd = 1979-05-27\n");
+    let a = doc.get("a").unwrap();
+    check(
+        |edit| edit.new_value(a),
+        r#"{ b = [1, "x", { c = 1.5 }], d = 1979-05-27 }"#,
+    );
 }
 
 #[test]
 fn create_entry() {
     check(
-        |doc| {
-            let key = doc.new_key("foo");
-            let val = doc.new_value("1.0");
-            doc.new_entry(iter::once(key), val)
+        |edit| {
+            let key = edit.new_key("foo");
+            let val = edit.new_value("1.0");
+            edit.new_entry(iter::once(key.borrowed()), val.borrowed())
         },
         r#"foo = "1.0""#,
     );
@@ -22,10 +104,10 @@ fn create_entry() {
 #[test]
 fn create_dict() {
     check(
-        |doc| {
-            let a = doc.new_entry_from_text("foo = \"1.0\"");
-            let b = doc.new_entry_from_text("bar = \"0.0.1\"");
-            doc.new_value_dict(vec![a, b].into_iter())
+        |edit| {
+            let a = edit.new_entry_from_text("foo = \"1.0\"");
+            let b = edit.new_entry_from_text("bar = \"0.0.1\"");
+            edit.new_value_dict(vec![a.borrowed(), b.borrowed()])
         },
         r#"{ foo = "1.0", bar = "0.0.1" }"#,
     );
@@ -34,10 +116,10 @@ fn create_dict() {
 #[test]
 fn create_array() {
     check(
-        |doc| {
-            let a = doc.new_value(92);
-            let b = doc.new_value(62);
-            doc.new_value_array(vec![a, b].into_iter())
+        |edit| {
+            let a = edit.new_value(92);
+            let b = edit.new_value(62);
+            edit.new_value_array(vec![a.borrowed(), b.borrowed()])
         },
         "[ 92, 62 ]",
     );
@@ -46,14 +128,17 @@ fn create_array() {
 #[test]
 fn create_table() {
     check(
-        |doc| {
-            let a = doc.new_entry_from_text("foo = \"1.0\"");
-            let b = doc.new_entry_from_text("bar = \"0.0.1\"");
-            let mut keys = Vec::new();
-            for key in "target x86_64.json dependencies".split_whitespace() {
-                keys.push(doc.new_key(key));
-            }
-            doc.new_table(keys.into_iter(), vec![a, b].into_iter())
+        |edit| {
+            let a = edit.new_entry_from_text("foo = \"1.0\"");
+            let b = edit.new_entry_from_text("bar = \"0.0.1\"");
+            let keys: Vec<_> = "target x86_64.json dependencies"
+                .split_whitespace()
+                .map(|key| edit.new_key(key))
+                .collect();
+            edit.new_table(
+                keys.iter().map(|it| it.borrowed()),
+                vec![a.borrowed(), b.borrowed()],
+            )
         },
         r#"[target."x86_64.json".dependencies]
 foo = "1.0"
@@ -64,21 +149,20 @@ bar = "0.0.1""#,
 #[test]
 fn create_array_table() {
     check(
-        |doc| {
-            let a = doc.new_entry_from_text("name = \"foo\"");
-            let key = doc.new_key("bin");
-
-            doc.new_array_table(iter::once(key), iter::once(a)).cst()
+        |edit| {
+            let a = edit.new_entry_from_text("name = \"foo\"");
+            let key = edit.new_key("bin");
+            edit.new_array_table(iter::once(key.borrowed()), iter::once(a.borrowed()))
         },
         r#"[[bin]]
 name = "foo""#,
     );
 }
 
-fn check<F: FnOnce(&mut TomlDoc) -> R, R: Into<CstNode>>(f: F, expected: &str) {
-    let mut doc = TomlDoc::new("");
-    doc.start_edit();
-    let cst = f(&mut doc).into();
-    let actual = cst.get_text(&doc);
+fn check(f: impl FnOnce(&Edit) -> SyntaxNode, expected: &str) {
+    let doc = TomlDoc::new("");
+    let edit = doc.start_edit();
+    let node = f(&edit);
+    let actual = node.borrowed().get_text();
     assert_eq_text(expected, &actual)
 }
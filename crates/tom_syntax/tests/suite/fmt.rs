@@ -0,0 +1,203 @@
+use std::fs;
+use tom_syntax::{
+    fmt::{format, format_range, FmtOptions},
+    symbol::*,
+    Item, TextRange, TextUnit, TomlDoc,
+};
+use crate::{
+    dir::to_json,
+    util::{assert_eq_text, test_data_dir},
+};
+
+#[test]
+fn spacing() {
+    check(
+        "a=1\nb . c={x=1 ,y=[ 1,2 ]}\n\"d\"   =   'e'\n",
+        "a = 1\nb.c = { x = 1, y = [1, 2] }\n\"d\" = 'e'\n",
+    );
+    check("[ a . \"b\" ]\n[[ c ]]\n", "[a.\"b\"]\n\n[[c]]\n");
+    check("e = {}\nf = [ ]\n", "e = {}\nf = []\n");
+}
+
+#[test]
+fn blank_lines() {
+    check(
+        "\n\na = 1\n\n\n\nb = 2\nc = 3\n\n\n",
+        "a = 1\n\nb = 2\nc = 3\n",
+    );
+    check(
+        "a = 1\n[t]\n\n\nb = 2\n\n\n\n[[u]]\n[[u]]",
+        "a = 1\n\n[t]\n\nb = 2\n\n[[u]]\n\n[[u]]\n",
+    );
+    check("# leading\n[t]\n", "# leading\n[t]\n");
+    check("a = 1\n# about t\n[t]\n", "a = 1\n\n# about t\n[t]\n");
+    check("", "");
+    check("\n\n", "");
+}
+
+#[test]
+fn arrays() {
+    check("a = [\n  1,\n  2\n]\n", "a = [1, 2]\n");
+    check(
+        r#"long = ["aaaaaaaaaaaaaaaaaaaa", "bbbbbbbbbbbbbbbbbbbbbbbb", "cccccccccccccccccccccccc"]
+"#,
+        r#"long = [
+    "aaaaaaaaaaaaaaaaaaaa",
+    "bbbbbbbbbbbbbbbbbbbbbbbb",
+    "cccccccccccccccccccccccc",
+]
+"#,
+    );
+    check(
+        r#"nested = [[1, 2], ["aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"]]
+"#,
+        r#"nested = [
+    [1, 2],
+    [
+        "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+    ],
+]
+"#,
+    );
+    check(
+        "a = [ # first\n1 # one\n,\n\n\n# two\n2,3]\n",
+        "a = [  # first\n    1, # one\n\n    # two\n    2,\n    3,\n]\n",
+    );
+    check(
+        "a = ['''\nmulti\n''', 2]\n",
+        "a = [\n    '''\nmulti\n''',\n    2,\n]\n",
+    );
+    check("a = [1, 2,]\n", "a = [1, 2]\n");
+
+    let text = "a = { b = [1111111, 2222222, 3333333, 4444444] }\n";
+    let options = FmtOptions {
+        max_width: 20,
+        ..FmtOptions::default()
+    };
+    // Inline tables can't be split into lines.
+    check_with(options, text, text);
+}
+
+#[test]
+fn comments() {
+    check(
+        "a = 1 # one\nbbb = 2    # two\n\nc = 3 #three   \n  # own\n[t]# t\n",
+        "a = 1   # one\nbbb = 2 # two\n\nc = 3 #three\n\n# own\n[t] # t\n",
+    );
+    check(
+        "a = [ # x\n  1, # y\n] # z\n",
+        "a = [  # x\n    1, # y\n]      # z\n",
+    );
+}
+
+#[test]
+fn options() {
+    let text = "a = 1 # one\nbbb = 2 # two\n[t]\nc = [1, 2, 3] # c\n[u]\n";
+    check_with(
+        FmtOptions {
+            indent_entries: true,
+            indent_width: 2,
+            blank_lines: 2,
+            align_comments: false,
+            ..FmtOptions::default()
+        },
+        text,
+        "a = 1 # one\nbbb = 2 # two\n\n\n[t]\n  c = [1, 2, 3] # c\n\n\n[u]\n",
+    );
+    check_with(
+        FmtOptions {
+            max_width: 10,
+            trailing_comma: false,
+            blank_lines: 0,
+            ..FmtOptions::default()
+        },
+        text,
+        "a = 1   # one\nbbb = 2 # two\n[t]\nc = [\n    1,\n    2,\n    3\n] # c\n[u]\n",
+    );
+}
+
+#[test]
+fn keeps_line_endings() {
+    check(
+        "a=1\r\nb = '''\r\nx\r\n'''\r\n",
+        "a = 1\r\nb = '''\r\nx\r\n'''\r\n",
+    );
+}
+
+#[test]
+fn syntax_errors() {
+    let doc = TomlDoc::new("a = \n[t]b = 1");
+    assert_eq!(format(&doc, &FmtOptions::default()), None);
+}
+
+#[test]
+fn range() {
+    let text = "a=1\nb=[1,\n2]\n[t]\nc=3 # c\nd=4";
+    let check = |from: &str, to: &str, expected: &str| {
+        let start = text.find(from).unwrap();
+        let end = text.find(to).unwrap() + to.len();
+        let range = TextRange::from_to(TextUnit::from_usize(start), TextUnit::from_usize(end));
+        let doc = TomlDoc::new(text);
+        let actual = match format_range(&doc, range, &FmtOptions::default()) {
+            Some(edit) => edit.apply(text),
+            None => text.to_string(),
+        };
+        assert_eq_text(expected, &actual);
+    };
+    check("a", "a", "a = 1\nb=[1,\n2]\n[t]\nc=3 # c\nd=4");
+    check("1,", "2", "a=1\nb = [1, 2]\n[t]\nc=3 # c\nd=4");
+    check("a", "t]", "a = 1\nb = [1, 2]\n\n[t]\nc=3 # c\nd=4");
+    check("# c", "d", "a=1\nb=[1,\n2]\n[t]\nc=3 # c\nd = 4");
+    check("[t", "[t", text);
+}
+
+#[test]
+fn formatting_keeps_the_document() {
+    let dir = test_data_dir().join("ok");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|it| it != "toml") {
+            continue;
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        let doc = TomlDoc::new(&text);
+        let formatted = format(&doc, &FmtOptions::default()).unwrap();
+        let formatted_doc = TomlDoc::new(&formatted);
+        assert!(
+            formatted_doc.errors().is_empty(),
+            "{}:\n{}",
+            path.display(),
+            formatted
+        );
+        assert_eq!(tokens(&doc), tokens(&formatted_doc), "{}", path.display());
+        assert_eq!(json(&doc), json(&formatted_doc), "{}", path.display());
+        let again = format(&formatted_doc, &FmtOptions::default()).unwrap();
+        assert_eq_text(&formatted, &again);
+    }
+}
+
+fn check(before: &str, after: &str) {
+    check_with(FmtOptions::default(), before, after)
+}
+
+fn check_with(options: FmtOptions, before: &str, after: &str) {
+    let actual = format(&TomlDoc::new(before), &options).unwrap();
+    assert_eq_text(after, &actual);
+    let again = format(&TomlDoc::new(&actual), &options).unwrap();
+    assert_eq_text(after, &again);
+}
+
+/// The leaves which the formatter doesn't change.
+fn tokens(doc: &TomlDoc) -> Vec<String> {
+    doc.cst()
+        .descendants()
+        .filter(|it| it.symbol() != WHITESPACE && it.symbol() != COMMA)
+        .filter_map(|it| it.leaf_text())
+        .map(|it| it.trim_end().to_string())
+        .collect()
+}
+
+fn json(doc: &TomlDoc) -> serde_json::Value {
+    to_json(Item::Map(doc.model()))
+}
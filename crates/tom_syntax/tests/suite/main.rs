@@ -1,13 +1,21 @@
 mod ast;
+#[cfg(feature = "serde")]
+mod de;
 mod dir;
-// mod edit;
-// mod factory;
-// mod model;
+mod edit;
+mod factory;
+mod fmt;
+mod model;
+mod path;
+mod reparse;
+mod transform;
+#[cfg(feature = "serde")]
+mod ser;
 mod util;
 
 use std::{panic, fs, sync::Mutex, time::Instant};
-use util::{test_data_dir};
-use tom_syntax::{AstNode, TomlDoc};
+use util::{assert_eq_text, test_data_dir};
+use tom_syntax::{AstNode, Edit, TomlDoc};
 use lazy_static::lazy_static;
 
 #[test]
@@ -20,6 +28,42 @@ fn simple_bench() {
     println!("{} μs", time);
 }
 
+#[test]
+fn lockfile_bench() {
+    let text = lockfile();
+    let start = Instant::now();
+    let _doc = TomlDoc::new(&text);
+    let time = start.elapsed().as_micros();
+    println!("{} μs", time);
+}
+
+#[test]
+fn large_lockfile() {
+    toml(&lockfile());
+}
+
+/// A lockfile of about 480 KiB, which has no errors.
+fn lockfile() -> String {
+    let mut text = String::new();
+    for i in 0..2000 {
+        text += &format!(
+            r#"[[package]]
+name = "package-{i}"
+version = "0.{i}.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "80094f509cf8b5ae86a4966a39b3ff66cd7e2a3e594accec3743ff3fabeab5b2"
+dependencies = [
+ "libc 0.2.{i}",
+ "winapi",
+]
+
+"#,
+            i = i
+        );
+    }
+    text
+}
+
 fn toml(text: &str) -> TomlDoc {
     let doc = TomlDoc::new(text);
     assert!(
@@ -35,26 +79,14 @@ fn find<'a, A: AstNode<'a>>(doc: &'a TomlDoc) -> A {
     doc.cst().descendants().filter_map(A::cast).next().unwrap()
 }
 
-// fn subtree(node: CstNode, doc: &TomlDoc) -> Vec<CstNode> {
-//     let mut buff = Vec::new();
-//     go(node, doc, &mut buff);
-//     return buff;
-
-//     fn go(node: CstNode, doc: &TomlDoc, buff: &mut Vec<CstNode>) {
-//         buff.push(node);
-//         for child in node.children(doc) {
-//             go(child, doc, buff);
-//         }
-//     }
-// }
-
-// pub fn check_edit(before: &str, after: &str, edit: impl FnOnce(&mut TomlDoc)) {
-//     let mut doc = TomlDoc::new(before);
-//     doc.start_edit();
-//     edit(&mut doc);
-//     let actual = doc.cst().get_text(&doc);
-//     assert_eq_text(after, &actual);
-// }
+pub fn check_edit(before: &str, after: &str, f: impl for<'a> FnOnce(&'a TomlDoc, &mut Edit<'a>)) {
+    let doc = TomlDoc::new(before);
+    let mut edit = doc.start_edit();
+    f(&doc, &mut edit);
+    let doc = edit.finish_edit_no_reparse();
+    let actual = doc.cst().get_text();
+    assert_eq_text(after, &actual);
+}
 
 lazy_static! {
     static ref LOCK: std::sync::Mutex<()> = Mutex::new(());
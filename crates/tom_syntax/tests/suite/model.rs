@@ -1,5 +1,3 @@
-use serde_json;
-
 #[test]
 fn top_level_dotted_keys() {
     do_test(r#"
@@ -55,9 +53,51 @@ color = "gray"
 "#)
 }
 
+#[test]
+fn array_table_subtables() {
+    do_test(r#"
+[[fruit]]
+name = "apple"
+
+[fruit.physical]
+color = "red"
+
+[[fruit.variety]]
+name = "red delicious"
+
+[[fruit]]
+name = "banana"
+"#, r#"
+{
+  "fruit": [
+    {
+      "name": "apple",
+      "physical": { "color": "red" },
+      "variety": [ { "name": "red delicious" } ]
+    },
+    { "name": "banana" }
+  ]
+}
+"#)
+}
+
+#[test]
+fn tables_and_dotted_keys_merge() {
+    do_test(r#"
+[a]
+b.c = 1
+f = { g = 3 }
+
+[a.d]
+e = 2
+"#, r#"
+{ "a": { "b": { "c": 1 }, "d": { "e": 2 }, "f": { "g": 3 } } }
+"#)
+}
+
 #[test]
 fn test_keys() {
-    let doc = ::toml(r"
+    let doc = crate::toml(r"
 foo.bar = 1
 foo.baz = 2
     ");
@@ -66,11 +106,45 @@ foo.baz = 2
     assert_eq!(keys.len(), 2);
 }
 
+#[test]
+fn test_keys_of_tables() {
+    let doc = crate::toml(r"
+[a]
+x = 1
+[a.b]
+[[c]]
+[[c]]
+    ");
+    let model = doc.model();
+
+    let keys = model.get_keys("a");
+    let ranges: Vec<_> = keys.iter().map(|k| k.syntax().range()).collect();
+    assert_eq!(format!("{:?}", ranges), "[[2; 3), [12; 13)]");
+
+    assert_eq!(model.get_keys("c").len(), 2);
+    assert!(model.get_keys("d").is_empty());
+}
+
+#[test]
+fn malformed_keys() {
+    let check = |text: &str, json: &str| {
+        let doc = tom_syntax::TomlDoc::new(text);
+        assert!(!doc.errors().is_empty());
+        let actual: serde_json::Value = serde_json::from_str(&doc.model().to_string()).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(actual, expected);
+    };
+    check("x = 1\n[a.", r#"{ "x": 1 }"#);
+    check("x = 1\n[a.]\nb = 1\n", r#"{ "x": 1 }"#);
+    check("[\"a]\nb=1\n", "{}");
+    check("a. = 1\nb = 2\n", "{}");
+}
+
 fn do_test(toml: &str, json: &str) {
-    let doc = ::toml(toml);
+    let doc = crate::toml(toml);
     let model = doc.model();
     let actual = model.to_string();
     let actual: serde_json::Value = serde_json::from_str(&actual).unwrap();
-    let expected: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let expected: serde_json::Value = serde_json::from_str(json).unwrap();
     assert_eq!(actual, expected);
 }
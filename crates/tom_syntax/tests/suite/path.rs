@@ -0,0 +1,460 @@
+use tom_syntax::{ast, TomlDoc};
+
+use crate::util::assert_eq_text;
+
+const MANIFEST: &str = r#"[package]
+name = "foo"
+metadata.docs = { all-features = true }
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+
+[[bin]]
+name = "a"
+
+[[bin]]
+name = "b"
+"#;
+
+#[test]
+fn get() {
+    let doc = TomlDoc::new(MANIFEST);
+    let get = |path: &str| doc.get(path).map(|it| it.to_string());
+    assert_eq!(get("package.name").as_deref(), Some("\"foo\""));
+    assert_eq!(
+        get("package.metadata.docs.all-features").as_deref(),
+        Some("true")
+    );
+    assert_eq!(
+        get("dependencies.serde.features[0]").as_deref(),
+        Some("\"derive\"")
+    );
+    assert_eq!(get("bin[1].name").as_deref(), Some("\"b\""));
+    assert_eq!(get("\"package\".'name'").as_deref(), Some("\"foo\""));
+    assert_eq!(get("package.version"), None);
+    assert_eq!(get("bin[2].name"), None);
+    assert_eq!(get("package..name"), None);
+    assert_eq!(get("bin[1"), None);
+}
+
+#[test]
+fn escaped_keys() {
+    let mut doc = TomlDoc::new("");
+    doc.set("\"\\n\"", 1).unwrap();
+    doc.set("a.\"\\u00e9\\\"\\\\\"", 2).unwrap();
+    assert_eq_text(
+        "\"\\n\" = 1\n\n[a]\n\"é\\\"\\\\\" = 2\n",
+        &doc.cst().get_text(),
+    );
+    assert_eq!(
+        doc.get("\"\\n\"").map(|it| it.to_string()).as_deref(),
+        Some("1")
+    );
+
+    // The rendered paths of the keys lead back to them.
+    for key in doc.cst().descendants().filter_map(ast::Key::cast) {
+        let path = key.path().unwrap();
+        assert!(doc.get(&path).is_some(), "{}", path);
+    }
+}
+
+#[test]
+fn set_existing() {
+    check_set(
+        "a = 1\nb = { c = 'x' }\n",
+        &[("a", "92"), ("b.c", "y")],
+        "a = 92\nb = { c = \"y\" }\n",
+    );
+    check_set(MANIFEST, &[("bin[0].name", "c")], &MANIFEST.replace("\"a\"", "\"c\""));
+}
+
+#[test]
+fn set_in_section() {
+    check_set(
+        "[dependencies]\nfoo = \"0.1\"\n\n[dev-dependencies]\n",
+        &[
+            ("dependencies.serde.version", "1.0"),
+            ("dev-dependencies.bar", "0.2"),
+        ],
+        r#"[dependencies]
+foo = "0.1"
+serde.version = "1.0"
+
+[dev-dependencies]
+bar = "0.2"
+"#,
+    );
+}
+
+#[test]
+fn set_top_level() {
+    check_set(
+        "a = 1\n\n[table]\nb = 2\n",
+        &[("c", "3")],
+        "a = 1\nc = 3\n\n[table]\nb = 2\n",
+    );
+    check_set(
+        "[table]\nb = 2\n",
+        &[("c", "3")],
+        "c = 3\n\n[table]\nb = 2\n",
+    );
+    check_set("", &[("c", "3")], "c = 3\n");
+}
+
+#[test]
+fn set_creates_tables() {
+    check_set(
+        "a = 1\n",
+        &[("profile.dev.debug", "full")],
+        "a = 1\n\n[profile.dev]\ndebug = \"full\"\n",
+    );
+    check_set(
+        "[target.x.dependencies]\nfoo = \"1\"\n",
+        &[("target.y.dependencies.bar", "2")],
+        r#"[target.x.dependencies]
+foo = "1"
+
+[target.y.dependencies]
+bar = 2
+"#,
+    );
+}
+
+#[test]
+fn set_in_dotted_and_inline_tables() {
+    check_set(
+        "a.b = 1\nc = {}\nd = { x = 1 }\n",
+        &[("a.c", "2"), ("c.x", "y"), ("d.y", "z")],
+        "a.b = 1\na.c = 2\nc = { x = \"y\" }\nd = { x = 1, y = \"z\" }\n",
+    );
+}
+
+#[test]
+fn set_in_array_of_tables() {
+    let expected = MANIFEST.replace("name = \"b\"", "name = \"b\"\npath = \"src/b.rs\"");
+    check_set(MANIFEST, &[("bin[1].path", "src/b.rs")], &expected);
+}
+
+#[test]
+fn set_and_remove_errors() {
+    let mut doc = TomlDoc::new(MANIFEST);
+    let error = |res: Result<_, tom_syntax::PathError>| res.unwrap_err().to_string();
+    assert_eq!(error(doc.set("package", 1)), "`package` is a table");
+    assert_eq!(
+        error(doc.set("package.name.first", 1)),
+        "`package.name` is not a table"
+    );
+    assert_eq!(
+        error(doc.set("bin[2].name", "c")),
+        "index 2 is out of bounds of `bin`, which has 2 elements"
+    );
+    assert_eq!(error(doc.set("package[0]", 1)), "`package` is not an array");
+    assert_eq!(error(doc.set("bin.[0]", 1)), "invalid path: `bin.[0]`");
+    assert_eq!(error(doc.set("bin[1", 1)), "invalid path: `bin[1`");
+    assert_eq!(
+        error(doc.set("package.size", u64::MAX)),
+        "integer is out of range of a 64-bit signed integer"
+    );
+    assert_eq!(error(doc.set("\"\\x\"", 1)), "invalid path: `\"\\x\"`");
+    assert_eq!(
+        error(doc.set("extra[0]", 1)),
+        "`extra[0]` doesn't exist and can't be created"
+    );
+    assert_eq!(doc.cst().get_text(), MANIFEST);
+
+    let broken = "a = \nb = 1\n";
+    let mut doc = TomlDoc::new(broken);
+    assert_eq!(error(doc.set("b", 2)), "the document has errors");
+    let error = doc.remove("b").unwrap_err();
+    assert_eq!(error.to_string(), "the document has errors");
+    assert_eq!(doc.cst().get_text(), broken);
+}
+
+#[test]
+fn remove() {
+    let mut doc = TomlDoc::new(MANIFEST);
+    assert!(doc.remove("package.name").unwrap());
+    assert!(doc.remove("dependencies.serde.version").unwrap());
+    assert!(doc.remove("dependencies.serde.features[0]").unwrap());
+    assert!(doc.remove("bin[0]").unwrap());
+    assert!(!doc.remove("package.version").unwrap());
+    assert_eq_text(
+        r#"[package]
+metadata.docs = { all-features = true }
+
+[dependencies]
+serde = { features = [] }
+
+[[bin]]
+name = "b"
+"#,
+        &doc.cst().get_text(),
+    );
+
+    let mut doc = TomlDoc::new("[a.b]\nx = 1\n\n[a.c]\ny = 2\n\n[d]\n");
+    assert!(doc.remove("a").unwrap());
+    assert_eq_text("[d]\n", &doc.cst().get_text());
+}
+
+#[test]
+fn remove_array_table_element() {
+    let text = "[[bin]]\nname = \"a\"\n\n[bin.test]\nx = 1\n\n[[bin]]\nname = \"b\"\n\n[bin.test]\nx = 2\n";
+    let mut doc = TomlDoc::new(text);
+    assert!(doc.remove("bin[1]").unwrap());
+    assert_eq_text(
+        "[[bin]]\nname = \"a\"\n\n[bin.test]\nx = 1\n",
+        &doc.cst().get_text(),
+    );
+    assert!(doc.errors().is_empty());
+
+    let mut doc = TomlDoc::new(text);
+    assert!(doc.remove("bin[0]").unwrap());
+    assert_eq_text(
+        "[[bin]]\nname = \"b\"\n\n[bin.test]\nx = 2\n",
+        &doc.cst().get_text(),
+    );
+    assert!(doc.errors().is_empty());
+
+    let mut doc = TomlDoc::new(text);
+    doc.set("bin[0].path", "src/a.rs").unwrap();
+    assert_eq_text(
+        &text.replace("name = \"a\"", "name = \"a\"\npath = \"src/a.rs\""),
+        &doc.cst().get_text(),
+    );
+}
+
+#[test]
+fn key_paths() {
+    let doc = TomlDoc::new(
+        r#"top = { x.y = [1, { z = 2 }] }
+
+[a.b]
+c.d = 1
+
+[[servers]]
+host = "a"
+
+[[servers]]
+host = "b"
+tls.cert = "c"
+
+[[servers.routes]]
+path = "/"
+
+[servers.limits]
+"max conns" = 10
+
+[[servers]]
+[[servers.routes]]
+[[servers.routes]]
+path = "/x"
+"#,
+    );
+    let actual: Vec<String> = doc
+        .cst()
+        .descendants()
+        .filter_map(ast::Key::cast)
+        .map(|key| format!("{} {}", key.syntax().get_text(), key.path().unwrap()))
+        .collect();
+    assert_eq_text(
+        r#"top top
+x top.x
+y top.x.y
+z top.x.y[1].z
+a a
+b a.b
+c a.b.c
+d a.b.c.d
+servers servers[0]
+host servers[0].host
+servers servers[1]
+host servers[1].host
+tls servers[1].tls
+cert servers[1].tls.cert
+servers servers[1]
+routes servers[1].routes[0]
+path servers[1].routes[0].path
+servers servers[1]
+limits servers[1].limits
+"max conns" servers[1].limits."max conns"
+servers servers[2]
+servers servers[2]
+routes servers[2].routes[0]
+servers servers[2]
+routes servers[2].routes[1]
+path servers[2].routes[1].path"#,
+        &actual.join("\n"),
+    );
+    for key in doc.cst().descendants().filter_map(ast::Key::cast) {
+        let path = key.path().unwrap();
+        assert!(doc.get(&path).is_some(), "{}", path);
+    }
+    let tables: Vec<String> = doc.table_paths().into_iter().map(|(_, it)| it).collect();
+    assert_eq!(
+        tables,
+        [
+            "a.b",
+            "servers[0]",
+            "servers[1]",
+            "servers[1].routes[0]",
+            "servers[1].limits",
+            "servers[2]",
+            "servers[2].routes[0]",
+            "servers[2].routes[1]",
+        ]
+    );
+
+    let doc = TomlDoc::new("a.b = 1\n[c.]\nd = 1\n");
+    let paths: Vec<Option<String>> = doc
+        .cst()
+        .descendants()
+        .filter_map(ast::Key::cast)
+        .map(|key| key.path())
+        .collect();
+    assert_eq!(paths[..2], [Some("a".to_string()), Some("a.b".to_string())]);
+    assert!(paths[2..].iter().all(|it| it.is_none()));
+    assert!(doc.table_paths().is_empty());
+}
+
+#[test]
+fn get_keys() {
+    let doc = TomlDoc::new(
+        r#"a.b = 1
+
+[a.c]
+x = { y = 1 }
+
+[[a.d]]
+[a.d.e]
+[[a.d]]
+[a.d.e]
+z = 1
+"#,
+    );
+    let text = doc.cst().get_text();
+    // The keys as `line:key`.
+    let keys = |path: &str| -> Vec<String> {
+        doc.get_keys(path)
+            .into_iter()
+            .map(|key| {
+                let start = key.syntax().range().start().to_usize();
+                let line = text[..start].matches('\n').count() + 1;
+                format!("{}:{}", line, key.syntax().get_text())
+            })
+            .collect()
+    };
+    assert_eq!(keys("a"), ["1:a", "3:a", "6:a", "7:a", "8:a", "9:a"]);
+    assert_eq!(keys("a.c.x.y"), ["4:y"]);
+    assert_eq!(keys("a.d"), ["6:d", "7:d", "8:d", "9:d"]);
+    assert_eq!(keys("a.d[1]"), ["8:d", "9:d"]);
+    assert_eq!(keys("a.d[1].e"), ["9:e"]);
+    assert_eq!(keys("a.d[1].e.z"), ["10:z"]);
+    assert!(keys("a.d[2]").is_empty());
+    assert!(keys("a.b.c").is_empty());
+    assert!(keys("a..b").is_empty());
+}
+
+#[test]
+fn rename_key() {
+    let text = r#"server.port = 80
+other = { server = 1 }
+
+[server.tls]
+cert = "b"
+
+[[server.routes]]
+path = "/"
+
+[[server.routes]]
+path = "/x"
+"#;
+    check_rename(
+        text,
+        "[server.|tls]",
+        "backend",
+        r#"server.port = 80
+other = { server = 1 }
+
+[server.backend]
+cert = "b"
+
+[[server.routes]]
+path = "/"
+
+[[server.routes]]
+path = "/x"
+"#,
+    );
+    check_rename(
+        text,
+        "[|server.tls]",
+        "my backend",
+        r#""my backend".port = 80
+other = { server = 1 }
+
+["my backend".tls]
+cert = "b"
+
+[["my backend".routes]]
+path = "/"
+
+[["my backend".routes]]
+path = "/x"
+"#,
+    );
+    check_rename(
+        text,
+        "[[server.|routes]]\npath = \"/x\"",
+        "paths",
+        &text.replace("routes", "paths"),
+    );
+
+    let doc = TomlDoc::new(text);
+    let key = key_at(&doc, "[server.|tls]");
+    let error = doc.rename_key(key, "port").unwrap_err();
+    assert_eq!(error.message(), "`server.port` already exists");
+    assert!(doc.rename_key(key, "tls").is_ok());
+
+    let doc = TomlDoc::new("\"a\\nb\" = 1\nc = 2\n");
+    let error = doc.rename_key(key_at(&doc, "|c"), "a\nb").unwrap_err();
+    assert_eq!(error.message(), "`\"a\\nb\"` already exists");
+
+    let doc = TomlDoc::new("a = 1\nb = ?\n");
+    let error = doc.rename_key(key_at(&doc, "|a"), "c").unwrap_err();
+    assert_eq!(error.message(), "the document has errors");
+}
+
+/// The key right after `|` in the `marker`, found in the text.
+fn key_at<'a>(doc: &'a TomlDoc, marker: &str) -> ast::Key<'a> {
+    let text = doc.cst().get_text();
+    let offset = text.find(&marker.replacen('|', "", 1)).unwrap() + marker.find('|').unwrap();
+    doc.cst()
+        .descendants()
+        .filter_map(ast::Key::cast)
+        .find(|it| it.syntax().range().start().to_usize() == offset)
+        .unwrap()
+}
+
+fn check_rename(text: &str, marker: &str, new_name: &str, after: &str) {
+    let doc = TomlDoc::new(text);
+    let edits = doc.rename_key(key_at(&doc, marker), new_name).unwrap();
+    let mut actual = text.to_string();
+    for edit in edits.iter().rev() {
+        actual = edit.apply(&actual);
+    }
+    assert_eq_text(after, &actual);
+}
+
+fn check_set(before: &str, values: &[(&str, &str)], after: &str) {
+    let mut doc = TomlDoc::new(before);
+    for &(path, value) in values {
+        let res = match value.parse::<i64>() {
+            Ok(value) => doc.set(path, value),
+            Err(_) => doc.set(path, value),
+        };
+        res.unwrap();
+    }
+    assert_eq_text(after, &doc.cst().get_text());
+    let doc = TomlDoc::new(&doc.cst().get_text());
+    assert!(doc.errors().is_empty(), "{:?}", doc.errors());
+}
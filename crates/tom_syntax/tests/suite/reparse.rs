@@ -0,0 +1,128 @@
+use std::fs;
+use lazy_static::lazy_static;
+use proptest::prelude::*;
+use tom_syntax::{TextEdit, TextRange, TextUnit, TomlDoc};
+use crate::util::{assert_eq_text, test_data_dir};
+
+const SAMPLE: &str = r#"# The title
+title = "TOML \"Example\"" # trailing
+
+[owner]
+  name = 'Tom'
+  dob = 1979-05-27T07:32:00-08:00
+  bio = """
+Multi "line"
+"""
+
+# Servers
+[[servers]]
+ip = "10.0.0.1"
+ports = [ 8001, 8002, -0x_7f ]
+limits = { cpu = 1.5, mem = 1e3 } # inline
+"quoted key" = '''
+raw \n'''
+
+[[servers]]
+enabled = true
+"#;
+
+#[test]
+fn relex_token() {
+    check(SAMPLE, "TOML", "Tomly");
+    check(SAMPLE, "ulti \"line", "ultiple\nlines\n\"");
+    check(SAMPLE, "Servers", "All servers");
+    check(SAMPLE, "8001", "80010");
+    check(SAMPLE, "raw", "raw '");
+    check(SAMPLE, "1.5", "1.25");
+}
+
+#[test]
+fn relex_changes_structure() {
+    check(SAMPLE, "TOML", "TOML\"");
+    check(SAMPLE, "Servers", "Servers\nport = 1");
+    check(SAMPLE, "8001", "8001-01-01");
+    check(SAMPLE, "raw", "raw'''");
+    check(SAMPLE, "trailing", "trailing\n[new]");
+}
+
+#[test]
+fn reparse_block() {
+    check(SAMPLE, "name", "full_name");
+    check(SAMPLE, " 8002,", " 8002, 8003,");
+    check(SAMPLE, "cpu = 1.5", "cpu = 2, gpu = 1");
+    check(SAMPLE, "'Tom'\n  dob", "'Tom'\n  age = 42\n  dob");
+    check(SAMPLE, "ip = \"10.0.0.1\"\n", "ip = \"10.0.0.1\"\n\n# about ports\n");
+    check(SAMPLE, "owner", "owner.pet");
+}
+
+#[test]
+fn reparse_block_changes_structure() {
+    check(SAMPLE, "= 'Tom'", "= ");
+    check(SAMPLE, "ip = \"10.0.0.1\"\n", "ip = \"10.0.0.1\"\n[other]\n");
+    check(SAMPLE, "'Tom'\n", "'Tom'\n\n");
+    check(SAMPLE, "[[servers]]\nip", "[[servers]]\n# ip\nip");
+    check(SAMPLE, "enabled = true", "enabled = true\n[[servers]]");
+    check("a = 1\nb = [\n", "= 1", "= 11");
+}
+
+proptest! {
+    #[test]
+    fn reparse_is_the_same_as_full_parse(
+        text in prop::sample::select(CORPUS.clone()),
+        edits in prop::collection::vec(
+            (any::<prop::sample::Index>(), 0..4usize, "[a-z0-9 =\n\"'#\\[\\]{},.:-]{0,4}"),
+            1..8,
+        ),
+    ) {
+        let mut doc = TomlDoc::new(&text);
+        for (offset, len, insert) in edits {
+            let text = doc.cst().get_text();
+            let start = char_boundary(&text, offset.index(text.len() + 1));
+            let end = char_boundary(&text, (start + len).min(text.len()));
+            let edit = TextEdit::replace(range(start, end), insert);
+            let expected = TomlDoc::new(&edit.apply(&text));
+            doc = doc.reparse(edit);
+            prop_assert_eq!(doc.debug(), expected.debug());
+        }
+    }
+}
+
+lazy_static! {
+    static ref CORPUS: Vec<String> = {
+        let mut corpus = vec![SAMPLE.to_string()];
+        let dir = test_data_dir().join("ok");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|it| it == "toml") {
+                corpus.push(fs::read_to_string(path).unwrap());
+            }
+        }
+        corpus.sort();
+        corpus
+    };
+}
+
+/// Replaces the first occurrence of `from` and checks that the reparsed
+/// document is the same as the one parsed from scratch.
+fn check(text: &str, from: &str, to: &str) {
+    let doc = TomlDoc::new(text);
+    let start = text.find(from).unwrap();
+    let edit = TextEdit::replace(range(start, start + from.len()), to);
+    let expected = TomlDoc::new(&edit.apply(text));
+    let actual = doc.reparse(edit);
+    assert_eq_text(&expected.debug(), &actual.debug());
+}
+
+fn range(start: usize, end: usize) -> TextRange {
+    TextRange::from_to(
+        TextUnit::from(start as u32),
+        TextUnit::from(end as u32),
+    )
+}
+
+fn char_boundary(text: &str, mut offset: usize) -> usize {
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
@@ -0,0 +1,466 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tom_syntax::{de, ser, TomlDoc};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    package: Package,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bin: Vec<Target>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Package {
+    name: String,
+    version: String,
+    description: Option<String>,
+    edition: Option<String>,
+    authors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Target {
+    name: String,
+    path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Dependency {
+    Version(String),
+    Detailed {
+        version: String,
+        optional: Option<bool>,
+    },
+}
+
+fn check<T>(before: &str, after: &str, f: impl FnOnce(&mut T))
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let mut doc = TomlDoc::new(before);
+    let mut value: T = de::from_doc(&doc).unwrap();
+    f(&mut value);
+    ser::update_doc(&mut doc, &value).unwrap();
+    assert_eq!(doc.cst().get_text(), after);
+    assert!(doc.errors().is_empty(), "{:?}", doc.errors());
+}
+
+const MANIFEST: &str = r#"# The manifest.
+[package]
+name    = "tom" # the name
+version = '0.1.0'
+authors = [
+    "Aleksey Kladov",
+]
+
+[dependencies]
+log = "0.4"
+serde = { version = "1.0" }
+"#;
+
+#[test]
+fn update_nothing() {
+    check::<Manifest>(MANIFEST, MANIFEST, |_| ());
+}
+
+#[test]
+fn update_values() {
+    check::<Manifest>(
+        MANIFEST,
+        r#"# The manifest.
+[package]
+name    = "tom-syntax" # the name
+version = '0.2.0'
+authors = [
+    "Aleksey Kladov",
+]
+
+[dependencies]
+log = "0.4"
+serde = { version = "1.0", optional = true }
+"#,
+        |manifest| {
+            manifest.package.name = "tom-syntax".to_string();
+            manifest.package.version = "0.2.0".to_string();
+            manifest.dependencies.insert(
+                "serde".to_string(),
+                Dependency::Detailed {
+                    version: "1.0".to_string(),
+                    optional: Some(true),
+                },
+            );
+        },
+    );
+}
+
+#[test]
+fn update_array() {
+    check::<Manifest>(
+        MANIFEST,
+        r#"# The manifest.
+[package]
+name    = "tom" # the name
+version = '0.1.0'
+authors = [
+    "matklad",
+]
+
+[dependencies]
+log = "0.4"
+serde = { version = "1.0" }
+"#,
+        |manifest| manifest.package.authors[0] = "matklad".to_string(),
+    );
+    check::<Manifest>(
+        MANIFEST,
+        r#"# The manifest.
+[package]
+name    = "tom" # the name
+version = '0.1.0'
+authors = ["Aleksey Kladov", "matklad"]
+
+[dependencies]
+log = "0.4"
+serde = { version = "1.0" }
+"#,
+        |manifest| manifest.package.authors.push("matklad".to_string()),
+    );
+}
+
+#[test]
+fn insert_next_to_related_keys() {
+    check::<Manifest>(
+        MANIFEST,
+        r#"# The manifest.
+[package]
+name    = "tom" # the name
+version = '0.1.0'
+description = "Yet another TOML parser"
+edition = "2018"
+authors = [
+    "Aleksey Kladov",
+]
+
+[dependencies]
+failure = "0.1"
+log = "0.4"
+serde = { version = "1.0" }
+"#,
+        |manifest| {
+            manifest.package.description = Some("Yet another TOML parser".to_string());
+            manifest.package.edition = Some("2018".to_string());
+            manifest.dependencies.insert(
+                "failure".to_string(),
+                Dependency::Version("0.1".to_string()),
+            );
+        },
+    );
+}
+
+#[test]
+fn insert_tables() {
+    check::<Manifest>(
+        "[package]\nname = \"tom\"\nversion = \"0.1.0\"\nauthors = []\n",
+        r#"[package]
+name = "tom"
+version = "0.1.0"
+authors = []
+
+[[bin]]
+name = "tom"
+
+[[bin]]
+name = "tomfmt"
+path = "src/fmt.rs"
+
+[dependencies."log 2"]
+version = "0.4"
+optional = false
+"#,
+        |manifest| {
+            manifest.bin.push(Target {
+                name: "tom".to_string(),
+                path: None,
+            });
+            manifest.bin.push(Target {
+                name: "tomfmt".to_string(),
+                path: Some("src/fmt.rs".to_string()),
+            });
+            manifest.dependencies.insert(
+                "log 2".to_string(),
+                Dependency::Detailed {
+                    version: "0.4".to_string(),
+                    optional: Some(false),
+                },
+            );
+        },
+    );
+}
+
+#[test]
+fn update_array_of_tables() {
+    let before = r#"[package]
+name = "tom"
+version = "0.1.0"
+authors = []
+
+[[bin]]
+name = "tom" # main
+
+[[bin]]
+name = "tomfmt"
+path = "src/fmt.rs"
+
+[dependencies]
+"#;
+    check::<Manifest>(
+        before,
+        r#"[package]
+name = "tom"
+version = "0.1.0"
+authors = []
+
+[[bin]]
+name = "tom" # main
+path = "src/main.rs"
+
+[[bin]]
+name = "tomfmt"
+
+[[bin]]
+name = "tomlint"
+
+[dependencies]
+"#,
+        |manifest| {
+            manifest.bin[0].path = Some("src/main.rs".to_string());
+            manifest.bin[1].path = None;
+            manifest.bin.push(Target {
+                name: "tomlint".to_string(),
+                path: None,
+            });
+        },
+    );
+    check::<Manifest>(
+        before,
+        r#"[package]
+name = "tom"
+version = "0.1.0"
+authors = []
+
+[[bin]]
+name = "tom" # main
+
+[dependencies]
+"#,
+        |manifest| {
+            manifest.bin.pop();
+        },
+    );
+}
+
+#[test]
+fn remove_none() {
+    check::<Package>(
+        "name = \"tom\"\n  # docs\n  description = \"TOML\"\nversion = \"0.1.0\"\nauthors = []\n",
+        "name = \"tom\"\nversion = \"0.1.0\"\nauthors = []\n",
+        |package| package.description = None,
+    );
+}
+
+#[test]
+fn keep_missing_keys() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        package: Target,
+        bin: Target,
+    }
+
+    check::<Config>(
+        "[package]\nname = \"a\"\nlicense = \"MIT\"\n\n[bin]\nname = \"b\"\nlicense = \"MIT\"\n",
+        "[package]\nname = \"c\"\npath = \"src/c.rs\"\nlicense = \"MIT\"\n\n[bin]\nname = \"b\"\nlicense = \"MIT\"\n",
+        |config| {
+            config.package.name = "c".to_string();
+            config.package.path = Some("src/c.rs".to_string());
+        },
+    );
+    check::<Config>(
+        "package = { name = \"a\", license = \"MIT\" }\nbin = { name = 'b', path = \"b.rs\", license = \"MIT\" }\n",
+        "package = { name = \"c\", path = \"src/c.rs\", license = \"MIT\" }\nbin = { name = 'd', license = \"MIT\" }\n",
+        |config| {
+            config.package.name = "c".to_string();
+            config.package.path = Some("src/c.rs".to_string());
+            config.bin.name = "d".to_string();
+            config.bin.path = None;
+        },
+    );
+}
+
+#[test]
+fn dotted_keys_and_styles() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        server: Server,
+        released: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Server {
+        host: String,
+        port: u16,
+        timeout: Option<f64>,
+    }
+
+    check::<Config>(
+        "server.host = 'localhost'\nreleased = 1979-05-27\nserver.port = 80\n",
+        "server.host = 'example.com'\nreleased = 2020-01-01\nserver.port = 8080\nserver.timeout = 2.5\n",
+        |config| {
+            config.server.host = "example.com".to_string();
+            config.server.port = 8080;
+            config.server.timeout = Some(2.5);
+            config.released = "2020-01-01".to_string();
+        },
+    );
+}
+
+#[test]
+fn implicit_tables() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        a: A,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct A {
+        x: Option<i64>,
+        y: Option<i64>,
+        b: BTreeMap<String, i64>,
+    }
+
+    check::<Config>(
+        "[a.b]\nc = 1\n",
+        "[a]\nx = 1\ny = 2\n\n[a.b]\nc = 1\n",
+        |config| {
+            config.a.x = Some(1);
+            config.a.y = Some(2);
+        },
+    );
+}
+
+#[test]
+fn replace_sections() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Config {
+        a: A,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct A {
+        x: i64,
+        b: Vec<BTreeMap<String, i64>>,
+    }
+
+    let mut doc = TomlDoc::new("[a]\nx = 1\n\n[a.b]\ny = 1\n\n[a.b.c]\nz = 1\n");
+    let mut element = BTreeMap::new();
+    element.insert("y".to_string(), 2);
+    let config = Config {
+        a: A {
+            x: 1,
+            b: vec![element],
+        },
+    };
+    ser::update_doc(&mut doc, &config).unwrap();
+    assert_eq!(doc.cst().get_text(), "[a]\nx = 1\n\n[[a.b]]\ny = 2\n");
+    assert!(doc.errors().is_empty(), "{:?}", doc.errors());
+}
+
+#[test]
+fn replace_tables_with_values() {
+    let check = |before: &str, value: serde_json::Value, after: &str| {
+        let mut doc = TomlDoc::new(before);
+        ser::update_doc(&mut doc, &value).unwrap();
+        assert_eq!(doc.cst().get_text(), after);
+        assert!(doc.errors().is_empty(), "{:?}", doc.errors());
+    };
+
+    check("[a]\nx = 1\n[a.b]\ny = 1\n", json!({ "a": 5 }), "a = 5\n");
+    check("[a.b]\nx = 1\n", json!({ "a": { "b": 3 } }), "[a]\nb = 3\n");
+    check(
+        "c = 1\n\n[a]\nx = 1\n",
+        json!({ "a": 5, "c": 1 }),
+        "a = 5\nc = 1\n",
+    );
+    check("a.x = 1\n[a.b]\ny = 1\n", json!({ "a": 5 }), "a = 5\n");
+    check(
+        "a.x = 1\nb = 2\n",
+        json!({ "a": 5, "b": 2, "c": 3 }),
+        "a = 5\nb = 2\nc = 3\n",
+    );
+}
+
+#[test]
+fn merge_overlapping_changes() {
+    #[derive(Serialize)]
+    struct Fields {
+        a: Option<i64>,
+        b: Option<i64>,
+        c: Option<i64>,
+    }
+    fn check(before: &str, value: &impl Serialize, after: &str) {
+        let mut doc = TomlDoc::new(before);
+        ser::update_doc(&mut doc, value).unwrap();
+        assert_eq!(doc.cst().get_text(), after);
+        assert!(doc.errors().is_empty(), "{:?}", doc.errors());
+    }
+
+    let fields = |a, b, c| Fields { a, b, c };
+    check("[a]\n[b]\n", &fields(None, None, None), "");
+    check(
+        "[a]\nx = 1\n\n[b]\ny = 2\n\nc = 1\n",
+        &fields(None, None, Some(1)),
+        "c = 1\n",
+    );
+    check(
+        "[a]\nx = 1\n[b]\ny = 2\n",
+        &json!({ "a": 5, "b": 6 }),
+        "a = 5\nb = 6\n",
+    );
+    check("a.b.c = 1\n", &json!({ "a": { "b": 5 } }), "a.b = 5\n");
+    check(
+        "a = 1 # c\nb = 2\n",
+        &fields(None, None, Some(3)),
+        "c = 3\n",
+    );
+    check(
+        "a = 1\nb = 2\nc = 3\n",
+        &fields(Some(1), None, Some(3)),
+        "a = 1\nc = 3\n",
+    );
+}
+
+#[test]
+fn unsupported_values() {
+    let mut doc = TomlDoc::new("");
+    let err = ser::update_doc(&mut doc, &92).unwrap_err();
+    assert_eq!(
+        err.message(),
+        "only structs and maps can be serialized as a document"
+    );
+    let mut map = BTreeMap::new();
+    map.insert("big", u64::MAX);
+    let err = ser::update_doc(&mut doc, &map).unwrap_err();
+    assert_eq!(
+        err.message(),
+        "integer is out of range of a 64-bit signed integer"
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("name", "tom");
+    ser::update_doc(&mut doc, &map).unwrap();
+    assert_eq!(doc.cst().get_text(), "name = \"tom\"\n");
+}
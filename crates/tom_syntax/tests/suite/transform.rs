@@ -0,0 +1,213 @@
+use tom_syntax::ast;
+use crate::{check_edit, find};
+
+#[test]
+fn sort_entries() {
+    check_edit(
+        r#"
+c = 3
+# about b
+b = 2 # two
+a.y = 1
+a.x = 1
+
+[t]
+z = { y = 2, x = 1 }
+y = 1
+"#,
+        r#"
+a.x = 1
+a.y = 1
+# about b
+b = 2 # two
+c = 3
+
+[t]
+z = { y = 2, x = 1 }
+y = 1
+"#,
+        |doc, edit| edit.sort_entries(doc.ast(), false),
+    );
+    check_edit(
+        "[t]\nc = 3\nb = 2\n'a' = 1\n\n[u]\nb = 1\na = 1\n",
+        "[t]\n'a' = 1\nb = 2\nc = 3\n\n[u]\nb = 1\na = 1\n",
+        |doc, edit| edit.sort_entries(doc.ast().tables().next().unwrap(), false),
+    );
+    check_edit(
+        "a = { c = 3, b = 2, a = 1 }\n",
+        "a = { a = 1, b = 2, c = 3 }\n",
+        |doc, edit| edit.sort_entries(find::<ast::Dict>(doc), false),
+    );
+}
+
+#[test]
+fn sort_entries_by_groups() {
+    let before = r#"[dependencies]
+serde = "1.0"
+log = "0.4"
+
+# internal
+tom = { path = "tom" }
+ast = { path = "ast" }
+# -------------------
+
+b = "1.0"
+a = "1.0"
+"#;
+    check_edit(
+        before,
+        r#"[dependencies]
+log = "0.4"
+serde = "1.0"
+
+ast = { path = "ast" }
+# internal
+tom = { path = "tom" }
+# -------------------
+
+a = "1.0"
+b = "1.0"
+"#,
+        |doc, edit| edit.sort_entries(doc.ast().tables().next().unwrap(), true),
+    );
+    check_edit(
+        before,
+        r#"[dependencies]
+a = "1.0"
+ast = { path = "ast" }
+
+b = "1.0"
+log = "0.4"
+# -------------------
+
+serde = "1.0"
+# internal
+tom = { path = "tom" }
+"#,
+        |doc, edit| edit.sort_entries(doc.ast().tables().next().unwrap(), false),
+    );
+}
+
+#[test]
+fn sort_values() {
+    check_edit(
+        r#"a = ["c", 'b', "a"]"#,
+        r#"a = ["a", 'b', "c"]"#,
+        |doc, edit| edit.sort_values(find(doc)),
+    );
+    check_edit(
+        "a = [\n  10, # ten\n  9,\n  1e1,\n  inf,\n  -1,\n]\n",
+        "a = [\n  -1, # ten\n  9,\n  10,\n  1e1,\n  inf,\n]\n",
+        |doc, edit| edit.sort_values(find(doc)),
+    );
+    check_edit(
+        "a = [[2], \"s\", 1979-05-27, 1, true, [1], false]\n",
+        "a = [false, true, 1, 1979-05-27, \"s\", [1], [2]]\n",
+        |doc, edit| edit.sort_values(find(doc)),
+    );
+    check_edit(
+        "a = [1979-05-27T07:32:00Z, 1979-05-27T07:32:00+05:30, 1979-05-27 07:00:00, \
+         1979-05-27t06:00:00, 1979-05-26, 2000-02-29, 1999-12-31]\n",
+        "a = [1979-05-27T07:32:00+05:30, 1979-05-27T07:32:00Z, 1979-05-27t06:00:00, \
+         1979-05-27 07:00:00, 1979-05-26, 1999-12-31, 2000-02-29]\n",
+        |doc, edit| edit.sort_values(find(doc)),
+    );
+}
+
+#[test]
+fn reorder_tables() {
+    let before = r#"top = 1
+
+[dev-dependencies]
+a = 1
+
+# the crate
+[package]
+name = "tom"
+
+[dependencies.serde]
+version = "1.0"
+
+[[bin]]
+name = "a"
+
+[bin.x]
+y = 1
+
+[dependencies]
+b = 1
+"#;
+    check_edit(
+        before,
+        r#"top = 1
+
+# the crate
+[package]
+name = "tom"
+
+[dependencies.serde]
+version = "1.0"
+
+[dependencies]
+b = 1
+
+[dev-dependencies]
+a = 1
+
+[[bin]]
+name = "a"
+
+[bin.x]
+y = 1
+"#,
+        |_, edit| {
+            edit.reorder_tables(&[
+                "package",
+                "dependencies",
+                "dependencies.serde",
+                "dev-dependencies",
+            ])
+            .unwrap()
+        },
+    );
+    check_edit(
+        before,
+        r#"top = 1
+
+# the crate
+[package]
+name = "tom"
+
+[dev-dependencies]
+a = 1
+
+[dependencies.serde]
+version = "1.0"
+
+[[bin]]
+name = "a"
+
+[bin.x]
+y = 1
+
+[dependencies]
+b = 1
+"#,
+        |_, edit| edit.reorder_tables(&["package", "bin.x"]).unwrap(),
+    );
+    check_edit(before, before, |_, edit| {
+        let error = edit.reorder_tables(&["bin[0]"]).unwrap_err();
+        assert_eq!(error.message(), "invalid table key: `bin[0]`");
+    });
+
+    // `[a.b.d]` can't go before the dotted keys which define `a.b`.
+    check_edit(
+        "[a]\nb.c = 1\n[a.b.d]\n[e]\n",
+        "[e]\n[a]\nb.c = 1\n[a.b.d]\n",
+        |_, edit| edit.reorder_tables(&["a.b.d", "e", "a"]).unwrap(),
+    );
+    let text = "[a]\nb.c = 1\n[a.b.d]\n";
+    check_edit(text, text, |_, edit| {
+        edit.reorder_tables(&["a.b.d"]).unwrap()
+    });
+}
@@ -1,6 +1,6 @@
 import * as vscode from 'vscode';
 
-import { Range, TextDocumentIdentifier } from 'vscode-languageclient';
+import { Range, TextDocumentIdentifier } from 'vscode-languageclient/node';
 import { Server } from '../server';
 
 interface ExtendSelectionParams {
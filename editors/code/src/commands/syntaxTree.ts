@@ -1,5 +1,5 @@
 import * as vscode from 'vscode';
-import { TextDocumentIdentifier } from 'vscode-languageclient';
+import { TextDocumentIdentifier } from 'vscode-languageclient/node';
 
 import { Server } from '../server';
 
@@ -1,4 +1,3 @@
-import * as changeActiveTextEditor from './change_active_text_editor';
 import * as changeTextDocument from './change_text_document';
 
-export { changeActiveTextEditor, changeTextDocument };
+export { changeTextDocument };
@@ -1,10 +1,9 @@
 import * as vscode from 'vscode';
-import * as lc from 'vscode-languageclient';
+import * as lc from 'vscode-languageclient/node';
 
 import * as commands from './commands';
 import { TextDocumentContentProvider } from './commands/syntaxTree';
 import * as events from './events';
-import * as notifications from './notifications';
 import { Server } from './server';
 
 export function activate(context: vscode.ExtensionContext) {
@@ -24,12 +23,7 @@ export function activate(context: vscode.ExtensionContext) {
     const allNotifications: Iterable<[
         string,
         lc.GenericNotificationHandler
-    ]> = [['m/publishDecorations', notifications.publishDecorations.handle]];
-
-    // The events below are plain old javascript events, triggered and handled by vscode
-    vscode.window.onDidChangeActiveTextEditor(
-        events.changeActiveTextEditor.handle
-    );
+    ]> = [];
 
     const textDocumentContentProvider = new TextDocumentContentProvider();
     disposeOnDeactivation(
@@ -39,6 +33,7 @@ export function activate(context: vscode.ExtensionContext) {
         )
     );
 
+    // The events below are plain old javascript events, triggered and handled by vscode
     vscode.workspace.onDidChangeTextDocument(
         events.changeTextDocument.createHandler(textDocumentContentProvider),
         null,
@@ -1,3 +0,0 @@
-import * as publishDecorations from './publish_decorations';
-
-export { publishDecorations };
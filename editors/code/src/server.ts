@@ -1,11 +1,6 @@
-import * as lc from 'vscode-languageclient';
-
-import { Config } from './config';
-import { Highlighter } from './highlighting';
+import * as lc from 'vscode-languageclient/node';
 
 export class Server {
-    public static highlighter = new Highlighter();
-    public static config = new Config();
     public static client: lc.LanguageClient;
 
     public static start(
@@ -48,6 +48,7 @@ fn descr() -> Vec<AstNode> {
         n("Number").text(),
         n("Bool").text(),
         n("DateTime").text(),
+        n("Comment").text(),
     ]
 }
 
@@ -62,12 +63,7 @@ struct AstNode {
 impl AstNode {
     fn methods(mut self, names: &[&'static str]) -> AstNode {
         self.methods.extend(names.iter().map(|&name| {
-            let type_name = if name.ends_with('s') {
-                &name[..name.len() - 1]
-            } else {
-                name
-            }
-            .to_camel_case();
+            let type_name = name.strip_suffix('s').unwrap_or(name).to_camel_case();
             Method {
                 name,
                 type_name,
@@ -205,7 +201,7 @@ fn ast_source_code() -> String {
         ln!("impl<'a> {}<'a> {{", n.name);
         {
             ln!(
-                "pub fn cast(node: SyntaxNodeRef<'a>) -> Option<{}> {{",
+                "pub fn cast(node: SyntaxNodeRef<'a>) -> Option<{}<'a>> {{",
                 n.name
             );
             {
@@ -20,8 +20,7 @@ pub fn gen_parser_tests(mode: codegen::Mode) -> Result<()> {
         let path = file?.path();
         let stem = path.file_stem().unwrap();
         let name = stem.to_str().unwrap();
-        if name.starts_with("test-") {
-            let name = &name["test-".len()..];
+        if let Some(name) = name.strip_prefix("test-") {
             if !tests.contains_key(name) {
                 panic!(
                     "File `{}` exists, but no inline test found.",
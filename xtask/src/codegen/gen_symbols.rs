@@ -45,8 +45,6 @@ MULTILINE_BASIC_STRING
 LITERAL_STRING
 MULTILINE_LITERAL_STRING
 DATE_TIME
-BARE_KEY_OR_NUMBER
-BARE_KEY_OR_DATE
 EOF
 ";
     ln!("use super::{{SymbolInfo, Symbol, NonZeroU8}};");
@@ -61,7 +59,7 @@ EOF
         let name = format!("{}: Symbol", s);
         let vis = if name == "EOF" { "(crate)" } else { "" };
         ln!(
-            r#"pub{} const {} = Symbol(unsafe {{ NonZeroU8::new_unchecked({}) }});"#,
+            r#"pub{} const {} = Symbol(NonZeroU8::new({}).unwrap());"#,
             vis,
             name,
             i + 1
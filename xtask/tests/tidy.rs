@@ -29,14 +29,13 @@ fn generated_tests_are_fresh() {
 fn rust_files_are_tidy() {
     let mut tidy_docs = TidyDocs::default();
 
-    let rust_source_roots = vec![
+    let rust_source_roots = [
         project_root_dir().join("crates"),
         project_root_dir().join("xtask"),
     ];
     let rust_files = rust_source_roots
         .iter()
-        .map(|it| find_rust_files_recursively(&it))
-        .flatten();
+        .flat_map(|it| find_rust_files_recursively(it));
 
     for path in rust_files {
         let text = fs2::read_to_string(&path).unwrap();
@@ -111,7 +110,7 @@ impl TidyDocs {
             d.file_name()
                 .unwrap_or_default()
                 .to_str()
-                .map(|f_n| file_names.iter().any(|name| *name == f_n))
+                .map(|f_n| file_names.contains(&f_n))
                 .unwrap_or(false)
         }
     }